@@ -0,0 +1,55 @@
+//! Compiles the GLSL shaders in `shaders` to SPIR-V in `OUT_DIR`, e.g. `ui.vert` to
+//! `ui.vert.spv`, where `include_shader!` picks them up. Requires glslc from the Vulkan
+//! SDK, or the one the `GLSLC` environment variable points to.
+
+use std::{env, ffi::OsStr, fs, io::ErrorKind, path::Path, process::Command};
+
+const SHADER_DIRECTORY: &str = "shaders";
+const SHADER_EXTENSIONS: [&str; 3] = ["vert", "frag", "comp"];
+
+fn main() {
+    println!("cargo::rerun-if-changed={SHADER_DIRECTORY}");
+    println!("cargo::rerun-if-env-changed=GLSLC");
+    let glslc = env::var_os("GLSLC").unwrap_or_else(|| "glslc".into());
+    let out_dir = env::var_os("OUT_DIR").expect("cargo sets OUT_DIR");
+
+    let mut sources = fs::read_dir(SHADER_DIRECTORY)
+        .expect("failed to read the shader directory")
+        .map(|entry| entry.expect("failed to read the shader directory").path())
+        .filter(|path| {
+            let extension = path.extension().and_then(OsStr::to_str);
+            extension.is_some_and(|extension| SHADER_EXTENSIONS.contains(&extension))
+        })
+        .collect::<Vec<_>>();
+    sources.sort();
+
+    let mut glslc_missing = false;
+    for source in sources {
+        let name = source.file_name().unwrap().to_string_lossy();
+        let output = Path::new(&out_dir).join(format!("{name}.spv"));
+        if !glslc_missing {
+            let status = Command::new(&glslc)
+                .arg("--target-env=vulkan1.3")
+                .arg("-I")
+                .arg(SHADER_DIRECTORY)
+                .arg(&source)
+                .arg("-o")
+                .arg(&output)
+                .status();
+            match status {
+                Ok(status) if status.success() => continue,
+                Ok(status) => panic!("glslc failed to compile {} ({status})", source.display()),
+                Err(err) if err.kind() == ErrorKind::NotFound => {
+                    println!(
+                        "cargo::warning=glslc was not found, shaders are left empty and the engine \
+                         fails to create its pipelines. Install the Vulkan SDK or set GLSLC."
+                    );
+                    glslc_missing = true;
+                }
+                Err(err) => panic!("failed to run glslc: {err}"),
+            }
+        }
+        // The crate still has to build, `load_shader_module` reports the empty shader.
+        fs::write(&output, []).expect("failed to write the shader placeholder");
+    }
+}
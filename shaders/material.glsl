@@ -0,0 +1,25 @@
+// Material parameters and the per draw push constants of the material pipelines.
+// Must match `GpuMaterialParameters` and `DrawPushConstants` in src/engine/materials.
+
+const uint TEMPLATE_OPAQUE = 0;
+const uint TEMPLATE_ALPHA_MASKED = 1;
+const uint TEMPLATE_TRANSPARENT = 2;
+
+struct Material {
+    vec4 base_color_factor;
+    // rgb: emissive factor
+    vec4 emissive_factor;
+    // x: metallic, y: roughness, z: alpha cutoff, w: normal scale
+    vec4 metallic_roughness;
+    // x: occlusion strength, y: template
+    vec4 extra;
+};
+
+layout(std430, set = 1, binding = 0) readonly buffer Materials {
+    Material materials[];
+};
+
+layout(push_constant) uniform PushConstants {
+    mat4 model;
+    uint material_index;
+} push_constants;
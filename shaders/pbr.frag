@@ -0,0 +1,63 @@
+#version 450
+
+#include "scene_data.glsl"
+#include "material.glsl"
+#include "pbr.glsl"
+
+layout(set = 2, binding = 0) uniform sampler2D base_color_texture;
+// g: roughness, b: metallic, as in glTF
+layout(set = 2, binding = 1) uniform sampler2D metallic_roughness_texture;
+layout(set = 2, binding = 2) uniform sampler2D normal_texture;
+layout(set = 2, binding = 3) uniform sampler2D occlusion_texture;
+layout(set = 2, binding = 4) uniform sampler2D emissive_texture;
+
+layout(location = 0) in vec3 in_world_position;
+layout(location = 1) in vec3 in_normal;
+layout(location = 2) in vec2 in_uv;
+layout(location = 3) in vec4 in_tangent;
+
+layout(location = 0) out vec4 out_color;
+
+vec3 surface_normal(float normal_scale) {
+    vec3 n = normalize(in_normal);
+    // Meshes without tangents leave them zero and skip normal mapping.
+    if (dot(in_tangent.xyz, in_tangent.xyz) < 1e-8) {
+        return n;
+    }
+    vec3 t = normalize(in_tangent.xyz - n * dot(n, in_tangent.xyz));
+    vec3 b = cross(n, t) * in_tangent.w;
+    vec3 tangent_normal = texture(normal_texture, in_uv).xyz * 2.0 - 1.0;
+    tangent_normal.xy *= normal_scale;
+    return normalize(mat3(t, b, n) * tangent_normal);
+}
+
+void main() {
+    Material material = materials[push_constants.material_index];
+    uint template_index = uint(material.extra.y);
+
+    vec4 base_color = material.base_color_factor * texture(base_color_texture, in_uv);
+    if (template_index == TEMPLATE_ALPHA_MASKED && base_color.a < material.metallic_roughness.z) {
+        discard;
+    }
+
+    vec4 metallic_roughness_sample = texture(metallic_roughness_texture, in_uv);
+    float metallic = clamp(material.metallic_roughness.x * metallic_roughness_sample.b, 0.0, 1.0);
+    float roughness = clamp(material.metallic_roughness.y * metallic_roughness_sample.g, MIN_ROUGHNESS, 1.0);
+    vec3 diffuse_color = base_color.rgb * (1.0 - metallic);
+    vec3 f0 = mix(vec3(0.04), base_color.rgb, metallic);
+
+    vec3 n = surface_normal(material.metallic_roughness.w);
+    vec3 v = normalize(scene_data.camera_position.xyz - in_world_position);
+    vec3 l = -normalize(scene_data.light_direction.xyz);
+    vec3 light_radiance = scene_data.light_color.rgb * scene_data.light_direction.w;
+    vec3 color = brdf(n, v, l, diffuse_color, f0, roughness) * light_radiance * max(dot(n, l), 0.0);
+
+    float occlusion = mix(1.0, texture(occlusion_texture, in_uv).r, material.extra.x);
+    vec3 ambient = scene_data.ambient_color.rgb * scene_data.ambient_color.a;
+    color += ambient_lighting(n, v, diffuse_color, f0, roughness, ambient) * occlusion;
+    color += material.emissive_factor.rgb * texture(emissive_texture, in_uv).rgb;
+
+    float alpha = template_index == TEMPLATE_TRANSPARENT ? base_color.a : 1.0;
+    // Linear HDR, tonemapped and sRGB encoded by tonemap.comp.
+    out_color = vec4(color, alpha);
+}
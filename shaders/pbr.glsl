@@ -0,0 +1,67 @@
+// Metallic-roughness shading shared by the material pipelines, following the glTF 2.0
+// BRDF: GGX distribution, height-correlated Smith visibility and Schlick Fresnel.
+
+const float PI = 3.14159265359;
+// Below this GGX highlights turn into aliased single pixels.
+const float MIN_ROUGHNESS = 0.045;
+
+float distribution_ggx(float n_dot_h, float alpha) {
+    float alpha_squared = alpha * alpha;
+    float denominator = n_dot_h * n_dot_h * (alpha_squared - 1.0) + 1.0;
+    return alpha_squared / (PI * denominator * denominator);
+}
+
+float visibility_smith_ggx_correlated(float n_dot_v, float n_dot_l, float alpha) {
+    float alpha_squared = alpha * alpha;
+    float ggx_v = n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - alpha_squared) + alpha_squared);
+    float ggx_l = n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - alpha_squared) + alpha_squared);
+    return 0.5 / max(ggx_v + ggx_l, 1e-5);
+}
+
+vec3 fresnel_schlick(vec3 f0, float v_dot_h) {
+    return f0 + (1.0 - f0) * pow(1.0 - v_dot_h, 5.0);
+}
+
+// Cook-Torrance specular plus Lambertian diffuse for a single light, without the
+// light's radiance and n_dot_l.
+vec3 brdf(vec3 n, vec3 v, vec3 l, vec3 diffuse_color, vec3 f0, float roughness) {
+    vec3 h = normalize(v + l);
+    float n_dot_v = max(dot(n, v), 1e-4);
+    float n_dot_l = max(dot(n, l), 0.0);
+    float n_dot_h = max(dot(n, h), 0.0);
+    float v_dot_h = max(dot(v, h), 0.0);
+    float alpha = roughness * roughness;
+
+    vec3 fresnel = fresnel_schlick(f0, v_dot_h);
+    vec3 specular = fresnel * distribution_ggx(n_dot_h, alpha) * visibility_smith_ggx_correlated(n_dot_v, n_dot_l, alpha);
+    vec3 diffuse = (1.0 - fresnel) * diffuse_color / PI;
+    return diffuse + specular;
+}
+
+// Analytic fit of the split-sum environment BRDF (Karis, "Physically Based Shading on
+// Mobile"), stands in for a lookup table until there is one.
+vec3 environment_brdf(vec3 f0, float roughness, float n_dot_v) {
+    const vec4 c0 = vec4(-1.0, -0.0275, -0.572, 0.022);
+    const vec4 c1 = vec4(1.0, 0.0425, 1.04, -0.04);
+    vec4 r = roughness * c0 + c1;
+    float a004 = min(r.x * r.x, exp2(-9.28 * n_dot_v)) * r.x + r.y;
+    vec2 scale_bias = vec2(-1.04, 1.04) * a004 + r.zw;
+    return f0 * scale_bias.x + scale_bias.y;
+}
+
+// Image based lighting hooks. Without environment maps both return the uniform ambient
+// light, replace them with irradiance and prefiltered radiance lookups.
+vec3 ibl_irradiance(vec3 n, vec3 ambient) {
+    return ambient;
+}
+
+vec3 ibl_radiance(vec3 reflected, float roughness, vec3 ambient) {
+    return ambient;
+}
+
+vec3 ambient_lighting(vec3 n, vec3 v, vec3 diffuse_color, vec3 f0, float roughness, vec3 ambient) {
+    float n_dot_v = max(dot(n, v), 1e-4);
+    vec3 diffuse = diffuse_color * ibl_irradiance(n, ambient);
+    vec3 specular = environment_brdf(f0, roughness, n_dot_v) * ibl_radiance(reflect(-v, n), roughness, ambient);
+    return diffuse + specular;
+}
@@ -0,0 +1,25 @@
+#version 450
+
+#include "scene_data.glsl"
+#include "material.glsl"
+
+layout(location = 0) in vec3 in_position;
+layout(location = 1) in vec3 in_normal;
+layout(location = 2) in vec2 in_uv;
+// xyz: tangent, w: handedness of the bitangent
+layout(location = 3) in vec4 in_tangent;
+
+layout(location = 0) out vec3 out_world_position;
+layout(location = 1) out vec3 out_normal;
+layout(location = 2) out vec2 out_uv;
+layout(location = 3) out vec4 out_tangent;
+
+void main() {
+    vec4 world_position = push_constants.model * vec4(in_position, 1.0);
+    mat3 normal_matrix = transpose(inverse(mat3(push_constants.model)));
+    out_world_position = world_position.xyz;
+    out_normal = normal_matrix * in_normal;
+    out_uv = in_uv;
+    out_tangent = vec4(mat3(push_constants.model) * in_tangent.xyz, in_tangent.w);
+    gl_Position = scene_data.view_projection * world_position;
+}
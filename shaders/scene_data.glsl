@@ -0,0 +1,15 @@
+// Global scene data bound to set 0 in every pipeline.
+// Must match `GpuSceneData` in src/engine/scene_data.rs.
+layout(set = 0, binding = 0) uniform SceneData {
+    mat4 view;
+    mat4 projection;
+    mat4 view_projection;
+    vec4 camera_position;
+    // x: seconds since start, y: frame delta, z: frame number
+    vec4 time;
+    // xyz: direction the light travels in, w: intensity
+    vec4 light_direction;
+    vec4 light_color;
+    // rgb: color, a: intensity
+    vec4 ambient_color;
+} scene_data;
@@ -0,0 +1,14 @@
+#version 450
+
+layout(set = 1, binding = 0) uniform sampler2D ui_texture;
+
+layout(location = 0) in vec2 in_uv;
+layout(location = 1) in vec4 in_color;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    // Vertex colors and textures are both premultiplied sRGB, written as is
+    // into the UNORM swapchain image.
+    out_color = in_color * texture(ui_texture, in_uv);
+}
@@ -0,0 +1,20 @@
+#version 450
+
+layout(location = 0) in vec2 in_position;
+layout(location = 1) in vec2 in_uv;
+layout(location = 2) in vec4 in_color;
+
+layout(push_constant) uniform PushConstants {
+    vec2 screen_size;
+} push_constants;
+
+layout(location = 0) out vec2 out_uv;
+layout(location = 1) out vec4 out_color;
+
+void main() {
+    out_uv = in_uv;
+    out_color = in_color;
+    // egui positions are in points with the origin in the top left corner,
+    // which already matches Vulkan's Y down clip space.
+    gl_Position = vec4(2.0 * in_position / push_constants.screen_size - 1.0, 0.0, 1.0);
+}
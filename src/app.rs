@@ -1,25 +1,211 @@
-use winit::{application::ApplicationHandler, window::{Window, WindowAttributes}};
+use std::{
+    cell::Cell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use log::{error, warn};
+use winit::{application::ApplicationHandler, event::{DeviceEvent, WindowEvent}, window::{Window, WindowAttributes}};
+
+use crate::demo::DemoScene;
+use crate::engine::{self, camera::{controller::CameraController, Projection}, config::EngineConfig, Engine, ErrorKind};
+use panels::{PanelActions, PanelSnapshot, SceneEdit};
+
+mod panels;
+
+/// Height in world units the orthographic projection shows.
+const ORTHOGRAPHIC_HEIGHT: f32 = 10.0;
+const ORTHOGRAPHIC_FAR: f32 = 100.0;
 
-use crate::engine::{self, Engine};
 #[derive(Default)]
 pub struct App {
     window: Option<Window>,
-    engine: Option<Engine>
+    engine: Option<Engine>,
+    camera_controller: CameraController,
+    last_update: Option<Instant>,
+    demo: Option<DemoScene>,
+    elapsed: Duration,
+    /// Time from submitting the last measured frame until the GPU finished it.
+    frame_latency: Rc<Cell<Option<Duration>>>,
+    /// The perspective projection to go back to while the camera is orthographic.
+    saved_projection: Option<Projection>,
 }
 
 impl ApplicationHandler for App {
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
        let window_attributes = WindowAttributes::default();
-       self.window = Some(event_loop.create_window(window_attributes).unwrap());
-       engine::Engine::new(self.window.as_ref().unwrap());
+       let window = match event_loop.create_window(window_attributes) {
+           Ok(window) => window,
+           Err(err) => {
+               error!("Failed to create the window: {err}");
+               event_loop.exit();
+               return;
+           }
+       };
+       match engine::Engine::new(&window, EngineConfig::default()) {
+           Ok(mut engine) => {
+               match DemoScene::new(&mut engine) {
+                   Ok(demo) => self.demo = Some(demo),
+                   Err(err) => warn!("Failed to create the demo scene: {err}"),
+               }
+               self.engine = Some(engine);
+           }
+           Err(err) => {
+               error!("Failed to create the engine: {err}");
+               event_loop.exit();
+           }
+       }
+       self.window = Some(window);
     }
 
     fn window_event(
         &mut self,
         event_loop: &winit::event_loop::ActiveEventLoop,
-        window_id: winit::window::WindowId,
+        _window_id: winit::window::WindowId,
         event: winit::event::WindowEvent,
     ) {
-        
+        if let (Some(window), Some(engine)) = (self.window.as_ref(), self.engine.as_mut()) {
+            // While the camera owns the cursor the UI must not react to it.
+            if !self.camera_controller.cursor_grabbed() && engine.handle_ui_event(window, &event) {
+                return;
+            }
+            if self.camera_controller.handle_window_event(window, &event) {
+                return;
+            }
+        }
+
+        match event {
+            WindowEvent::CloseRequested => {
+                self.engine = None;
+                event_loop.exit();
+            }
+            WindowEvent::Resized(size) => {
+                if let Some(engine) = self.engine.as_mut() {
+                    if let Err(err) = engine.resize(size.width, size.height) {
+                        error!("Failed to resize the engine: {err}");
+                    }
+                }
+            }
+            WindowEvent::RedrawRequested => {
+                if let (Some(window), Some(engine)) = (self.window.as_ref(), self.engine.as_mut()) {
+                    let snapshot = PanelSnapshot::new(engine, self.frame_latency.get());
+                    let mut actions = PanelActions::default();
+                    let camera_controller = &mut self.camera_controller;
+                    let result = engine.run_ui(window, |ctx| {
+                        panels::camera_panel(ctx, camera_controller, &snapshot, &mut actions);
+                        panels::frames_panel(ctx, &snapshot.frames);
+                        panels::background_panel(ctx, &snapshot, &mut actions);
+                        panels::validation_panel(ctx, snapshot.validation.as_ref());
+                        panels::scene_panel(ctx, &snapshot.scene, &mut actions);
+                    });
+                    if let Err(err) = result {
+                        error!("Failed to apply the inspector settings: {err}");
+                    }
+                    apply_panel_actions(engine, &mut self.saved_projection, actions);
+                    if let Some(demo) = self.demo.as_ref() {
+                        if let Err(err) = demo.queue_draws(engine) {
+                            warn!("Failed to queue the demo's draws: {err}");
+                        }
+                    }
+                    let frame_number = engine.frame_number();
+                    let submitted = Instant::now();
+                    let frame_latency = self.frame_latency.clone();
+                    engine.after_frame(frame_number, move |_| frame_latency.set(Some(submitted.elapsed())));
+                    if let Err(err) = engine.draw() {
+                        match err.kind() {
+                            ErrorKind::OutOfDate => {
+                                let size = window.inner_size();
+                                if let Err(err) = engine.resize(size.width, size.height) {
+                                    error!("Failed to recreate the swapchain: {err}");
+                                }
+                            }
+                            ErrorKind::Timeout => warn!("Skipped a frame: {err}"),
+                            ErrorKind::DeviceLost if engine.config().rebuild_on_device_lost => {
+                                let engine = self.engine.take().unwrap();
+                                match engine.rebuild(window) {
+                                    Ok(engine) => self.engine = Some(engine),
+                                    Err(err) => {
+                                        error!("Failed to rebuild the engine: {err}");
+                                        event_loop.exit();
+                                    }
+                                }
+                            }
+                            ErrorKind::DeviceLost | ErrorKind::Fatal => {
+                                error!("Failed to draw the frame: {err}");
+                                self.engine = None;
+                                event_loop.exit();
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn device_event(
+        &mut self,
+        _event_loop: &winit::event_loop::ActiveEventLoop,
+        _device_id: winit::event::DeviceId,
+        event: DeviceEvent,
+    ) {
+        if let DeviceEvent::MouseMotion { delta } = event {
+            self.camera_controller.handle_mouse_motion(delta);
+        }
+    }
+
+    fn about_to_wait(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
+        let now = Instant::now();
+        let delta_time = now - self.last_update.unwrap_or(now);
+        self.last_update = Some(now);
+        self.elapsed += delta_time;
+
+        if let Some(engine) = self.engine.as_mut() {
+            self.camera_controller.update(engine.camera_mut(), delta_time);
+            if let Some(demo) = self.demo.as_ref() {
+                if let Err(err) = demo.update(engine, self.elapsed) {
+                    warn!("Failed to update the demo scene: {err}");
+                }
+            }
+        }
+        if let Some(window) = self.window.as_ref() {
+            window.request_redraw();
+        }
+    }
+}
+
+fn apply_panel_actions(engine: &mut Engine, saved_projection: &mut Option<Projection>, actions: PanelActions) {
+    match actions.orthographic {
+        Some(true) => {
+            let near = match engine.camera().projection {
+                Projection::Perspective { near, .. } => near,
+                Projection::Orthographic { near, .. } => near,
+            };
+            let orthographic = Projection::Orthographic {
+                height: ORTHOGRAPHIC_HEIGHT,
+                near,
+                far: ORTHOGRAPHIC_FAR,
+            };
+            *saved_projection = Some(std::mem::replace(&mut engine.camera_mut().projection, orthographic));
+        }
+        Some(false) => {
+            if let Some(projection) = saved_projection.take() {
+                engine.camera_mut().projection = projection;
+            }
+        }
+        None => {}
+    }
+    if let Some(background) = actions.background {
+        engine.set_background(background);
+    }
+    let scene = engine.scene_mut();
+    let result = match actions.scene_edit {
+        Some(SceneEdit::Remove(node)) => scene.remove_node(node),
+        Some(SceneEdit::DetachMesh(node)) => scene.detach_mesh(node).map(|_| ()),
+        Some(SceneEdit::MoveToRoot(node)) => scene.set_parent(node, None),
+        None => Ok(()),
+    };
+    if let Err(err) = result {
+        warn!("Failed to edit the scene: {err}");
     }
 }
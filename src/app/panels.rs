@@ -0,0 +1,240 @@
+use std::time::Duration;
+
+use cgmath::Vector3;
+use egui::{CollapsingHeader, Color32, ScrollArea, Ui};
+
+use crate::engine::{
+    camera::{controller::{CameraController, ControllerMode}, Projection},
+    config::Background,
+    frame_sync::SyncMode,
+    scene::{NodeId, Scene},
+    validation::{ValidationCounts, ValidationMessage},
+    Engine,
+};
+
+/// Period of the animated background when it is switched back on.
+const ANIMATED_BACKGROUND_PERIOD: u32 = 600;
+
+/// Engine state the panels show. The engine is borrowed while the UI runs, so this is
+/// read from it right before.
+pub struct PanelSnapshot {
+    pub frames: FrameStats,
+    /// `None` without debug utils.
+    pub validation: Option<(ValidationCounts, Vec<ValidationMessage>)>,
+    pub background: Background,
+    pub orthographic: bool,
+    pub scene: Vec<SceneRow>,
+}
+
+impl PanelSnapshot {
+    pub fn new(engine: &mut Engine, latency: Option<Duration>) -> PanelSnapshot {
+        PanelSnapshot {
+            frames: FrameStats {
+                sync_mode: engine.sync_mode(),
+                frame_number: engine.frame_number(),
+                completed_frames: engine.completed_frames().ok(),
+                latency,
+            },
+            validation: engine
+                .validation_counts()
+                .map(|counts| (counts, engine.validation_messages())),
+            background: engine.background(),
+            orthographic: matches!(engine.camera().projection, Projection::Orthographic { .. }),
+            scene: engine
+                .scene()
+                .roots()
+                .iter()
+                .map(|&root| SceneRow::new(engine.scene(), root))
+                .collect(),
+        }
+    }
+}
+
+pub struct FrameStats {
+    pub sync_mode: SyncMode,
+    pub frame_number: u64,
+    pub completed_frames: Option<u64>,
+    /// Time from submitting the last measured frame until the GPU finished it.
+    pub latency: Option<Duration>,
+}
+
+/// A node of the scene with its subtree.
+pub struct SceneRow {
+    node: NodeId,
+    name: String,
+    has_mesh: bool,
+    has_parent: bool,
+    translation: Vector3<f32>,
+    children: Vec<SceneRow>,
+}
+
+impl SceneRow {
+    fn new(scene: &Scene, node: NodeId) -> SceneRow {
+        SceneRow {
+            node,
+            name: scene.name(node).unwrap_or_default().to_owned(),
+            has_mesh: scene.mesh(node).is_some(),
+            has_parent: scene.parent(node).is_some(),
+            translation: scene
+                .transform(node)
+                .map_or(Vector3::new(0.0, 0.0, 0.0), |transform| transform.translation),
+            children: scene
+                .children(node)
+                .iter()
+                .map(|&child| SceneRow::new(scene, child))
+                .collect(),
+        }
+    }
+}
+
+/// Changes made in the panels, applied to the engine after the UI ran.
+#[derive(Debug, Default)]
+pub struct PanelActions {
+    pub orthographic: Option<bool>,
+    pub background: Option<Background>,
+    pub scene_edit: Option<SceneEdit>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SceneEdit {
+    Remove(NodeId),
+    DetachMesh(NodeId),
+    MoveToRoot(NodeId),
+}
+
+pub fn camera_panel(
+    ctx: &egui::Context,
+    camera_controller: &mut CameraController,
+    snapshot: &PanelSnapshot,
+    actions: &mut PanelActions,
+) {
+    egui::Window::new("Camera").show(ctx, |ui| {
+        let mut mode = camera_controller.mode();
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut mode, ControllerMode::Fly, "Fly");
+            ui.selectable_value(&mut mode, ControllerMode::Orbit, "Orbit");
+        });
+        camera_controller.set_mode(mode);
+        let mut orthographic = snapshot.orthographic;
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut orthographic, false, "Perspective");
+            ui.selectable_value(&mut orthographic, true, "Orthographic");
+        });
+        if orthographic != snapshot.orthographic {
+            actions.orthographic = Some(orthographic);
+        }
+        ui.add(egui::Slider::new(&mut camera_controller.move_speed, 0.1..=100.0).logarithmic(true).text("Move speed"));
+        ui.add(egui::Slider::new(&mut camera_controller.look_sensitivity, 0.0005..=0.01).text("Look sensitivity"));
+        ui.label("Right click to grab the cursor, Escape to release it, Tab to switch modes.");
+    });
+}
+
+pub fn frames_panel(ctx: &egui::Context, stats: &FrameStats) {
+    egui::Window::new("Frames").show(ctx, |ui| {
+        ui.label(format!("Sync mode: {:?}", stats.sync_mode));
+        ui.label(format!("Recording frame {}", stats.frame_number));
+        match stats.completed_frames {
+            Some(completed_frames) => ui.label(format!(
+                "GPU finished {completed_frames} frames, {} in flight",
+                stats.frame_number - completed_frames
+            )),
+            None => ui.label("GPU progress unknown"),
+        };
+        match stats.latency {
+            Some(latency) => ui.label(format!("Submit to completion: {:.2} ms", latency.as_secs_f64() * 1000.0)),
+            None => ui.label("Submit to completion: waiting for the first frame"),
+        };
+    });
+}
+
+pub fn background_panel(ctx: &egui::Context, snapshot: &PanelSnapshot, actions: &mut PanelActions) {
+    let background = snapshot.background;
+    egui::Window::new("Background").default_open(false).show(ctx, |ui| {
+        let mut animated = matches!(background, Background::Animated { .. });
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut animated, true, "Animated");
+            ui.selectable_value(&mut animated, false, "Solid");
+        });
+        match (background, animated) {
+            (Background::Solid(_), true) => {
+                actions.background = Some(Background::Animated {
+                    period: ANIMATED_BACKGROUND_PERIOD,
+                })
+            }
+            // Starts from the animation's current color, so switching doesn't jump.
+            (Background::Animated { .. }, false) => {
+                actions.background = Some(Background::Solid(background.color(snapshot.frames.frame_number)))
+            }
+            (Background::Solid(mut color), false) => {
+                if ui.color_edit_button_rgba_unmultiplied(&mut color).changed() {
+                    actions.background = Some(Background::Solid(color));
+                }
+            }
+            (Background::Animated { .. }, true) => {}
+        }
+    });
+}
+
+pub fn validation_panel(ctx: &egui::Context, validation: Option<&(ValidationCounts, Vec<ValidationMessage>)>) {
+    egui::Window::new("Validation").default_open(false).show(ctx, |ui| {
+        let Some((counts, messages)) = validation else {
+            ui.label("Debug utils are unavailable, no messages are received");
+            return;
+        };
+        ui.label(format!(
+            "{} messages: {} errors, {} warnings, {} infos, {} verbose",
+            counts.total(),
+            counts.errors,
+            counts.warnings,
+            counts.infos,
+            counts.verbose
+        ));
+        ui.label(format!("{} repeats, {} ignored", counts.duplicates, counts.ignored));
+        ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+            for message in messages.iter().rev() {
+                ui.colored_label(Color32::LIGHT_RED, format!("{} (x{})", message.id_name, message.count));
+                ui.label(&message.message);
+                ui.separator();
+            }
+        });
+    });
+}
+
+pub fn scene_panel(ctx: &egui::Context, rows: &[SceneRow], actions: &mut PanelActions) {
+    egui::Window::new("Scene").default_open(false).show(ctx, |ui| {
+        ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+            for row in rows {
+                scene_row(ui, row, actions);
+            }
+        });
+    });
+}
+
+fn scene_row(ui: &mut Ui, row: &SceneRow, actions: &mut PanelActions) {
+    let buttons = |ui: &mut Ui, actions: &mut PanelActions| {
+        let translation = row.translation;
+        ui.weak(format!("({:.1}, {:.1}, {:.1})", translation.x, translation.y, translation.z));
+        if row.has_mesh && ui.small_button("Detach mesh").clicked() {
+            actions.scene_edit = Some(SceneEdit::DetachMesh(row.node));
+        }
+        if row.has_parent && ui.small_button("Move to root").clicked() {
+            actions.scene_edit = Some(SceneEdit::MoveToRoot(row.node));
+        }
+        if ui.small_button("Remove").clicked() {
+            actions.scene_edit = Some(SceneEdit::Remove(row.node));
+        }
+    };
+    if row.children.is_empty() {
+        ui.horizontal(|ui| {
+            ui.label(&row.name);
+            buttons(ui, actions);
+        });
+        return;
+    }
+    CollapsingHeader::new(&row.name).id_salt(row.node).show(ui, |ui| {
+        ui.horizontal(|ui| buttons(ui, actions));
+        for child in &row.children {
+            scene_row(ui, child, actions);
+        }
+    });
+}
@@ -0,0 +1,242 @@
+use std::time::Duration;
+
+use anyhow::Error;
+use cgmath::{EuclideanSpace, Matrix4, Point3, Quaternion, Rad, Rotation3, Transform as _, Vector3};
+
+use crate::engine::{
+    include_shader,
+    materials::{MaterialId, MaterialParameters, MaterialTemplate, MaterialTextures, TextureEncoding},
+    mesh::{MeshId, Vertex},
+    scene::{transform::Transform, NodeId},
+    Engine,
+};
+
+const GROUND_SIZE: f32 = 20.0;
+/// Vertices per side of the ground grid generated by `ground.comp`.
+const GROUND_RESOLUTION: u32 = 64;
+const GROUND_WAVE_AMPLITUDE: f32 = 0.1;
+const GROUND_WAVELENGTH: f32 = 5.0;
+const CHECKER_SIZE: u32 = 16;
+/// Cubes per side of the field around the spinner, enough draws for the engine to spread
+/// the scene over its recording threads.
+const FIELD_SIZE: usize = 12;
+const FIELD_SPACING: f32 = 1.5;
+/// Radians per second.
+const SPIN_SPEED: f32 = 0.5;
+/// Radians per second the sun circles the scene with.
+const SUN_SPEED: f32 = 0.1;
+/// Radians per second of the glass cube's glow.
+const GLOW_SPEED: f32 = 2.0;
+const BEACON_HEIGHT: f32 = 1.0;
+const BEACON_SCALE: f32 = 0.2;
+
+/// A spinning cube with a transparent cube orbiting it in a field of small cubes, on a
+/// textured ground the GPU generates on the compute queue. The sample app exercises
+/// materials, the scene hierarchy, parallel recording and async compute with it.
+pub struct DemoScene {
+    spinner: NodeId,
+    satellite: NodeId,
+    cube: MeshId,
+    glass_material: MaterialId,
+    beacon_material: MaterialId,
+}
+
+impl DemoScene {
+    pub fn new(engine: &mut Engine) -> Result<DemoScene, Error> {
+        let (cube_vertices, cube_indices) = cube();
+        let cube = engine.upload_mesh(&cube_vertices, &cube_indices)?;
+        let ground = engine.generate_mesh(
+            include_shader!("ground.comp"),
+            GROUND_RESOLUTION * GROUND_RESOLUTION,
+            [GROUND_SIZE, GROUND_RESOLUTION as f32, GROUND_WAVE_AMPLITUDE, GROUND_WAVELENGTH],
+            &ground_indices(),
+        )?;
+
+        let checker = engine.create_texture(CHECKER_SIZE, CHECKER_SIZE, TextureEncoding::Srgb, &checker_texels())?;
+        let ground_material = engine.create_material(
+            MaterialTemplate::Opaque,
+            MaterialParameters {
+                metallic: 0.0,
+                roughness: 0.8,
+                ..MaterialParameters::default()
+            },
+            MaterialTextures {
+                base_color: Some(checker),
+                ..MaterialTextures::default()
+            },
+        )?;
+        let cube_material = engine.create_material(
+            MaterialTemplate::Opaque,
+            MaterialParameters {
+                base_color: [0.8, 0.2, 0.1, 1.0],
+                metallic: 0.0,
+                roughness: 0.4,
+                ..MaterialParameters::default()
+            },
+            MaterialTextures::default(),
+        )?;
+        let glass_material =
+            engine.create_material(MaterialTemplate::Transparent, glass_parameters(0.0), MaterialTextures::default())?;
+        let beacon_material = engine.create_material(
+            MaterialTemplate::Opaque,
+            MaterialParameters {
+                base_color: [1.0, 0.9, 0.4, 1.0],
+                metallic: 0.0,
+                emissive: [1.0, 0.8, 0.3],
+                ..MaterialParameters::default()
+            },
+            MaterialTextures::default(),
+        )?;
+
+        let scene = engine.scene_mut();
+        let floor = scene.add_node("ground", None, Transform::from_translation(Vector3::new(0.0, -1.0, 0.0)))?;
+        scene.attach_mesh(floor, ground, ground_material)?;
+        let spinner = scene.add_node("spinner", None, Transform::default())?;
+        scene.attach_mesh(spinner, cube, cube_material)?;
+        let satellite = scene.add_node(
+            "satellite",
+            Some(spinner),
+            Transform {
+                translation: Vector3::new(2.0, 0.0, 0.0),
+                scale: Vector3::new(0.5, 0.5, 0.5),
+                ..Transform::default()
+            },
+        )?;
+        scene.attach_mesh(satellite, cube, glass_material)?;
+
+        let field = scene.add_node("field", None, Transform::from_translation(Vector3::new(0.0, -0.75, 0.0)))?;
+        let offset = (FIELD_SIZE - 1) as f32 * FIELD_SPACING / 2.0;
+        for x in 0..FIELD_SIZE {
+            for z in 0..FIELD_SIZE {
+                let translation =
+                    Vector3::new(x as f32 * FIELD_SPACING - offset, 0.0, z as f32 * FIELD_SPACING - offset);
+                // The middle of the field is left to the spinner.
+                if translation.x.abs() < 3.0 && translation.z.abs() < 3.0 {
+                    continue;
+                }
+                let marker = scene.add_node(
+                    "marker",
+                    Some(field),
+                    Transform {
+                        translation,
+                        scale: Vector3::new(0.5, 0.5, 0.5),
+                        ..Transform::default()
+                    },
+                )?;
+                scene.attach_mesh(marker, cube, ground_material)?;
+            }
+        }
+        Ok(DemoScene {
+            spinner,
+            satellite,
+            cube,
+            glass_material,
+            beacon_material,
+        })
+    }
+
+    /// `elapsed` is the time since the app started. Nodes removed in the app's scene
+    /// panel are left alone.
+    pub fn update(&self, engine: &mut Engine, elapsed: Duration) -> Result<(), Error> {
+        let seconds = elapsed.as_secs_f32();
+        if engine.scene().contains(self.spinner) {
+            let transform = Transform {
+                rotation: Quaternion::from_angle_y(Rad(seconds * SPIN_SPEED)),
+                ..Transform::default()
+            };
+            engine.scene_mut().set_transform(self.spinner, transform)?;
+        }
+        let glow = 0.5 + 0.5 * (seconds * GLOW_SPEED).sin();
+        engine.set_material_parameters(self.glass_material, glass_parameters(glow))?;
+        let (sin, cos) = (seconds * SUN_SPEED).sin_cos();
+        engine.lighting_mut().light_direction = Vector3::new(cos * 0.5, -1.0, sin * 0.5);
+        Ok(())
+    }
+
+    /// Queues what the demo draws outside the scene, has to be called once per frame
+    /// before [`Engine::draw`]. The beacon follows the satellite, a frame behind since
+    /// world transforms are only updated when a frame is drawn.
+    pub fn queue_draws(&self, engine: &mut Engine) -> Result<(), Error> {
+        let Some(satellite) = engine.scene().world_transform(self.satellite) else {
+            return Ok(());
+        };
+        let position = satellite.transform_point(Point3::new(0.0, BEACON_HEIGHT, 0.0));
+        let transform = Matrix4::from_translation(position.to_vec()) * Matrix4::from_scale(BEACON_SCALE);
+        engine.draw_mesh(self.cube, self.beacon_material, transform)?;
+        Ok(())
+    }
+}
+
+/// The transparent cube, glowing with `glow` between zero and one.
+fn glass_parameters(glow: f32) -> MaterialParameters {
+    MaterialParameters {
+        base_color: [0.3, 0.6, 1.0, 0.4],
+        metallic: 0.0,
+        roughness: 0.1,
+        emissive: [0.1 * glow, 0.3 * glow, 0.6 * glow],
+        ..MaterialParameters::default()
+    }
+}
+
+/// A unit cube around the origin with counter-clockwise front faces.
+fn cube() -> (Vec<Vertex>, Vec<u32>) {
+    // Outward normal and two in-plane axes per face, with `u x v = normal`.
+    let faces = [
+        ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ];
+    let mut vertices = Vec::with_capacity(faces.len() * 4);
+    let mut indices = Vec::with_capacity(faces.len() * 6);
+    for (normal, u, v) in faces {
+        let normal = Vector3::from(normal);
+        let (u, v) = (Vector3::from(u), Vector3::from(v));
+        let first = vertices.len() as u32;
+        for (u_sign, v_sign, uv) in [
+            (-1.0, -1.0, [0.0, 1.0]),
+            (1.0, -1.0, [1.0, 1.0]),
+            (1.0, 1.0, [1.0, 0.0]),
+            (-1.0, 1.0, [0.0, 0.0]),
+        ] {
+            let position = (normal + u * u_sign + v * v_sign) * 0.5;
+            vertices.push(Vertex {
+                position: position.into(),
+                normal: normal.into(),
+                uv,
+                ..Vertex::default()
+            });
+        }
+        indices.extend([0, 1, 2, 2, 3, 0].map(|index| first + index));
+    }
+    (vertices, indices)
+}
+
+/// Two triangles per cell of the ground grid, whose vertices `ground.comp` generates row
+/// by row along z, each row along x.
+fn ground_indices() -> Vec<u32> {
+    let cells = GROUND_RESOLUTION - 1;
+    (0..cells)
+        .flat_map(|row| (0..cells).map(move |column| (row, column)))
+        .flat_map(|(row, column)| {
+            let vertex = |row: u32, column: u32| row * GROUND_RESOLUTION + column;
+            let (a, b) = (vertex(row + 1, column), vertex(row + 1, column + 1));
+            let (c, d) = (vertex(row, column + 1), vertex(row, column));
+            [a, b, c, c, d, a]
+        })
+        .collect()
+}
+
+/// Two by two checks of light and dark grey, as sRGB RGBA8.
+fn checker_texels() -> Vec<u8> {
+    let half = CHECKER_SIZE / 2;
+    (0..CHECKER_SIZE)
+        .flat_map(|y| (0..CHECKER_SIZE).map(move |x| (x / half + y / half).is_multiple_of(2)))
+        .flat_map(|light| match light {
+            true => [200, 200, 200, 255],
+            false => [60, 60, 60, 255],
+        })
+        .collect()
+}
@@ -1,115 +1,587 @@
-use anyhow::Error;
+use anyhow::anyhow;
 use ash::{
-    khr::surface,
     vk::{
-        CommandBufferResetFlags, CommandBufferUsageFlags, DebugUtilsMessengerEXT, FenceCreateFlags, Image, PhysicalDevice, Queue, QueueFlags, SurfaceKHR, SwapchainKHR
+        self, BufferCopy, BufferUsageFlags, CommandBufferUsageFlags, DescriptorSetLayout, DescriptorType, DeviceSize, Extent2D, Fence, FenceCreateFlags, Image, ImageLayout, ImageView, MemoryPropertyFlags, PhysicalDevice, PhysicalDeviceMemoryProperties, PipelineStageFlags2, PresentInfoKHR, QueueFlags, RenderingFlags, Semaphore, SemaphoreSubmitInfo, SubmitInfo2, PresentModeKHR, SurfaceKHR, SwapchainKHR
     },
     Device, Entry,
 };
-use command_buffers::begin_command_buffer;
-use debugger::setup_debugger;
+use buffers::{create_buffer, AllocatedBuffer};
+use bytemuck::Pod;
+use camera::Camera;
+use cgmath::{Matrix4, Point3};
+use command_encoder::CommandEncoder;
+use config::{
+    Background, EngineConfig, GpuSelector, MsaaSamples, MAX_FRAMES_IN_FLIGHT, MAX_RECORDING_THREADS, MAX_RENDER_SCALE,
+    MIN_FRAMES_IN_FLIGHT, MIN_RENDER_SCALE,
+};
+use debug_names::DebugNames;
+use debugger::Debugger;
+use descriptors::DescriptorAllocator;
+use diagnostics::DeviceDiagnostics;
+pub use errors::engine_error::{EngineError, ErrorKind};
+use errors::engine_error::VkResultExt;
+use features::{DeviceRequirements, EnabledFeatures, Feature};
 use frame_data::FrameData;
+use frame_sync::{FrameSync, SyncMode};
+use immediate_submit::{BufferOwnershipTransfer, ImmediateSubmit};
 use instance::create_instance;
-use queues::QueueIndices;
+use log::{error, info, warn};
+use materials::{
+    DrawList, MaterialId, MaterialParameters, MaterialSystem, MaterialTemplate, MaterialTextures, TextureEncoding,
+    TextureId,
+};
+use mesh::{Mesh, MeshId, Vertex};
+use mesh_generator::MeshGenerator;
+use parallel_recording::RecordJob;
+use physical_devices::DeviceInfo;
+use pipeline_cache::PersistentPipelineCache;
+pub(crate) use pipelines::include_shader;
+use profiler::GpuProfiler;
+use queries::{FrameQueries, QueryResults};
+use queues::{QueueFamilyIndicesError, QueueIndices, Queues};
+use render_targets::RenderTargets;
+use scene::{
+    bounds::Aabb,
+    culling::{cull_render_objects, CullingStats, Frustum},
+    RenderObject, Scene,
+};
+use scene_data::{GpuSceneData, SceneLighting};
+use std::time::{Duration, Instant};
 use swapchain::SwapchainSupportDetails;
 use sync_objects::{create_fence, create_semaphore};
+use tonemap::Tonemapper;
+use ui::{
+    inspector::{Inspector, InspectorActions, InspectorSnapshot, SwapchainInfo},
+    Ui,
+};
+use validation::{ValidationCounts, ValidationMessage};
 use winit::{
+    event::WindowEvent,
     raw_window_handle::{HasDisplayHandle, HasWindowHandle},
-    window::{self, Window},
+    window::Window,
 };
+mod buffers;
+pub mod camera;
 mod command_buffers;
+mod command_encoder;
+pub mod config;
+mod debug_names;
 mod debugger;
+mod descriptors;
+mod diagnostics;
 mod device;
 mod errors;
+pub mod features;
 mod frame_data;
+pub mod frame_sync;
+mod images;
+mod immediate_submit;
 mod instance;
+pub mod materials;
+mod memory;
+pub mod mesh;
+mod mesh_generator;
+pub mod parallel_recording;
 mod physical_devices;
+pub mod profiler;
+pub mod queries;
+mod pipeline_cache;
+mod pipelines;
 mod queues;
+mod render_targets;
+pub mod scene;
+pub mod scene_data;
 mod swapchain;
 mod sync_objects;
+mod tonemap;
+mod ui;
 mod util;
+pub mod validation;
 
-pub static MAX_FRAME_SIZE: usize = 2;
+/// Colors of the queue and pass labels in frame captures.
+const FRAME_LABEL_COLOR: [f32; 4] = [0.7, 0.7, 0.7, 1.0];
+const SCENE_LABEL_COLOR: [f32; 4] = [0.2, 0.6, 1.0, 1.0];
+const TONEMAP_LABEL_COLOR: [f32; 4] = [0.8, 0.4, 0.9, 1.0];
+const BLIT_LABEL_COLOR: [f32; 4] = [1.0, 0.6, 0.2, 1.0];
+const UI_LABEL_COLOR: [f32; 4] = [0.4, 0.9, 0.4, 1.0];
+/// Where the inspector exports the profiler's trace to.
+const CHROME_TRACE_PATH: &str = "metapod-trace.json";
 
 pub struct Engine {
-    entry: Entry,
+    /// Keeps the Vulkan library loaded for as long as the instance exists.
+    _entry: Entry,
     instance: ash::Instance,
-    debug_instance: ash::ext::debug_utils::Instance,
-    debug_messenger: DebugUtilsMessengerEXT,
+    /// `None` if debug utils were not requested or are unavailable.
+    debugger: Option<Debugger>,
     queue_indices: QueueIndices,
     physical_device: PhysicalDevice,
+    device_info: DeviceInfo,
+    enabled_features: EnabledFeatures,
+    memory_properties: PhysicalDeviceMemoryProperties,
     surface_instance: ash::khr::surface::Instance,
     surface_khr: SurfaceKHR,
     device: Device,
-    graphics_queue: Queue,
-    presentation_queue: Queue,
+    queues: Queues,
+    upload_context: ImmediateSubmit,
+    /// Buffers released by the transfer queue that the graphics queue still has to acquire.
+    pending_acquires: Vec<BufferOwnershipTransfer>,
     swapchain_device: ash::khr::swapchain::Device,
     swapchain: SwapchainKHR,
+    swapchain_extent: Extent2D,
+    present_mode: PresentModeKHR,
     images: Vec<Image>,
+    swapchain_image_views: Vec<ImageView>,
+    /// Signaled when rendering to the swapchain image with the same index finished and
+    /// waited on by its present. Per image, as a frame slot may present any image.
+    present_semaphores: Vec<Semaphore>,
+    render_targets: RenderTargets,
+    global_set_layout: DescriptorSetLayout,
+    descriptor_allocator: DescriptorAllocator,
     frame_data: Vec<FrameData>,
     frame: usize,
+    frame_number: u64,
+    config: EngineConfig,
+    camera: Camera,
+    lighting: SceneLighting,
+    start_time: Instant,
+    last_frame_time: Instant,
+    frame_time: Duration,
+    ui: Ui,
+    inspector: Inspector,
+    diagnostics: DeviceDiagnostics,
+    debug_names: DebugNames,
+    profiler: GpuProfiler,
+    query_results: QueryResults,
+    pipeline_cache: PersistentPipelineCache,
+    frame_sync: FrameSync,
+    materials: MaterialSystem,
+    tonemapper: Tonemapper,
+    /// Runs mesh generation shaders on the async compute queue.
+    mesh_generator: MeshGenerator,
+    meshes: Vec<Mesh>,
+    scene: Scene,
+    /// Drawn in the next frame's scene pass, extracted from the scene or queued directly.
+    render_objects: Vec<RenderObject>,
+    culling_stats: CullingStats,
 }
 
 impl Engine {
-    pub fn draw(&self) -> Result<(), Error> {
-        unsafe {
+    /// Renders and presents a frame. A device loss is reported as [`EngineError::DeviceLost`]
+    /// with whatever the diagnostics know about the GPU's progress. Errors of kind
+    /// [`ErrorKind::OutOfDate`] mean the swapchain has to be recreated with [`Engine::resize`].
+    pub fn draw(&mut self) -> Result<(), EngineError> {
+        match self.draw_frame() {
+            Err(err) if err.is_device_lost() => {
+                let report = self.diagnostics.report(self.queues.graphics, self.frame_number);
+                error!("{report}");
+                Err(EngineError::DeviceLost {
+                    report: Box::new(report),
+                })
+            }
+            Ok(()) => self.take_validation_error(),
+            result => result,
+        }
+    }
+
+    /// Turns a validation error the messenger recorded into an [`EngineError::Validation`],
+    /// the debug callback itself can't fail or unwind.
+    fn take_validation_error(&self) -> Result<(), EngineError> {
+        let error = self
+            .debugger
+            .as_ref()
+            .and_then(|debugger| debugger.sink().take_first_error());
+        match error {
+            Some(message) => Err(EngineError::Validation(Box::new(message))),
+            None => Ok(()),
+        }
+    }
+
+    /// Culls the scene and the objects queued with [`Engine::draw_mesh`] and resolves them
+    /// into this frame's draw list.
+    fn prepare_draw_list(&mut self) -> Result<DrawList, EngineError> {
+        let meshes = &self.meshes;
+        let mesh = |mesh: MeshId| meshes.get(mesh.0).ok_or(EngineError::UnknownMesh(mesh));
+        self.scene
+            .extract(|id| mesh(id).map(|mesh| mesh.bounds), &mut self.render_objects)?;
+        self.culling_stats = match self.config.frustum_culling {
+            true => {
+                let frustum = Frustum::from_view_projection(self.camera.view_projection_matrix());
+                cull_render_objects(&frustum, &mut self.render_objects)
+            }
+            false => CullingStats {
+                objects: self.render_objects.len(),
+                culled: 0,
+            },
+        };
+        let draws = self
+            .render_objects
+            .drain(..)
+            .map(|object| Ok((mesh(object.mesh)?, object.material, object.transform)))
+            .collect::<Result<Vec<_>, EngineError>>()?;
+        self.materials.draw_list(
+            self.frame,
+            self.frame_data[self.frame].global_descriptor,
+            self.render_targets.extent(),
+            self.camera.position,
+            draws,
+        )
+    }
+
+    fn draw_frame(&mut self) -> Result<(), EngineError> {
+        let render_fence = self.frame_data[self.frame].render_fence;
+        self.frame_sync
+            .wait_for_slot(&self.device, self.frame, render_fence, 1_000_000_000)?;
+        self.frame_sync.run_completed(&self.device)?;
+        self.frame_data[self.frame].thread_pools.reset(&self.device)?;
+        // Before anything is acquired, so unknown ids fail the frame without side effects.
+        let draw_list = self.prepare_draw_list();
+        self.render_objects.clear();
+        let draw_list = draw_list?;
+
+        let frame = &self.frame_data[self.frame];
+        let acquired = unsafe {
+            self.swapchain_device.acquire_next_image(
+                self.swapchain,
+                1_000_000_000,
+                frame.swapchain_semaphore,
+                Fence::null(),
+            )
+        };
+        // Without an image the frame is skipped until the caller recreated the swapchain.
+        // Whatever was queued for it is dropped with the draw list.
+        let (image_index, acquired_suboptimal) = acquired.vk_context("vkAcquireNextImageKHR")?;
+        let image_index = image_index as usize;
+        self.ui.begin_frame(&self.device, self.frame);
+        self.diagnostics.begin_frame(self.frame);
+        self.profiler.begin_frame(&self.device, self.frame, self.frame_number)?;
+        self.frame_sync.begin_submit(&self.device, render_fence)?;
+
+        self.upload_scene_data()?;
+        self.materials.begin_frame(self.frame)?;
+
+        let mut encoder = CommandEncoder::reset_and_begin(
+            &self.device,
+            self.frame_data[self.frame].command_buffer,
+            CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+        )?;
+        self.profiler.begin_commands(&mut encoder, self.frame);
+        if let Some(results) =
+            self.frame_data[self.frame].queries.begin_frame(&self.device, &mut encoder, self.frame_number)?
+        {
+            self.query_results = results;
+        }
+        for transfer in self.pending_acquires.drain(..) {
+            transfer.record_acquire(&mut encoder);
+        }
+        for staging_buffer in self.materials.record_uploads(&mut encoder) {
+            self.frame_sync.after_frame(
+                self.frame_number,
+                Box::new(move |device| staging_buffer.destroy(device)),
+            );
+        }
+
+        // Large scenes are recorded on the worker threads, one secondary per part.
+        let mut draw_lists = draw_list.split(self.config.recording_threads);
+        let (inline_draw_list, scene_jobs) = match draw_lists.len() {
+            1 => (draw_lists.pop(), Vec::new()),
+            _ => (
+                None,
+                draw_lists
+                    .into_iter()
+                    .map(|draw_list| -> RecordJob { Box::new(move |rendering| draw_list.record(rendering)) })
+                    .collect(),
+            ),
+        };
+        self.begin_pass(&mut encoder, "scene", SCENE_LABEL_COLOR);
+        let frame = &mut self.frame_data[self.frame];
+        // A query may only stay active while secondaries execute with `inheritedQueries`.
+        let (scene_statistics, scene_occlusion) =
+            match scene_jobs.is_empty() || frame.queries.supports_inherited_queries() {
+                true => (
+                    frame.queries.begin_statistics(&mut encoder, "scene"),
+                    frame.queries.begin_occlusion(&mut encoder, "scene"),
+                ),
+                false => (None, None),
+            };
+        let (rendering_flags, secondary_command_buffers) = match scene_jobs.is_empty() {
+            true => (RenderingFlags::empty(), Vec::new()),
+            false => {
+                let inheritance = self.render_targets.inheritance(
+                    frame.queries.inherited_statistics(scene_statistics),
+                    frame.queries.inherited_occlusion(scene_occlusion),
+                );
+                let command_buffers = frame.thread_pools.record(&self.device, &inheritance, scene_jobs)?;
+                (RenderingFlags::CONTENTS_SECONDARY_COMMAND_BUFFERS, command_buffers)
+            }
+        };
+        let background = self.config.background.color(self.frame_number);
+        let mut rendering = self
+            .render_targets
+            .begin_rendering(&mut encoder, background, rendering_flags);
+        match inline_draw_list {
+            Some(draw_list) => draw_list.record(&mut rendering),
+            None => rendering.execute_commands(&secondary_command_buffers),
+        }
+        rendering.end();
+        for query in [scene_statistics, scene_occlusion].into_iter().flatten() {
+            self.frame_data[self.frame].queries.end(&mut encoder, query);
+        }
+        self.end_pass(&mut encoder);
+
+        self.begin_pass(&mut encoder, "tonemap", TONEMAP_LABEL_COLOR);
+        encoder.transition_image(
+            self.render_targets.draw_image.image,
+            ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ImageLayout::GENERAL,
+        );
+        self.tonemapper.record(
+            &mut encoder,
+            self.frame_data[self.frame].global_descriptor,
+            &self.render_targets.draw_image,
+        );
+        self.end_pass(&mut encoder);
+
+        self.begin_pass(&mut encoder, "blit", BLIT_LABEL_COLOR);
+
+        let swapchain_image = self.images[image_index];
+        encoder.transition_image(
+            self.render_targets.draw_image.image,
+            ImageLayout::GENERAL,
+            ImageLayout::TRANSFER_SRC_OPTIMAL,
+        );
+        encoder.transition_image(swapchain_image, ImageLayout::UNDEFINED, ImageLayout::TRANSFER_DST_OPTIMAL);
+        encoder.blit_image(
+            self.render_targets.draw_image.image,
+            swapchain_image,
+            self.render_targets.extent(),
+            self.swapchain_extent,
+        );
+        encoder.transition_image(
+            swapchain_image,
+            ImageLayout::TRANSFER_DST_OPTIMAL,
+            ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        );
+        self.end_pass(&mut encoder);
+
+        self.begin_pass(&mut encoder, "ui", UI_LABEL_COLOR);
+        self.ui.record(
+            &self.device,
+            &mut encoder,
+            self.frame,
+            self.swapchain_image_views[image_index],
+            self.swapchain_extent,
+            self.frame_data[self.frame].global_descriptor,
+        )?;
+        self.end_pass(&mut encoder);
+        encoder.transition_image(
+            swapchain_image,
+            ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ImageLayout::PRESENT_SRC_KHR,
+        );
+        self.profiler.end_commands(&mut encoder, self.frame);
+        let commands = encoder.finish()?;
+
+        let frame = &self.frame_data[self.frame];
+
+        let wait_semaphores = [SemaphoreSubmitInfo::default()
+            .semaphore(frame.swapchain_semaphore)
+            .stage_mask(PipelineStageFlags2::ALL_COMMANDS)];
+        let signal_semaphores = std::iter::once(
+            SemaphoreSubmitInfo::default()
+                .semaphore(self.present_semaphores[image_index])
+                .stage_mask(PipelineStageFlags2::ALL_GRAPHICS),
+        )
+        .chain(self.frame_sync.signal_semaphore_info(self.frame_number))
+        .collect::<Vec<_>>();
+        let command_buffers = [commands.submit_info()];
+        let submit_info = SubmitInfo2::default()
+            .wait_semaphore_infos(&wait_semaphores)
+            .signal_semaphore_infos(&signal_semaphores)
+            .command_buffer_infos(&command_buffers);
+        // Validation messages about the submission name the frame in their queue labels.
+        self.debug_names.begin_queue_label(
+            self.queues.graphics,
+            &format!("frame {}", self.frame_number),
+            FRAME_LABEL_COLOR,
+        );
+        let submitted = unsafe {
             self.device
-                .wait_for_fences(
-                    &[self.frame_data[self.frame].render_fence],
-                    true,
-                    1000000000 as u64,
+                .queue_submit2(
+                    self.queues.graphics,
+                    &[submit_info],
+                    self.frame_sync.submit_fence(frame.render_fence),
                 )
-                .unwrap();
+                .vk_context("vkQueueSubmit2")
+        };
+        self.debug_names.end_queue_label(self.queues.graphics);
+        submitted?;
+        self.frame_sync.submitted(self.frame, self.frame_number);
 
-            self.device.reset_fences(&[self.frame_data[self.frame].render_fence]).unwrap();
+        let swapchains = [self.swapchain];
+        let render_semaphores = [self.present_semaphores[image_index]];
+        let image_indices = [image_index as u32];
+        let present_info = PresentInfoKHR::default()
+            .swapchains(&swapchains)
+            .wait_semaphores(&render_semaphores)
+            .image_indices(&image_indices);
+        let presented = unsafe { self.swapchain_device.queue_present(self.queues.presentation, &present_info) };
 
-            let _next_image = self.swapchain_device.acquire_next_image(self.swapchain,1000000000 as u64 ,self.frame_data[self.frame].swapchain_semaphore, self.frame_data[self.frame].render_fence).unwrap();
+        // The frame was submitted either way, only the swapchain has to be recreated.
+        self.frame = (self.frame + 1) % self.frame_data.len();
+        self.frame_number += 1;
+        match presented.vk_context("vkQueuePresentKHR")? {
+            true => Err(vk::Result::SUBOPTIMAL_KHR).vk_context("vkQueuePresentKHR"),
+            // An image acquired as suboptimal is still rendered and presented, so its
+            // semaphore doesn't stay signaled, and reported once it was.
+            false if acquired_suboptimal => Err(vk::Result::SUBOPTIMAL_KHR).vk_context("vkAcquireNextImageKHR"),
+            false => Ok(()),
         }
+    }
 
-            unsafe { self.device.reset_command_buffer(self.frame_data[self.frame].command_buffer, CommandBufferResetFlags::empty())? };
-            begin_command_buffer(&self.device,self.frame_data[self.frame].command_buffer, CommandBufferUsageFlags::ONE_TIME_SUBMIT).unwrap();
+    /// Opens a pass for the diagnostics, the profiler and frame captures.
+    fn begin_pass(&mut self, encoder: &mut CommandEncoder, name: &'static str, color: [f32; 4]) {
+        self.diagnostics.begin_pass(encoder, self.frame, name);
+        self.debug_names.begin_label(encoder, name, color);
+        self.profiler.begin_pass(encoder, self.frame, name);
+    }
 
-            Ok(())
+    fn end_pass(&mut self, encoder: &mut CommandEncoder) {
+        self.profiler.end_pass(encoder, self.frame);
+        self.debug_names.end_label(encoder);
+        self.diagnostics.end_pass(encoder, self.frame);
     }
 
-    pub fn new(window: &Window) -> Result<Engine, Error> {
+    /// Feeds a window event to the UI. Returns `true` if the UI consumed it and it
+    /// should not be handled by anything else.
+    pub fn handle_ui_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        self.ui.handle_window_event(window, event)
+    }
+
+    /// Builds the UI for the next [`Engine::draw`], including the engine inspector.
+    /// Settings changed in the inspector are applied before returning.
+    pub fn run_ui(&mut self, window: &Window, mut run_ui: impl FnMut(&egui::Context)) -> Result<(), EngineError> {
+        let snapshot = InspectorSnapshot {
+            device_info: &self.device_info,
+            queue_indices: self.queue_indices,
+            swapchain: SwapchainInfo {
+                format: swapchain::SWAPCHAIN_IMAGE_FORMAT,
+                color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+                present_mode: self.present_mode,
+                image_count: self.images.len(),
+                extent: self.swapchain_extent,
+            },
+            render_extent: self.render_targets.extent(),
+            frame_time: self.frame_time,
+            enabled_features: &self.enabled_features,
+            memory_budget: memory::query_memory_budget(
+                &self.instance,
+                self.physical_device,
+                self.memory_budget_enabled(),
+            ),
+            memory_budget_supported: self.memory_budget_enabled(),
+            vsync: self.config.vsync,
+            msaa_samples: self.render_targets.samples,
+            supported_msaa_samples: self.supported_msaa_samples(),
+            render_scale: self.config.render_scale,
+            frames_in_flight: self.frame_data.len(),
+            profiler: &self.profiler,
+            query_results: &self.query_results,
+            frustum_culling: self.config.frustum_culling,
+            culling_stats: self.culling_stats,
+        };
+        let mut actions = InspectorActions::default();
+        let inspector = &mut self.inspector;
+        self.ui.run(window, |ctx| {
+            inspector.show(ctx, &snapshot, &mut actions);
+            run_ui(ctx);
+        });
+
+        if let Some(vsync) = actions.vsync {
+            self.set_vsync(vsync)?;
+        }
+        if let Some(samples) = actions.msaa_samples {
+            self.set_msaa_samples(samples)?;
+        }
+        if let Some(render_scale) = actions.render_scale {
+            self.set_render_scale(render_scale)?;
+        }
+        if let Some(frames_in_flight) = actions.frames_in_flight {
+            self.set_frames_in_flight(frames_in_flight)?;
+        }
+        if let Some(frustum_culling) = actions.frustum_culling {
+            self.set_frustum_culling(frustum_culling);
+        }
+        if actions.export_trace {
+            match self.profiler.write_chrome_trace(CHROME_TRACE_PATH) {
+                Ok(()) => info!("Wrote the Chrome trace to {CHROME_TRACE_PATH}"),
+                Err(err) => error!("Failed to write the Chrome trace to {CHROME_TRACE_PATH}: {err}"),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn new(window: &Window, config: EngineConfig) -> Result<Engine, EngineError> {
         let width = window.inner_size().width;
         let height = window.inner_size().height;
-        let (entry, instance) = create_instance(&window).unwrap();
-        let (debug_instance, debug_messenger) = setup_debugger(&entry, &instance);
+        let (entry, instance, debug_support) = create_instance(window, &config.validation)?;
+        info!("Validation layer enabled: {}, debug utils enabled: {}", debug_support.validation, debug_support.debug_utils);
+        let debugger = match debug_support.debug_utils {
+            true => Some(Debugger::new(&entry, &instance, &config.validation)?),
+            false => None,
+        };
         let surface_instance = ash::khr::surface::Instance::new(&entry, &instance);
         let surface_khr = unsafe {
             ash_window::create_surface(
                 &entry,
                 &instance,
-                window.display_handle().unwrap().as_raw(),
-                window.window_handle().unwrap().as_raw(),
+                window.display_handle()?.as_raw(),
+                window.window_handle()?.as_raw(),
                 None,
             )
-            .unwrap()
-        };
-        let physical_device =
-            physical_devices::find_physical_device(&instance, &surface_instance, surface_khr)
-                .unwrap();
+        }
+        .vk_context("vkCreateSurfaceKHR")?;
+        let gpu_selector = GpuSelector::from_env().or_else(|| config.gpu.clone());
+        let mut requirements = Self::device_requirements().merge(config.device_requirements.clone());
+        if config.device_diagnostics {
+            requirements = requirements.merge(DeviceDiagnostics::device_requirements());
+        }
+        let physical_device = physical_devices::find_physical_device(
+            &instance,
+            &surface_instance,
+            surface_khr,
+            &requirements,
+            gpu_selector.as_ref(),
+        )?;
         let queue_indices = QueueIndices::find_queue_family_indices(
             physical_device,
             &instance,
             &surface_instance,
             &surface_khr,
             QueueFlags::GRAPHICS,
-        )
-        .unwrap();
-        let device = device::create_device(&instance, physical_device, queue_indices).unwrap();
-        let graphics_queue =
-            unsafe { device.get_device_queue(queue_indices.graphics_queue_index.unwrap(), 0) };
-        let presentation_queue =
-            unsafe { device.get_device_queue(queue_indices.presentation_queue_index.unwrap(), 0) };
+        )?;
+        if !queue_indices.is_complete() {
+            return Err(QueueFamilyIndicesError::NoPresentationFamily.into());
+        }
+        let memory_properties =
+            unsafe { instance.get_physical_device_memory_properties(physical_device) };
+        let device_info = DeviceInfo::query(&instance, physical_device);
+        let enabled_features = requirements.negotiate(&instance, physical_device);
+        info!(
+            "Enabled device features {:?} and extensions {:?}",
+            enabled_features.features, enabled_features.extensions
+        );
+        let device =
+            device::create_device(&instance, physical_device, queue_indices, &enabled_features)?;
+        let queues = Queues::new(&device, queue_indices)?;
+        let upload_context =
+            ImmediateSubmit::new(&device, queues.transfer, queue_indices.transfer_family())?;
         let swapchain_device = ash::khr::swapchain::Device::new(&instance, &device);
         let swapchain_support_details = SwapchainSupportDetails::query_swapchain_support(
             &surface_instance,
             physical_device,
             surface_khr,
         )?;
+        let present_mode = swapchain::choose_present_mode(&swapchain_support_details, config.vsync);
         let swapchain = swapchain::create_swapchain(
             &swapchain_device,
             swapchain_support_details,
@@ -117,38 +589,799 @@ impl Engine {
             surface_khr,
             width,
             height,
-        )
-        .unwrap();
+            present_mode,
+        )?;
         let images = swapchain::create_swapchain_images(&swapchain_device, swapchain)?;
-        let mut frames: Vec<FrameData> = Vec::new();
+        let swapchain_image_views = swapchain::create_swapchain_image_views(&device, &images)?;
+        let swapchain_extent = Extent2D::default().width(width).height(height);
 
-        for i in 0..MAX_FRAME_SIZE {
-            frames.push(FrameData::new(
-                &device,
-                queue_indices.graphics_queue_index.unwrap(),
-                create_semaphore(&device)?,
-                create_semaphore(&device)?,
-                create_fence(&device, FenceCreateFlags::SIGNALED)?,
-            )?);
-        }
+        let present_semaphores = Self::create_present_semaphores(&device, images.len())?;
 
-        Ok(Engine {
-            entry,
+        let mut config = config;
+        config.frames_in_flight = config.frames_in_flight.clamp(MIN_FRAMES_IN_FLIGHT, MAX_FRAMES_IN_FLIGHT);
+        config.recording_threads = config.recording_threads.clamp(1, MAX_RECORDING_THREADS);
+        config.msaa_samples = Self::clamp_msaa_samples(&instance, physical_device, config.msaa_samples);
+        config.render_scale = config.render_scale.clamp(MIN_RENDER_SCALE, MAX_RENDER_SCALE);
+        let render_targets = RenderTargets::new(
+            &device,
+            &memory_properties,
+            Self::render_extent(swapchain_extent, config.render_scale),
+            config.msaa_samples,
+        )?;
+        let global_set_layout = scene_data::create_global_set_layout(&device)?;
+        let descriptor_allocator = Self::create_frame_descriptor_allocator(&device, config.frames_in_flight)?;
+        let max_texture_side = unsafe { instance.get_physical_device_properties(physical_device) }
+            .limits
+            .max_image_dimension2_d as usize;
+        let pipeline_cache = PersistentPipelineCache::new(
+            &device,
+            &device_info.properties,
+            config.pipeline_cache_dir.as_deref(),
+        )?;
+        let materials = MaterialSystem::new(
+            &device,
+            &memory_properties,
+            global_set_layout,
+            config.msaa_samples.sample_count_flags(),
+            config.frames_in_flight,
+            pipeline_cache.cache,
+        )?;
+        let tonemapper = Tonemapper::new(
+            &device,
+            global_set_layout,
+            &render_targets.draw_image,
+            pipeline_cache.cache,
+        )?;
+        let mesh_generator = MeshGenerator::new(
+            &device,
+            global_set_layout,
+            queues.compute,
+            queue_indices.compute_family(),
+            queue_indices.graphics_queue_index,
+        )?;
+        let ui = Ui::new(
+            &device,
+            &memory_properties,
+            global_set_layout,
+            swapchain::SWAPCHAIN_IMAGE_FORMAT,
+            config.frames_in_flight,
+            max_texture_side,
+            pipeline_cache.cache,
+        )?;
+        let diagnostics = DeviceDiagnostics::new(
+            &instance,
+            &device,
+            &memory_properties,
+            &enabled_features,
+            config.frames_in_flight,
+        )?;
+        let debug_names = DebugNames::new(&instance, &device, debugger.is_some());
+        let timestamp_valid_bits = match config.gpu_profiling {
+            true => device_info.queue_families[queue_indices.graphics_queue_index as usize].timestamp_valid_bits,
+            false => 0,
+        };
+        let profiler = GpuProfiler::new(
+            &device,
+            &device_info.properties.limits,
+            timestamp_valid_bits,
+            config.frames_in_flight,
+        )?;
+        let frame_sync = FrameSync::new(
+            &device,
+            &enabled_features,
+            config.timeline_semaphores,
+            config.frames_in_flight,
+        )?;
+        let frames = Self::create_frames(
+            &device,
+            &memory_properties,
+            queue_indices.graphics_queue_index,
+            &descriptor_allocator,
+            global_set_layout,
+            &enabled_features,
+            &config,
+        )?;
+
+        let engine = Engine {
+            _entry: entry,
             instance,
-            debug_instance,
-            debug_messenger,
+            debugger,
             physical_device,
+            device_info,
+            enabled_features,
+            memory_properties,
             queue_indices,
             surface_instance,
             surface_khr,
             device,
-            graphics_queue,
-            presentation_queue,
+            queues,
+            upload_context,
+            pending_acquires: Vec::new(),
             swapchain_device,
             swapchain,
+            swapchain_extent,
+            present_mode,
             images,
-            frames,
+            swapchain_image_views,
+            present_semaphores,
+            render_targets,
+            global_set_layout,
+            descriptor_allocator,
+            frame_data: frames,
             frame: 0,
-        })
+            frame_number: 0,
+            config,
+            camera: Camera::new(Point3::new(0.0, 0.0, 5.0), width as f32 / height.max(1) as f32),
+            lighting: SceneLighting::default(),
+            start_time: Instant::now(),
+            last_frame_time: Instant::now(),
+            frame_time: Duration::ZERO,
+            ui,
+            inspector: Inspector::default(),
+            diagnostics,
+            debug_names,
+            profiler,
+            query_results: QueryResults::default(),
+            pipeline_cache,
+            frame_sync,
+            materials,
+            tonemapper,
+            mesh_generator,
+            meshes: Vec::new(),
+            scene: Scene::new(),
+            render_objects: Vec::new(),
+            culling_stats: CullingStats::default(),
+        };
+        engine.name_objects();
+        Ok(engine)
+    }
+
+    /// Names everything created in [`Engine::new`]. Swapchain objects and render targets are
+    /// named again whenever they are recreated.
+    fn name_objects(&self) {
+        let names = &self.debug_names;
+        if !names.is_enabled() {
+            return;
+        }
+        names.name(self.device.handle(), "device");
+        names.name(self.queues.graphics, "graphics queue");
+        if self.queues.presentation != self.queues.graphics {
+            names.name(self.queues.presentation, "presentation queue");
+        }
+        if self.queues.transfer != self.queues.graphics {
+            names.name(self.queues.transfer, "transfer queue");
+        }
+        if self.queues.compute != self.queues.graphics {
+            names.name(self.queues.compute, "compute queue");
+        }
+        names.name(self.global_set_layout, "global set layout");
+        self.upload_context.name_objects(names, "upload");
+        for (frame_index, frame) in self.frame_data.iter().enumerate() {
+            frame.name_objects(names, frame_index);
+        }
+        self.ui.name_objects(names);
+        self.diagnostics.name_objects(names);
+        self.profiler.name_objects(names);
+        self.frame_sync.name_objects(names);
+        self.materials.name_objects(names);
+        self.tonemapper.name_objects(names);
+        self.mesh_generator.name_objects(names);
+        self.name_swapchain_objects();
+        self.render_targets.name_objects(names);
+    }
+
+    fn name_swapchain_objects(&self) {
+        self.debug_names.name(self.swapchain, "swapchain");
+        for (index, (image, image_view)) in self.images.iter().zip(self.swapchain_image_views.iter()).enumerate() {
+            self.debug_names.name(*image, &format!("swapchain image {index}"));
+            self.debug_names.name(*image_view, &format!("swapchain image {index} view"));
+        }
+        for (index, semaphore) in self.present_semaphores.iter().enumerate() {
+            self.debug_names.name(*semaphore, &format!("swapchain image {index} present semaphore"));
+        }
+    }
+
+    /// Recreates the instance, device and every GPU resource from the configuration, keeping
+    /// the camera, lighting, inspector and scene. Meshes, textures and materials are uploaded
+    /// again under their old ids. Used to continue after [`ErrorKind::DeviceLost`].
+    pub fn rebuild(mut self, window: &Window) -> Result<Engine, EngineError> {
+        let config = self.config.clone();
+        let camera = self.camera.clone();
+        let lighting = self.lighting;
+        let inspector = std::mem::take(&mut self.inspector);
+        let scene = std::mem::take(&mut self.scene);
+        let materials = self.materials.snapshot();
+        let meshes = self
+            .meshes
+            .iter_mut()
+            .map(|mesh| (std::mem::take(&mut mesh.vertices), std::mem::take(&mut mesh.indices)))
+            .collect::<Vec<_>>();
+        // The old surface has to be gone before the window can get a new one.
+        drop(self);
+        let mut engine = Engine::new(window, config)?;
+        engine.camera = camera;
+        engine.lighting = lighting;
+        engine.inspector = inspector;
+        engine.scene = scene;
+        // Recreated in creation order, so every handle the caller holds stays valid.
+        engine.materials.restore(&engine.device, &materials)?;
+        engine.materials.name_objects(&engine.debug_names);
+        for (vertices, indices) in meshes {
+            engine.upload_mesh(&vertices, &indices)?;
+        }
+        info!("Engine rebuilt after a device loss");
+        Ok(engine)
+    }
+
+    /// Number of the frame the next [`Engine::draw`] submits.
+    pub fn frame_number(&self) -> u64 {
+        self.frame_number
+    }
+
+    /// How many frames the GPU has finished, frame `n` is done once this exceeds `n`.
+    pub fn completed_frames(&mut self) -> Result<u64, EngineError> {
+        self.frame_sync.completed_frames(&self.device)
+    }
+
+    /// Runs `callback` once the GPU finished frame `frame_number`, e.g. to free resources
+    /// the frame used or read back its results. Checked at the start of every frame, and
+    /// everything still queued runs when the engine shuts down.
+    pub fn after_frame(&mut self, frame_number: u64, callback: impl FnOnce(&Device) + 'static) {
+        self.frame_sync.after_frame(frame_number, Box::new(callback));
+    }
+
+    pub fn sync_mode(&self) -> SyncMode {
+        self.frame_sync.mode()
+    }
+
+
+
+
+    pub fn config(&self) -> &EngineConfig {
+        &self.config
+    }
+
+    /// Features and extensions every device has to support for the engine itself.
+    fn device_requirements() -> DeviceRequirements {
+        DeviceRequirements::default()
+            .require_extension(ash::khr::swapchain::NAME)
+            .require_feature(Feature::DynamicRendering)
+            .require_feature(Feature::Synchronization2)
+            .optional_feature(Feature::SamplerAnisotropy)
+            .optional_feature(Feature::FillModeNonSolid)
+            .optional_feature(Feature::TimelineSemaphore)
+            .optional_extension(ash::ext::memory_budget::NAME)
+            .merge(FrameQueries::device_requirements())
+    }
+
+    /// Validation messages received so far, `None` without debug utils.
+    pub fn validation_counts(&self) -> Option<ValidationCounts> {
+        self.debugger.as_ref().map(Debugger::validation_counts)
+    }
+
+    /// The distinct validation messages received so far, with their repeat counts.
+    pub fn validation_messages(&self) -> Vec<ValidationMessage> {
+        self.debugger
+            .as_ref()
+            .map(|debugger| debugger.sink().messages())
+            .unwrap_or_default()
+    }
+
+
+    fn memory_budget_enabled(&self) -> bool {
+        self.enabled_features
+            .has_extension(ash::ext::memory_budget::NAME)
+    }
+
+
+    /// Creates a device local buffer holding `data`, copied on the transfer queue. `data`
+    /// must not be empty, Vulkan has no zero sized buffers.
+    /// If that is a dedicated family the buffer is acquired by the graphics queue at the
+    /// start of the next frame, so it can be used from then on.
+    ///
+    /// Blocks until the copy finished. The dedicated queue only keeps the copy off the
+    /// graphics queue, the upload doesn't overlap with recording or submitting frames.
+    pub fn upload_buffer<T: Pod>(
+        &mut self,
+        data: &[T],
+        usage: BufferUsageFlags,
+    ) -> Result<AllocatedBuffer, EngineError> {
+        if data.is_empty() {
+            return Err(anyhow!("Can't upload an empty buffer").into());
+        }
+        let size = std::mem::size_of_val(data) as DeviceSize;
+        let staging = create_buffer(
+            &self.device,
+            &self.memory_properties,
+            size,
+            BufferUsageFlags::TRANSFER_SRC,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+        let buffer = staging.write(data).and_then(|()| {
+            create_buffer(
+                &self.device,
+                &self.memory_properties,
+                size,
+                usage | BufferUsageFlags::TRANSFER_DST,
+                MemoryPropertyFlags::DEVICE_LOCAL,
+            )
+        });
+        let buffer = match buffer {
+            Ok(buffer) => buffer,
+            Err(err) => {
+                staging.destroy(&self.device);
+                return Err(err.into());
+            }
+        };
+
+        let transfer = BufferOwnershipTransfer {
+            buffer: buffer.buffer,
+            src_queue_family_index: self.upload_context.queue_family_index,
+            dst_queue_family_index: self.queue_indices.graphics_queue_index,
+        };
+        let result = self.upload_context.submit(&self.device, |encoder| {
+            encoder.copy_buffer(staging.buffer, buffer.buffer, &[BufferCopy::default().size(size)]);
+            transfer.record_release(encoder);
+        });
+        let result = result.and_then(|()| self.take_validation_error());
+        staging.destroy(&self.device);
+        if let Err(err) = result {
+            buffer.destroy(&self.device);
+            return Err(err);
+        }
+        if transfer.is_needed() {
+            self.pending_acquires.push(transfer);
+        }
+        Ok(buffer)
+    }
+
+    /// Uploads a triangle list through [`Self::upload_buffer`], usable from the next frame.
+    pub fn upload_mesh(&mut self, vertices: &[Vertex], indices: &[u32]) -> Result<MeshId, EngineError> {
+        let Some(bounds) = Aabb::from_points(vertices.iter().map(|vertex| Point3::from(vertex.position))) else {
+            return Err(anyhow!("Can't upload a mesh without vertices").into());
+        };
+        if indices.is_empty() {
+            return Err(anyhow!("Can't upload a mesh without indices").into());
+        }
+        let vertex_buffer = self.upload_buffer(vertices, BufferUsageFlags::VERTEX_BUFFER)?;
+        self.add_mesh(vertex_buffer, bounds, vertices.to_vec(), indices)
+    }
+
+    /// Generates `vertex_count` vertices with the SPIR-V compute shader `shader`, e.g. from
+    /// [`include_shader!`], on the async compute queue and uploads `indices` for them, usable from the next frame. The shader
+    /// gets `parameters` and the vertex count as push constants, see `ground.comp`.
+    ///
+    /// Blocks until the vertices are read back for the mesh's bounds. After a device loss
+    /// the mesh is uploaded from that copy instead of being generated again.
+    pub fn generate_mesh(
+        &mut self,
+        shader: &[u8],
+        vertex_count: u32,
+        parameters: [f32; 4],
+        indices: &[u32],
+    ) -> Result<MeshId, EngineError> {
+        if indices.is_empty() {
+            return Err(anyhow!("Can't generate a mesh without indices").into());
+        }
+        let generated = self.mesh_generator.generate(
+            &self.device,
+            &self.memory_properties,
+            self.pipeline_cache.cache,
+            shader,
+            vertex_count,
+            parameters,
+        )?;
+        let bounds = Aabb::from_points(generated.vertices.iter().map(|vertex| Point3::from(vertex.position)));
+        let result = self.take_validation_error().and_then(|()| {
+            bounds.ok_or_else(|| anyhow!("Generated mesh has no vertices").into())
+        });
+        let bounds = match result {
+            Ok(bounds) => bounds,
+            Err(err) => {
+                generated.vertex_buffer.destroy(&self.device);
+                return Err(err);
+            }
+        };
+        let mesh = self.add_mesh(generated.vertex_buffer, bounds, generated.vertices, indices)?;
+        if generated.transfer.is_needed() {
+            self.pending_acquires.push(generated.transfer);
+        }
+        Ok(mesh)
+    }
+
+    /// Uploads the indices for `vertex_buffer` and adds the mesh. The vertex buffer is
+    /// destroyed if that fails.
+    fn add_mesh(
+        &mut self,
+        vertex_buffer: AllocatedBuffer,
+        bounds: Aabb,
+        vertices: Vec<Vertex>,
+        indices: &[u32],
+    ) -> Result<MeshId, EngineError> {
+        let index_buffer = match self.upload_buffer(indices, BufferUsageFlags::INDEX_BUFFER) {
+            Ok(index_buffer) => index_buffer,
+            Err(err) => {
+                vertex_buffer.destroy(&self.device);
+                return Err(err);
+            }
+        };
+        let mesh = Mesh {
+            vertex_buffer,
+            index_buffer,
+            index_count: indices.len() as u32,
+            bounds,
+            vertices,
+            indices: indices.to_vec(),
+        };
+        let mesh_index = self.meshes.len();
+        self.debug_names.name_buffer(&mesh.vertex_buffer, &format!("mesh {mesh_index} vertices"));
+        self.debug_names.name_buffer(&mesh.index_buffer, &format!("mesh {mesh_index} indices"));
+        self.meshes.push(mesh);
+        Ok(MeshId(mesh_index))
+    }
+
+    /// Creates a texture from tightly packed RGBA8 texels, uploaded at the start of the
+    /// next frame.
+    pub fn create_texture(
+        &mut self,
+        width: u32,
+        height: u32,
+        encoding: TextureEncoding,
+        rgba: &[u8],
+    ) -> Result<TextureId, EngineError> {
+        Ok(self
+            .materials
+            .create_texture(&self.device, Extent2D { width, height }, encoding, rgba)?)
+    }
+
+    pub fn create_material(
+        &mut self,
+        template: MaterialTemplate,
+        parameters: MaterialParameters,
+        textures: MaterialTextures,
+    ) -> Result<MaterialId, EngineError> {
+        Ok(self
+            .materials
+            .create_material(&self.device, template, parameters, textures)?)
+    }
+
+    /// Takes effect with the next frame.
+    pub fn set_material_parameters(
+        &mut self,
+        material: MaterialId,
+        parameters: MaterialParameters,
+    ) -> Result<(), EngineError> {
+        self.materials.set_parameters(material, parameters)
+    }
+
+    /// Draws `mesh` with `material` in the next frame's scene pass, in addition to the scene.
+    pub fn draw_mesh(
+        &mut self,
+        mesh: MeshId,
+        material: MaterialId,
+        transform: Matrix4<f32>,
+    ) -> Result<(), EngineError> {
+        let bounds = self.meshes.get(mesh.0).ok_or(EngineError::UnknownMesh(mesh))?.bounds;
+        if !self.materials.contains(material) {
+            return Err(EngineError::UnknownMaterial(material));
+        }
+        self.render_objects.push(RenderObject {
+            mesh,
+            material,
+            transform,
+            bounds: bounds.transformed(&transform),
+        });
+        Ok(())
+    }
+
+    /// Nodes with meshes attached are drawn every frame.
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    pub fn scene_mut(&mut self) -> &mut Scene {
+        &mut self.scene
+    }
+
+
+
+    pub fn set_frustum_culling(&mut self, frustum_culling: bool) {
+        self.config.frustum_culling = frustum_culling;
+    }
+
+
+    pub fn lighting_mut(&mut self) -> &mut SceneLighting {
+        &mut self.lighting
+    }
+
+    /// Writes this frame's [`GpuSceneData`]. Must only be called once the frame's fence
+    /// has been waited on, the buffer may still be read by the GPU before that.
+    fn upload_scene_data(&mut self) -> Result<(), EngineError> {
+        let now = Instant::now();
+        let delta: Duration = now - self.last_frame_time;
+        self.last_frame_time = now;
+        self.frame_time = delta;
+        let scene_data = GpuSceneData::new(
+            &self.camera,
+            &self.lighting,
+            (now - self.start_time).as_secs_f32(),
+            delta.as_secs_f32(),
+            self.frame_number,
+        );
+        Ok(self.frame_data[self.frame].scene_buffer.write(&[scene_data])?)
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+
+    /// Every sample count the selected device can use for both color and depth targets.
+    pub fn supported_msaa_samples(&self) -> Vec<MsaaSamples> {
+        let supported =
+            physical_devices::supported_sample_counts(&self.instance, self.physical_device);
+        MsaaSamples::ALL
+            .into_iter()
+            .filter(|samples| supported.contains(samples.sample_count_flags()))
+            .collect()
+    }
+
+    /// Switches the scene to a different sample count, rebuilding the render targets.
+    /// Unsupported counts are clamped to the highest supported one below them.
+    pub fn set_msaa_samples(&mut self, samples: MsaaSamples) -> Result<(), EngineError> {
+        let samples = Self::clamp_msaa_samples(&self.instance, self.physical_device, samples);
+        self.config.msaa_samples = samples;
+        if samples == self.render_targets.samples {
+            return Ok(());
+        }
+        self.recreate_render_targets()?;
+        self.materials
+            .rebuild_pipelines(&self.device, samples.sample_count_flags(), self.pipeline_cache.cache)?;
+        self.materials.name_objects(&self.debug_names);
+        Ok(())
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), EngineError> {
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        unsafe { self.device.device_wait_idle() }.vk_context("vkDeviceWaitIdle")?;
+
+        let swapchain_support_details = SwapchainSupportDetails::query_swapchain_support(
+            &self.surface_instance,
+            self.physical_device,
+            self.surface_khr,
+        )?;
+        self.present_mode = swapchain::choose_present_mode(&swapchain_support_details, self.config.vsync);
+        unsafe {
+            for image_view in self.swapchain_image_views.drain(..) {
+                self.device.destroy_image_view(image_view, None);
+            }
+            for semaphore in self.present_semaphores.drain(..) {
+                self.device.destroy_semaphore(semaphore, None);
+            }
+            self.swapchain_device.destroy_swapchain(self.swapchain, None);
+        }
+        self.swapchain = swapchain::create_swapchain(
+            &self.swapchain_device,
+            swapchain_support_details,
+            self.queue_indices,
+            self.surface_khr,
+            width,
+            height,
+            self.present_mode,
+        )?;
+        self.images = swapchain::create_swapchain_images(&self.swapchain_device, self.swapchain)?;
+        self.swapchain_image_views =
+            swapchain::create_swapchain_image_views(&self.device, &self.images)?;
+        self.present_semaphores = Self::create_present_semaphores(&self.device, self.images.len())?;
+        self.swapchain_extent = Extent2D::default().width(width).height(height);
+        self.name_swapchain_objects();
+        self.camera.set_viewport(width, height);
+        self.recreate_render_targets()
+    }
+
+
+    /// Switches between FIFO and the fastest available present mode, recreating the swapchain.
+    pub fn set_vsync(&mut self, vsync: bool) -> Result<(), EngineError> {
+        if vsync == self.config.vsync {
+            return Ok(());
+        }
+        self.config.vsync = vsync;
+        self.resize(self.swapchain_extent.width, self.swapchain_extent.height)
+    }
+
+    pub fn background(&self) -> Background {
+        self.config.background
+    }
+
+    pub fn set_background(&mut self, background: Background) {
+        self.config.background = background;
+    }
+
+
+
+    /// Changes how many frames the CPU may record ahead of the GPU. Waits for the device to
+    /// go idle and rebuilds every per frame resource, so this is not meant for every frame.
+    pub fn set_frames_in_flight(&mut self, frames_in_flight: usize) -> Result<(), EngineError> {
+        let frames_in_flight = frames_in_flight.clamp(MIN_FRAMES_IN_FLIGHT, MAX_FRAMES_IN_FLIGHT);
+        self.config.frames_in_flight = frames_in_flight;
+        if frames_in_flight == self.frame_data.len() {
+            return Ok(());
+        }
+        unsafe { self.device.device_wait_idle() }.vk_context("vkDeviceWaitIdle")?;
+        self.frame_sync.set_frames_in_flight(frames_in_flight);
+        self.frame_sync.run_completed(&self.device)?;
+
+        for frame in self.frame_data.drain(..) {
+            frame.destroy(&self.device);
+        }
+        self.descriptor_allocator.destroy(&self.device);
+        self.descriptor_allocator = Self::create_frame_descriptor_allocator(&self.device, frames_in_flight)?;
+        self.frame_data = Self::create_frames(
+            &self.device,
+            &self.memory_properties,
+            self.queue_indices.graphics_queue_index,
+            &self.descriptor_allocator,
+            self.global_set_layout,
+            &self.enabled_features,
+            &self.config,
+        )?;
+        self.ui.set_frames_in_flight(&self.device, frames_in_flight)?;
+        self.materials.set_frames_in_flight(&self.device, frames_in_flight)?;
+        self.diagnostics.destroy(&self.device);
+        self.diagnostics = DeviceDiagnostics::new(
+            &self.instance,
+            &self.device,
+            &self.memory_properties,
+            &self.enabled_features,
+            frames_in_flight,
+        )?;
+        self.profiler.set_frames_in_flight(&self.device, frames_in_flight)?;
+        self.frame = 0;
+
+        for (frame_index, frame) in self.frame_data.iter().enumerate() {
+            frame.name_objects(&self.debug_names, frame_index);
+        }
+        self.diagnostics.name_objects(&self.debug_names);
+        self.profiler.name_objects(&self.debug_names);
+        info!("Now running with {frames_in_flight} frames in flight");
+        Ok(())
+    }
+
+    fn create_frame_descriptor_allocator(
+        device: &Device,
+        frames_in_flight: usize,
+    ) -> Result<DescriptorAllocator, EngineError> {
+        Ok(DescriptorAllocator::new(
+            device,
+            frames_in_flight as u32,
+            &[(DescriptorType::UNIFORM_BUFFER, 1.0)],
+        )?)
+    }
+
+    fn create_frames(
+        device: &Device,
+        memory_properties: &PhysicalDeviceMemoryProperties,
+        queue_family_index: u32,
+        descriptor_allocator: &DescriptorAllocator,
+        global_set_layout: DescriptorSetLayout,
+        enabled_features: &EnabledFeatures,
+        config: &EngineConfig,
+    ) -> Result<Vec<FrameData>, EngineError> {
+        (0..config.frames_in_flight)
+            .map(|_| {
+                Ok(FrameData::new(
+                    device,
+                    memory_properties,
+                    queue_family_index,
+                    create_semaphore(device)?,
+                    create_fence(device, FenceCreateFlags::SIGNALED)?,
+                    descriptor_allocator.allocate(device, global_set_layout)?,
+                    FrameQueries::new(device, enabled_features)?,
+                    config.recording_threads,
+                )?)
+            })
+            .collect()
+    }
+
+    fn create_present_semaphores(device: &Device, image_count: usize) -> Result<Vec<Semaphore>, EngineError> {
+        (0..image_count).map(|_| create_semaphore(device)).collect()
+    }
+
+
+    /// Resizes the render targets relative to the swapchain, the result is scaled when
+    /// it is blitted to the swapchain image.
+    pub fn set_render_scale(&mut self, render_scale: f32) -> Result<(), EngineError> {
+        let render_scale = render_scale.clamp(MIN_RENDER_SCALE, MAX_RENDER_SCALE);
+        if render_scale == self.config.render_scale {
+            return Ok(());
+        }
+        self.config.render_scale = render_scale;
+        self.recreate_render_targets()
+    }
+
+    fn render_extent(swapchain_extent: Extent2D, render_scale: f32) -> Extent2D {
+        Extent2D::default()
+            .width(((swapchain_extent.width as f32 * render_scale) as u32).max(1))
+            .height(((swapchain_extent.height as f32 * render_scale) as u32).max(1))
+    }
+
+    fn recreate_render_targets(&mut self) -> Result<(), EngineError> {
+        unsafe { self.device.device_wait_idle() }.vk_context("vkDeviceWaitIdle")?;
+        self.render_targets.destroy(&self.device);
+        let extent = Self::render_extent(self.swapchain_extent, self.config.render_scale);
+        self.render_targets = RenderTargets::new(
+            &self.device,
+            &self.memory_properties,
+            extent,
+            self.config.msaa_samples,
+        )?;
+        self.render_targets.name_objects(&self.debug_names);
+        self.tonemapper
+            .set_draw_image(&self.device, &self.render_targets.draw_image);
+        info!(
+            "Render targets recreated at {}x{} with {:?}",
+            extent.width, extent.height, self.config.msaa_samples
+        );
+        Ok(())
+    }
+
+    fn clamp_msaa_samples(
+        instance: &ash::Instance,
+        physical_device: PhysicalDevice,
+        samples: MsaaSamples,
+    ) -> MsaaSamples {
+        let supported = physical_devices::supported_sample_counts(instance, physical_device);
+        let clamped = samples.clamp_to(supported);
+        if clamped != samples {
+            warn!("{samples:?} MSAA is not supported by the selected device, using {clamped:?}");
+        }
+        clamped
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.device.device_wait_idle();
+            self.frame_sync.run_all(&self.device);
+            self.ui.destroy(&self.device);
+            self.materials.destroy(&self.device);
+            self.tonemapper.destroy(&self.device);
+            self.mesh_generator.destroy(&self.device);
+            for mesh in self.meshes.iter() {
+                mesh.destroy(&self.device);
+            }
+            self.upload_context.destroy(&self.device);
+            self.diagnostics.destroy(&self.device);
+            self.profiler.destroy(&self.device);
+            self.frame_sync.destroy(&self.device);
+            self.pipeline_cache.save(&self.device);
+            self.pipeline_cache.destroy(&self.device);
+            self.render_targets.destroy(&self.device);
+            for frame in self.frame_data.iter() {
+                frame.destroy(&self.device);
+            }
+            self.descriptor_allocator.destroy(&self.device);
+            self.device.destroy_descriptor_set_layout(self.global_set_layout, None);
+            for image_view in self.swapchain_image_views.iter() {
+                self.device.destroy_image_view(*image_view, None);
+            }
+            for semaphore in self.present_semaphores.iter() {
+                self.device.destroy_semaphore(*semaphore, None);
+            }
+            self.swapchain_device.destroy_swapchain(self.swapchain, None);
+            self.device.destroy_device(None);
+            self.surface_instance.destroy_surface(self.surface_khr, None);
+            if let Some(debugger) = &self.debugger {
+                debugger.destroy();
+            }
+            self.instance.destroy_instance(None);
+        }
     }
 }
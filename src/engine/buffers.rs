@@ -0,0 +1,152 @@
+use std::{ffi::c_void, mem::size_of, ptr};
+
+use anyhow::{anyhow, Error};
+use ash::{
+    vk::{
+        Buffer, BufferCreateInfo, BufferUsageFlags, DeviceMemory, DeviceSize, MemoryAllocateInfo,
+        MemoryMapFlags, MemoryPropertyFlags, PhysicalDeviceMemoryProperties, SharingMode,
+    },
+    Device,
+};
+use bytemuck::Pod;
+
+use super::memory::find_memory_type_index;
+
+pub struct AllocatedBuffer {
+    pub buffer: Buffer,
+    pub memory: DeviceMemory,
+    pub size: DeviceSize,
+    /// Persistent mapping for host visible buffers, null otherwise.
+    mapped: *mut c_void,
+}
+
+impl AllocatedBuffer {
+    pub fn is_mapped(&self) -> bool {
+        !self.mapped.is_null()
+    }
+
+    /// Copies `data` to the start of a host visible buffer.
+    pub fn write<T: Pod>(&self, data: &[T]) -> Result<(), Error> {
+        self.write_at(0, data)
+    }
+
+    pub fn write_at<T: Pod>(&self, offset: DeviceSize, data: &[T]) -> Result<(), Error> {
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        let byte_count = bytes.len() as DeviceSize;
+        if !self.is_mapped() {
+            return Err(anyhow!("Buffer is not host visible"));
+        }
+        if offset + byte_count > self.size {
+            return Err(anyhow!(
+                "Write of {byte_count} bytes at {offset} overflows buffer of {} bytes",
+                self.size
+            ));
+        }
+        unsafe {
+            ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                (self.mapped as *mut u8).add(offset as usize),
+                byte_count as usize,
+            )
+        };
+        Ok(())
+    }
+
+    /// Reads `count` values starting at `offset` from a host visible buffer.
+    pub fn read_at<T: Pod>(&self, offset: DeviceSize, count: usize) -> Result<Vec<T>, Error> {
+        let byte_count = (count * size_of::<T>()) as DeviceSize;
+        if !self.is_mapped() {
+            return Err(anyhow!("Buffer is not host visible"));
+        }
+        if offset + byte_count > self.size {
+            return Err(anyhow!(
+                "Read of {byte_count} bytes at {offset} overflows buffer of {} bytes",
+                self.size
+            ));
+        }
+        let mut values = vec![T::zeroed(); count];
+        let bytes: &mut [u8] = bytemuck::cast_slice_mut(&mut values);
+        unsafe {
+            ptr::copy_nonoverlapping(
+                (self.mapped as *const u8).add(offset as usize),
+                bytes.as_mut_ptr(),
+                bytes.len(),
+            )
+        };
+        Ok(values)
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        unsafe {
+            if self.is_mapped() {
+                device.unmap_memory(self.memory);
+            }
+            device.destroy_buffer(self.buffer, None);
+            device.free_memory(self.memory, None);
+        }
+    }
+}
+
+/// Creates a buffer with its own memory allocation. Host visible buffers stay mapped
+/// for their whole lifetime.
+pub fn create_buffer(
+    device: &Device,
+    memory_properties: &PhysicalDeviceMemoryProperties,
+    size: DeviceSize,
+    usage: BufferUsageFlags,
+    memory_flags: MemoryPropertyFlags,
+) -> Result<AllocatedBuffer, Error> {
+    let create_info = BufferCreateInfo::default()
+        .size(size)
+        .usage(usage)
+        .sharing_mode(SharingMode::EXCLUSIVE);
+    let buffer = unsafe { device.create_buffer(&create_info, None)? };
+    let (memory, mapped) = match allocate_buffer_memory(device, memory_properties, buffer, size, memory_flags) {
+        Ok(allocation) => allocation,
+        Err(err) => {
+            unsafe { device.destroy_buffer(buffer, None) };
+            return Err(err);
+        }
+    };
+
+    Ok(AllocatedBuffer {
+        buffer,
+        memory,
+        size,
+        mapped,
+    })
+}
+
+/// Allocates and binds memory for `buffer` and maps it if it is host visible. Nothing is
+/// left allocated on failure.
+fn allocate_buffer_memory(
+    device: &Device,
+    memory_properties: &PhysicalDeviceMemoryProperties,
+    buffer: Buffer,
+    size: DeviceSize,
+    memory_flags: MemoryPropertyFlags,
+) -> Result<(DeviceMemory, *mut c_void), Error> {
+    let requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+    let memory_type_index = find_memory_type_index(memory_properties, &requirements, memory_flags)
+        .ok_or_else(|| anyhow!("No memory type with {memory_flags:?} for buffer"))?;
+    let allocate_info = MemoryAllocateInfo::default()
+        .allocation_size(requirements.size)
+        .memory_type_index(memory_type_index);
+    let memory = unsafe { device.allocate_memory(&allocate_info, None)? };
+
+    let mapped = unsafe {
+        device.bind_buffer_memory(buffer, memory, 0).and_then(|()| {
+            match memory_flags.contains(MemoryPropertyFlags::HOST_VISIBLE) {
+                true => device.map_memory(memory, 0, size, MemoryMapFlags::empty()),
+                false => Ok(ptr::null_mut()),
+            }
+        })
+    };
+    match mapped {
+        Ok(mapped) => Ok((memory, mapped)),
+        Err(err) => {
+            unsafe { device.free_memory(memory, None) };
+            Err(err.into())
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use std::{collections::HashSet, time::Duration};
+
+use cgmath::{InnerSpace, Point3, Rad, Vector3, Zero};
+use log::warn;
+use winit::{
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::{KeyCode, PhysicalKey},
+    window::{CursorGrabMode, Window},
+};
+
+use super::Camera;
+
+const MAX_PITCH: Rad<f32> = Rad(1.55);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControllerMode {
+    /// Free WASD movement, mouse rotates the camera in place.
+    Fly,
+    /// Mouse rotates the camera around `orbit_target`, scroll changes the distance.
+    Orbit,
+}
+
+/// Turns winit input into camera movement.
+///
+/// Events are only accumulated while handling them, the camera itself is moved in
+/// [`CameraController::update`] so movement speed is independent of the event rate.
+pub struct CameraController {
+    mode: ControllerMode,
+    pub move_speed: f32,
+    pub sprint_multiplier: f32,
+    pub look_sensitivity: f32,
+    pub zoom_speed: f32,
+    pub orbit_target: Point3<f32>,
+    pub orbit_distance: f32,
+    pressed_keys: HashSet<KeyCode>,
+    mouse_delta: (f64, f64),
+    scroll_delta: f32,
+    cursor_grabbed: bool,
+    recenter_orbit: bool,
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        Self {
+            mode: ControllerMode::Fly,
+            move_speed: 5.0,
+            sprint_multiplier: 4.0,
+            look_sensitivity: 0.002,
+            zoom_speed: 0.5,
+            orbit_target: Point3::new(0.0, 0.0, 0.0),
+            orbit_distance: 5.0,
+            pressed_keys: HashSet::new(),
+            mouse_delta: (0.0, 0.0),
+            scroll_delta: 0.0,
+            cursor_grabbed: false,
+            recenter_orbit: false,
+        }
+    }
+}
+
+impl CameraController {
+    pub fn cursor_grabbed(&self) -> bool {
+        self.cursor_grabbed
+    }
+
+    /// Returns `true` if the event was consumed by the controller.
+    pub fn handle_window_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(key_code),
+                        state,
+                        repeat,
+                        ..
+                    },
+                ..
+            } => {
+                match (key_code, state) {
+                    (KeyCode::Escape, ElementState::Pressed) => {
+                        self.set_cursor_grab(window, false)
+                    }
+                    (KeyCode::Tab, ElementState::Pressed) if !repeat => self.toggle_mode(),
+                    (_, ElementState::Pressed) => {
+                        self.pressed_keys.insert(*key_code);
+                    }
+                    (_, ElementState::Released) => {
+                        self.pressed_keys.remove(key_code);
+                    }
+                }
+                true
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Right,
+                ..
+            } => {
+                self.set_cursor_grab(window, !self.cursor_grabbed);
+                true
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.scroll_delta += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 50.0,
+                };
+                true
+            }
+            WindowEvent::Focused(false) => {
+                self.pressed_keys.clear();
+                self.set_cursor_grab(window, false);
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Raw mouse motion from `DeviceEvent::MouseMotion`, only used while the cursor is grabbed.
+    pub fn handle_mouse_motion(&mut self, delta: (f64, f64)) {
+        if self.cursor_grabbed {
+            self.mouse_delta.0 += delta.0;
+            self.mouse_delta.1 += delta.1;
+        }
+    }
+
+    pub fn set_cursor_grab(&mut self, window: &Window, grabbed: bool) {
+        let result = match grabbed {
+            true => window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined)),
+            false => window.set_cursor_grab(CursorGrabMode::None),
+        };
+        if let Err(err) = result {
+            warn!("Failed to change the cursor grab: {err}");
+            return;
+        }
+        window.set_cursor_visible(!grabbed);
+        self.cursor_grabbed = grabbed;
+        self.mouse_delta = (0.0, 0.0);
+    }
+
+    pub fn mode(&self) -> ControllerMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: ControllerMode) {
+        if mode == self.mode {
+            return;
+        }
+        self.mode = mode;
+        // Orbit around whatever the camera is looking at instead of jumping to the old target.
+        self.recenter_orbit = mode == ControllerMode::Orbit;
+    }
+
+    pub fn toggle_mode(&mut self) {
+        self.set_mode(match self.mode {
+            ControllerMode::Fly => ControllerMode::Orbit,
+            ControllerMode::Orbit => ControllerMode::Fly,
+        });
+    }
+
+    pub fn update(&mut self, camera: &mut Camera, delta_time: Duration) {
+        let dt = delta_time.as_secs_f32();
+        let (dx, dy) = std::mem::take(&mut self.mouse_delta);
+        let scroll = std::mem::take(&mut self.scroll_delta);
+
+        camera.yaw += Rad(dx as f32 * self.look_sensitivity);
+        camera.pitch -= Rad(dy as f32 * self.look_sensitivity);
+        camera.pitch = Rad(camera.pitch.0.clamp(-MAX_PITCH.0, MAX_PITCH.0));
+
+        match self.mode {
+            ControllerMode::Fly => {
+                // Scrolling scales the speed exponentially so it feels the same at any magnitude.
+                self.move_speed *= (1.0 + self.zoom_speed * 0.2).powf(scroll);
+
+                let mut direction = Vector3::zero();
+                let forward = camera.forward();
+                let right = camera.right();
+                for key in self.pressed_keys.iter() {
+                    direction += match key {
+                        KeyCode::KeyW => forward,
+                        KeyCode::KeyS => -forward,
+                        KeyCode::KeyD => right,
+                        KeyCode::KeyA => -right,
+                        KeyCode::KeyE | KeyCode::Space => Vector3::unit_y(),
+                        KeyCode::KeyQ | KeyCode::ControlLeft => -Vector3::unit_y(),
+                        _ => Vector3::zero(),
+                    };
+                }
+                if direction.magnitude2() > 0.0 {
+                    let speed = match self.pressed_keys.contains(&KeyCode::ShiftLeft) {
+                        true => self.move_speed * self.sprint_multiplier,
+                        false => self.move_speed,
+                    };
+                    camera.position += direction.normalize() * speed * dt;
+                }
+            }
+            ControllerMode::Orbit => {
+                if std::mem::take(&mut self.recenter_orbit) {
+                    self.orbit_target = camera.position + camera.forward() * self.orbit_distance;
+                }
+                self.orbit_distance =
+                    (self.orbit_distance * (1.0 - self.zoom_speed * 0.2 * scroll)).max(0.1);
+                camera.position = self.orbit_target - camera.forward() * self.orbit_distance;
+            }
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use cgmath::{Angle, Deg, InnerSpace, Matrix4, Point3, Rad, Vector3};
+
+pub mod controller;
+
+/// All projections use reverse-Z: the near plane maps to depth 1 and the far plane
+/// (or infinity) to depth 0, so depth tests must use `GREATER_OR_EQUAL` and clear to 0.
+#[derive(Debug, Clone, Copy)]
+pub enum Projection {
+    Perspective { fovy: Rad<f32>, near: f32 },
+    Orthographic { height: f32, near: f32, far: f32 },
+}
+
+#[derive(Debug, Clone)]
+pub struct Camera {
+    pub position: Point3<f32>,
+    pub yaw: Rad<f32>,
+    pub pitch: Rad<f32>,
+    pub projection: Projection,
+    pub aspect: f32,
+}
+
+impl Camera {
+    pub fn new(position: Point3<f32>, aspect: f32) -> Camera {
+        Camera {
+            position,
+            yaw: Rad::from(Deg(-90.0)),
+            pitch: Rad(0.0),
+            projection: Projection::Perspective {
+                fovy: Rad::from(Deg(70.0)),
+                near: 0.1,
+            },
+            aspect,
+        }
+    }
+
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.aspect = width as f32 / height as f32;
+        }
+    }
+
+    pub fn forward(&self) -> Vector3<f32> {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        Vector3::new(cos_yaw * cos_pitch, sin_pitch, sin_yaw * cos_pitch).normalize()
+    }
+
+    pub fn right(&self) -> Vector3<f32> {
+        self.forward().cross(Vector3::unit_y()).normalize()
+    }
+
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_to_rh(self.position, self.forward(), Vector3::unit_y())
+    }
+
+    /// Projection into Vulkan clip space (Y down, depth in 0..1, reverse-Z).
+    pub fn projection_matrix(&self) -> Matrix4<f32> {
+        match self.projection {
+            Projection::Perspective { fovy, near } => {
+                let focal_length = 1.0 / (fovy / 2.0).tan();
+                #[rustfmt::skip]
+                let projection = Matrix4::new(
+                    focal_length / self.aspect, 0.0, 0.0, 0.0,
+                    0.0, -focal_length, 0.0, 0.0,
+                    0.0, 0.0, 0.0, -1.0,
+                    0.0, 0.0, near, 0.0,
+                );
+                projection
+            }
+            Projection::Orthographic { height, near, far } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * self.aspect;
+                #[rustfmt::skip]
+                let projection = Matrix4::new(
+                    1.0 / half_width, 0.0, 0.0, 0.0,
+                    0.0, -1.0 / half_height, 0.0, 0.0,
+                    0.0, 0.0, 1.0 / (far - near), 0.0,
+                    0.0, 0.0, far / (far - near), 1.0,
+                );
+                projection
+            }
+        }
+    }
+
+    pub fn view_projection_matrix(&self) -> Matrix4<f32> {
+        self.projection_matrix() * self.view_matrix()
+    }
+}
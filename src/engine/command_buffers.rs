@@ -1,48 +1,47 @@
-use anyhow::Error;
 use ash::{
     vk::{
-        CommandBuffer, CommandBufferAllocateInfo, CommandBufferBeginInfo, CommandBufferLevel,
-        CommandBufferUsageFlags, CommandPool, CommandPoolCreateFlags, CommandPoolCreateInfo,
+        CommandBuffer, CommandBufferAllocateInfo, CommandBufferLevel, CommandPool, CommandPoolCreateFlags,
+        CommandPoolCreateInfo,
     },
     Device,
 };
 
-pub fn create_command_pool(device: &Device, queue_family_index: u32) -> Result<CommandPool, Error> {
+use super::errors::engine_error::{EngineError, VkResultExt};
+
+pub fn create_command_pool(device: &Device, queue_family_index: u32) -> Result<CommandPool, EngineError> {
     let create_info = CommandPoolCreateInfo::default()
         .queue_family_index(queue_family_index)
         .flags(CommandPoolCreateFlags::RESET_COMMAND_BUFFER);
-    Ok(unsafe { device.create_command_pool(&create_info, None).unwrap() })
+    unsafe { device.create_command_pool(&create_info, None) }.vk_object_context(
+        "vkCreateCommandPool",
+        format!("queue family {queue_family_index}"),
+    )
 }
 
 pub fn create_command_buffer(
     device: &Device,
     command_pool: CommandPool,
-) -> Result<CommandBuffer, Error> {
+) -> Result<CommandBuffer, EngineError> {
     let command_buffer_allocate_info = CommandBufferAllocateInfo::default()
         .command_pool(command_pool)
         .level(CommandBufferLevel::PRIMARY)
         .command_buffer_count(1);
 
-    Ok(unsafe {
-        *device
-            .allocate_command_buffers(&command_buffer_allocate_info)
-            .unwrap()
-            .get(0)
-            .unwrap()
-    })
+    let command_buffers = unsafe { device.allocate_command_buffers(&command_buffer_allocate_info) }
+        .vk_context("vkAllocateCommandBuffers")?;
+    Ok(command_buffers[0])
 }
 
-pub fn begin_command_buffer(
+pub fn create_secondary_command_buffers(
     device: &Device,
-    command_buffer: CommandBuffer,
-    flags: CommandBufferUsageFlags,
-) -> Result<(), Error> {
-    let begin_info = CommandBufferBeginInfo::default().flags(flags);
+    command_pool: CommandPool,
+    count: u32,
+) -> Result<Vec<CommandBuffer>, EngineError> {
+    let command_buffer_allocate_info = CommandBufferAllocateInfo::default()
+        .command_pool(command_pool)
+        .level(CommandBufferLevel::SECONDARY)
+        .command_buffer_count(count);
 
-    unsafe {
-        device
-            .begin_command_buffer(command_buffer, &begin_info)
-            .unwrap()
-    };
-    Ok(())
+    unsafe { device.allocate_command_buffers(&command_buffer_allocate_info) }
+        .vk_context("vkAllocateCommandBuffers")
 }
@@ -0,0 +1,387 @@
+use std::ffi::c_void;
+
+use ash::{
+    vk::{
+        BlitImageInfo2, Buffer, BufferCopy, BufferImageCopy, BufferMemoryBarrier2, CommandBuffer,
+        CommandBufferBeginInfo, CommandBufferResetFlags, CommandBufferSubmitInfo,
+        CommandBufferUsageFlags, DebugUtilsLabelEXT, DependencyInfo, DescriptorSet, DeviceSize,
+        Extent2D, Filter, Image, ImageLayout, ImageMemoryBarrier2, IndexType, Pipeline,
+        PipelineBindPoint, PipelineLayout, PipelineStageFlags, PipelineStageFlags2,
+        QueryControlFlags, QueryPool, Rect2D, RenderingInfo, ShaderStageFlags, Viewport,
+    },
+    Device,
+};
+use log::warn;
+
+use super::{
+    errors::engine_error::{EngineError, VkResultExt},
+    util::{full_blit_region, image_transition_barrier},
+};
+
+/// Records into a command buffer, which is in the recording state for as long as the
+/// encoder exists.
+///
+/// The Vulkan states map onto types: a command buffer in the initial state is a plain
+/// [`CommandBuffer`], [`CommandEncoder::begin`] moves it to recording and
+/// [`CommandEncoder::finish`] to executable, returning the only thing that can be
+/// submitted. Draws are only available on the [`RenderingScope`] of a dynamic rendering,
+/// which ends the rendering when it goes out of scope, and while it exists the encoder
+/// can't record commands that aren't allowed inside rendering.
+///
+/// Keeps its own handle to the device so it can be held across calls that borrow the
+/// engine. The command buffer handle never leaves the encoder, every command goes through
+/// a typed method, including the few from extensions, which take the extension's loader.
+pub struct CommandEncoder {
+    device: Device,
+    command_buffer: CommandBuffer,
+    finished: bool,
+}
+
+/// A command buffer that finished recording, ready to be submitted.
+#[derive(Debug, Clone, Copy)]
+pub struct ExecutableCommands {
+    command_buffer: CommandBuffer,
+}
+
+impl ExecutableCommands {
+    pub fn submit_info(&self) -> CommandBufferSubmitInfo<'static> {
+        CommandBufferSubmitInfo::default().command_buffer(self.command_buffer)
+    }
+}
+
+impl CommandEncoder {
+    /// Begins `command_buffer`, which has to be in the initial state.
+    pub fn begin(
+        device: &Device,
+        command_buffer: CommandBuffer,
+        flags: CommandBufferUsageFlags,
+    ) -> Result<CommandEncoder, EngineError> {
+        Self::begin_with(device, command_buffer, &CommandBufferBeginInfo::default().flags(flags))
+    }
+
+    /// Resets `command_buffer` first, its pool needs `RESET_COMMAND_BUFFER`.
+    pub fn reset_and_begin(
+        device: &Device,
+        command_buffer: CommandBuffer,
+        flags: CommandBufferUsageFlags,
+    ) -> Result<CommandEncoder, EngineError> {
+        unsafe { device.reset_command_buffer(command_buffer, CommandBufferResetFlags::empty()) }
+            .vk_context("vkResetCommandBuffer")?;
+        Self::begin(device, command_buffer, flags)
+    }
+
+    /// Begins with a full begin info, e.g. the inheritance of a secondary command buffer.
+    pub fn begin_with(
+        device: &Device,
+        command_buffer: CommandBuffer,
+        begin_info: &CommandBufferBeginInfo,
+    ) -> Result<CommandEncoder, EngineError> {
+        unsafe { device.begin_command_buffer(command_buffer, begin_info) }.vk_context("vkBeginCommandBuffer")?;
+        Ok(CommandEncoder {
+            device: device.clone(),
+            command_buffer,
+            finished: false,
+        })
+    }
+
+    /// Ends recording.
+    pub fn finish(mut self) -> Result<ExecutableCommands, EngineError> {
+        self.finished = true;
+        unsafe { self.device.end_command_buffer(self.command_buffer) }.vk_context("vkEndCommandBuffer")?;
+        Ok(ExecutableCommands {
+            command_buffer: self.command_buffer,
+        })
+    }
+
+    pub fn pipeline_barrier(&mut self, dependency_info: &DependencyInfo) {
+        unsafe { self.device.cmd_pipeline_barrier2(self.command_buffer, dependency_info) };
+    }
+
+    pub fn image_barriers(&mut self, barriers: &[ImageMemoryBarrier2]) {
+        self.pipeline_barrier(&DependencyInfo::default().image_memory_barriers(barriers));
+    }
+
+    pub fn buffer_barriers(&mut self, barriers: &[BufferMemoryBarrier2]) {
+        self.pipeline_barrier(&DependencyInfo::default().buffer_memory_barriers(barriers));
+    }
+
+    /// A full barrier moving the whole image between layouts, see [`image_transition_barrier`].
+    pub fn transition_image(&mut self, image: Image, current_layout: ImageLayout, new_layout: ImageLayout) {
+        self.image_barriers(&[image_transition_barrier(image, current_layout, new_layout)]);
+    }
+
+    pub fn copy_buffer(&mut self, source: Buffer, destination: Buffer, regions: &[BufferCopy]) {
+        unsafe { self.device.cmd_copy_buffer(self.command_buffer, source, destination, regions) };
+    }
+
+    pub fn copy_buffer_to_image(
+        &mut self,
+        source: Buffer,
+        destination: Image,
+        destination_layout: ImageLayout,
+        regions: &[BufferImageCopy],
+    ) {
+        unsafe {
+            self.device
+                .cmd_copy_buffer_to_image(self.command_buffer, source, destination, destination_layout, regions)
+        };
+    }
+
+    /// Blits the first mip level of `source` onto `destination`, scaling and converting the
+    /// format if needed. `source` has to be in TRANSFER_SRC and `destination` in TRANSFER_DST.
+    pub fn blit_image(
+        &mut self,
+        source: Image,
+        destination: Image,
+        source_extent: Extent2D,
+        destination_extent: Extent2D,
+    ) {
+        let regions = [full_blit_region(source_extent, destination_extent)];
+        let blit_info = BlitImageInfo2::default()
+            .src_image(source)
+            .src_image_layout(ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .dst_image(destination)
+            .dst_image_layout(ImageLayout::TRANSFER_DST_OPTIMAL)
+            .filter(Filter::LINEAR)
+            .regions(&regions);
+        unsafe { self.device.cmd_blit_image2(self.command_buffer, &blit_info) };
+    }
+
+    pub fn dispatch(&mut self, group_count_x: u32, group_count_y: u32, group_count_z: u32) {
+        unsafe {
+            self.device
+                .cmd_dispatch(self.command_buffer, group_count_x, group_count_y, group_count_z)
+        };
+    }
+
+    /// Resets `count` queries starting at `first`, they have to be reset before each use.
+    pub fn reset_query_pool(&mut self, query_pool: QueryPool, first: u32, count: u32) {
+        unsafe {
+            self.device
+                .cmd_reset_query_pool(self.command_buffer, query_pool, first, count)
+        };
+    }
+
+    pub fn begin_query(&mut self, query_pool: QueryPool, query: u32, flags: QueryControlFlags) {
+        unsafe {
+            self.device
+                .cmd_begin_query(self.command_buffer, query_pool, query, flags)
+        };
+    }
+
+    pub fn end_query(&mut self, query_pool: QueryPool, query: u32) {
+        unsafe { self.device.cmd_end_query(self.command_buffer, query_pool, query) };
+    }
+
+    pub fn write_timestamp(&mut self, stage: PipelineStageFlags2, query_pool: QueryPool, query: u32) {
+        unsafe {
+            self.device
+                .cmd_write_timestamp2(self.command_buffer, stage, query_pool, query)
+        };
+    }
+
+    /// Every label needs a matching [`Self::end_debug_label`] in the same command buffer.
+    pub fn begin_debug_label(&mut self, debug_utils: &ash::ext::debug_utils::Device, label: &DebugUtilsLabelEXT) {
+        unsafe { debug_utils.cmd_begin_debug_utils_label(self.command_buffer, label) };
+    }
+
+    pub fn end_debug_label(&mut self, debug_utils: &ash::ext::debug_utils::Device) {
+        unsafe { debug_utils.cmd_end_debug_utils_label(self.command_buffer) };
+    }
+
+    /// `marker` is opaque to the driver and comes back in the checkpoint data.
+    pub fn set_checkpoint(
+        &mut self,
+        checkpoints: &ash::nv::device_diagnostic_checkpoints::Device,
+        marker: *const c_void,
+    ) {
+        unsafe { checkpoints.cmd_set_checkpoint(self.command_buffer, marker) };
+    }
+
+    /// Writes `marker` to `buffer` at `offset` once everything before `stage` finished.
+    pub fn write_buffer_marker(
+        &mut self,
+        buffer_marker: &ash::amd::buffer_marker::Device,
+        stage: PipelineStageFlags,
+        buffer: Buffer,
+        offset: DeviceSize,
+        marker: u32,
+    ) {
+        unsafe {
+            buffer_marker.cmd_write_buffer_marker(self.command_buffer, stage, buffer, offset, marker)
+        };
+    }
+
+    /// Begins dynamic rendering, ended when the returned scope is dropped or ended.
+    pub fn begin_rendering(&mut self, rendering_info: &RenderingInfo) -> RenderingScope<'_> {
+        unsafe { self.device.cmd_begin_rendering(self.command_buffer, rendering_info) };
+        RenderingScope {
+            encoder: self,
+            owns_rendering: true,
+        }
+    }
+
+    /// Continues the rendering of the primary command buffer this secondary is executed in,
+    /// which has to be begun with `RENDER_PASS_CONTINUE`. The scope doesn't end the rendering.
+    pub fn continue_rendering(&mut self) -> RenderingScope<'_> {
+        RenderingScope {
+            encoder: self,
+            owns_rendering: false,
+        }
+    }
+}
+
+impl BindCommands for CommandEncoder {}
+
+impl sealed::Recorder for CommandEncoder {
+    fn device(&self) -> &Device {
+        &self.device
+    }
+
+    fn command_buffer(&self) -> CommandBuffer {
+        self.command_buffer
+    }
+}
+
+impl Drop for CommandEncoder {
+    /// Ends a command buffer that was never finished, e.g. when recording bailed out on an
+    /// error, so it doesn't stay in the recording state.
+    fn drop(&mut self) {
+        if !self.finished {
+            warn!("Command buffer {:?} was dropped while recording", self.command_buffer);
+            let _ = unsafe { self.device.end_command_buffer(self.command_buffer) };
+        }
+    }
+}
+
+/// A dynamic rendering begun with [`CommandEncoder::begin_rendering`], or continued with
+/// [`CommandEncoder::continue_rendering`].
+pub struct RenderingScope<'a> {
+    encoder: &'a mut CommandEncoder,
+    owns_rendering: bool,
+}
+
+impl RenderingScope<'_> {
+    pub fn bind_vertex_buffer(&mut self, binding: u32, buffer: Buffer, offset: DeviceSize) {
+        unsafe {
+            self.encoder
+                .device
+                .cmd_bind_vertex_buffers(self.encoder.command_buffer, binding, &[buffer], &[offset])
+        };
+    }
+
+    pub fn bind_index_buffer(&mut self, buffer: Buffer, offset: DeviceSize, index_type: IndexType) {
+        unsafe {
+            self.encoder
+                .device
+                .cmd_bind_index_buffer(self.encoder.command_buffer, buffer, offset, index_type)
+        };
+    }
+
+    pub fn draw_indexed(
+        &mut self,
+        index_count: u32,
+        instance_count: u32,
+        first_index: u32,
+        vertex_offset: i32,
+        first_instance: u32,
+    ) {
+        unsafe {
+            self.encoder.device.cmd_draw_indexed(
+                self.encoder.command_buffer,
+                index_count,
+                instance_count,
+                first_index,
+                vertex_offset,
+                first_instance,
+            )
+        };
+    }
+
+    /// Only allowed if the rendering was begun with `CONTENTS_SECONDARY_COMMAND_BUFFERS`.
+    pub fn execute_commands(&mut self, secondary_command_buffers: &[CommandBuffer]) {
+        unsafe {
+            self.encoder
+                .device
+                .cmd_execute_commands(self.encoder.command_buffer, secondary_command_buffers)
+        };
+    }
+
+    pub fn end(self) {}
+}
+
+impl BindCommands for RenderingScope<'_> {}
+
+impl sealed::Recorder for RenderingScope<'_> {
+    fn device(&self) -> &Device {
+        &self.encoder.device
+    }
+
+    fn command_buffer(&self) -> CommandBuffer {
+        self.encoder.command_buffer
+    }
+}
+
+impl Drop for RenderingScope<'_> {
+    fn drop(&mut self) {
+        if self.owns_rendering {
+            unsafe { self.encoder.device.cmd_end_rendering(self.encoder.command_buffer) };
+        }
+    }
+}
+
+mod sealed {
+    use ash::{vk::CommandBuffer, Device};
+
+    /// What [`super::BindCommands`] records with, out of reach outside this module so the
+    /// command buffer can't be recorded into behind the encoder's back.
+    pub trait Recorder {
+        fn device(&self) -> &Device;
+
+        fn command_buffer(&self) -> CommandBuffer;
+    }
+}
+
+/// State commands that are valid both inside and outside of rendering.
+pub trait BindCommands: sealed::Recorder {
+    fn bind_pipeline(&mut self, bind_point: PipelineBindPoint, pipeline: Pipeline) {
+        unsafe {
+            self.device()
+                .cmd_bind_pipeline(self.command_buffer(), bind_point, pipeline)
+        };
+    }
+
+    fn bind_descriptor_sets(
+        &mut self,
+        bind_point: PipelineBindPoint,
+        layout: PipelineLayout,
+        first_set: u32,
+        descriptor_sets: &[DescriptorSet],
+    ) {
+        unsafe {
+            self.device().cmd_bind_descriptor_sets(
+                self.command_buffer(),
+                bind_point,
+                layout,
+                first_set,
+                descriptor_sets,
+                &[],
+            )
+        };
+    }
+
+    fn push_constants(&mut self, layout: PipelineLayout, stages: ShaderStageFlags, offset: u32, data: &[u8]) {
+        unsafe {
+            self.device()
+                .cmd_push_constants(self.command_buffer(), layout, stages, offset, data)
+        };
+    }
+
+    fn set_viewport(&mut self, viewport: Viewport) {
+        unsafe { self.device().cmd_set_viewport(self.command_buffer(), 0, &[viewport]) };
+    }
+
+    fn set_scissor(&mut self, scissor: Rect2D) {
+        unsafe { self.device().cmd_set_scissor(self.command_buffer(), 0, &[scissor]) };
+    }
+}
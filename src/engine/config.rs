@@ -0,0 +1,287 @@
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use ash::vk::{DebugUtilsMessageSeverityFlagsEXT, DebugUtilsMessageTypeFlagsEXT, SampleCountFlags};
+
+use super::features::DeviceRequirements;
+
+/// Environment variable that overrides [`EngineConfig::gpu`], parsed with [`GpuSelector::from_str`].
+pub const GPU_ENV_VAR: &str = "METAPOD_GPU";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MsaaSamples {
+    X1,
+    X2,
+    X4,
+    X8,
+}
+
+impl MsaaSamples {
+    pub const ALL: [MsaaSamples; 4] = [
+        MsaaSamples::X1,
+        MsaaSamples::X2,
+        MsaaSamples::X4,
+        MsaaSamples::X8,
+    ];
+
+    pub fn sample_count_flags(self) -> SampleCountFlags {
+        match self {
+            MsaaSamples::X1 => SampleCountFlags::TYPE_1,
+            MsaaSamples::X2 => SampleCountFlags::TYPE_2,
+            MsaaSamples::X4 => SampleCountFlags::TYPE_4,
+            MsaaSamples::X8 => SampleCountFlags::TYPE_8,
+        }
+    }
+
+    pub fn is_multisampled(self) -> bool {
+        self != MsaaSamples::X1
+    }
+
+    /// Returns the highest sample count that is not above `self` and is part of `supported`.
+    /// Single sampling is always supported, so this never fails.
+    pub fn clamp_to(self, supported: SampleCountFlags) -> MsaaSamples {
+        Self::ALL
+            .into_iter()
+            .rev()
+            .filter(|samples| *samples as u32 <= self as u32)
+            .find(|samples| supported.contains(samples.sample_count_flags()))
+            .unwrap_or(MsaaSamples::X1)
+    }
+}
+
+/// What the scene is cleared to before anything is drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Background {
+    Solid([f32; 4]),
+    /// Cycles smoothly through colors, once every `period` frames. Tied to the frame
+    /// number rather than time so every frame's color is known in advance.
+    Animated { period: u32 },
+}
+
+impl Background {
+    pub fn color(self, frame_number: u64) -> [f32; 4] {
+        match self {
+            Background::Solid(color) => color,
+            Background::Animated { period } => {
+                let phase = (frame_number % period.max(1) as u64) as f32 / period.max(1) as f32;
+                let angle = phase * std::f32::consts::TAU;
+                // Kept dark so the scene stays readable on top of it.
+                let channel = |offset: f32| 0.25 + 0.25 * (angle + offset * std::f32::consts::TAU).sin();
+                [channel(0.0), channel(1.0 / 3.0), channel(2.0 / 3.0), 1.0]
+            }
+        }
+    }
+}
+
+/// Picks a specific GPU instead of the highest scoring one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GpuSelector {
+    /// Case-insensitive substring of the device name.
+    Name(String),
+    /// Position in the list returned by `vkEnumeratePhysicalDevices`.
+    Index(usize),
+    Id { vendor_id: u32, device_id: u32 },
+    Uuid([u8; 16]),
+}
+
+impl GpuSelector {
+    /// Reads the selector from [`GPU_ENV_VAR`], ignoring it if it is unset or empty.
+    pub fn from_env() -> Option<GpuSelector> {
+        std::env::var(GPU_ENV_VAR)
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .and_then(|value| value.parse().ok())
+    }
+}
+
+impl FromStr for GpuSelector {
+    type Err = std::convert::Infallible;
+
+    /// `#3` selects by index, `10de:2684` by vendor and device ID (hex), 32 hex digits with
+    /// optional dashes by UUID, anything else by name. Plain numbers are names, since they
+    /// are often part of one, e.g. `4090`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        if let Some(Ok(index)) = value.strip_prefix('#').map(str::parse) {
+            return Ok(GpuSelector::Index(index));
+        }
+        if let Some((vendor_id, device_id)) = value.split_once(':') {
+            let parse_hex = |id: &str| u32::from_str_radix(id.trim_start_matches("0x"), 16);
+            if let (Ok(vendor_id), Ok(device_id)) = (parse_hex(vendor_id), parse_hex(device_id)) {
+                return Ok(GpuSelector::Id { vendor_id, device_id });
+            }
+        }
+        let hex = value.replace('-', "");
+        if hex.len() == 32 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            let mut uuid = [0u8; 16];
+            for (index, byte) in uuid.iter_mut().enumerate() {
+                *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).unwrap();
+            }
+            return Ok(GpuSelector::Uuid(uuid));
+        }
+        Ok(GpuSelector::Name(value.to_owned()))
+    }
+}
+
+impl fmt::Display for GpuSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GpuSelector::Name(name) => write!(f, "name \"{name}\""),
+            GpuSelector::Index(index) => write!(f, "index #{index}"),
+            GpuSelector::Id { vendor_id, device_id } => write!(f, "id {vendor_id:04x}:{device_id:04x}"),
+            GpuSelector::Uuid(uuid) => {
+                write!(f, "uuid ")?;
+                uuid.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+            }
+        }
+    }
+}
+
+/// Validation layer and `VK_EXT_debug_utils` setup. Anything unavailable on the machine
+/// is skipped with a warning instead of failing instance creation.
+#[derive(Debug, Clone)]
+pub struct ValidationConfig {
+    /// Enables `VK_LAYER_KHRONOS_validation`.
+    pub enabled: bool,
+    /// Enables `VK_EXT_debug_utils` for the message callback, object names and labels.
+    pub debug_utils: bool,
+    pub severities: DebugUtilsMessageSeverityFlagsEXT,
+    pub message_types: DebugUtilsMessageTypeFlagsEXT,
+    /// The following need `VK_EXT_validation_features` and are fairly slow.
+    pub gpu_assisted: bool,
+    pub best_practices: bool,
+    pub synchronization: bool,
+    /// Message ID names (`VUID-...`) or numbers that are dropped without logging.
+    pub ignored_message_ids: Vec<String>,
+    /// Fails the next [`Engine::draw`](super::Engine::draw) or upload with
+    /// [`EngineError::Validation`](super::errors::engine_error::EngineError::Validation) after a
+    /// validation error, so automated runs fail loudly.
+    pub fail_on_error: bool,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: cfg!(debug_assertions),
+            debug_utils: cfg!(debug_assertions),
+            severities: DebugUtilsMessageSeverityFlagsEXT::WARNING
+                | DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            message_types: DebugUtilsMessageTypeFlagsEXT::GENERAL
+                | DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                | DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+            gpu_assisted: false,
+            best_practices: false,
+            synchronization: false,
+            ignored_message_ids: Vec::new(),
+            fail_on_error: false,
+        }
+    }
+}
+
+pub const MIN_RENDER_SCALE: f32 = 0.25;
+pub const MAX_RENDER_SCALE: f32 = 2.0;
+pub const MIN_FRAMES_IN_FLIGHT: usize = 1;
+pub const MAX_FRAMES_IN_FLIGHT: usize = 4;
+pub const MAX_RECORDING_THREADS: usize = 16;
+
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    pub msaa_samples: MsaaSamples,
+    /// Present with FIFO when enabled, otherwise MAILBOX or IMMEDIATE if available.
+    pub vsync: bool,
+    /// Size of the render targets relative to the swapchain, clamped to
+    /// [`MIN_RENDER_SCALE`]..=[`MAX_RENDER_SCALE`].
+    pub render_scale: f32,
+    /// Frames the CPU may record ahead of the GPU, clamped to
+    /// [`MIN_FRAMES_IN_FLIGHT`]..=[`MAX_FRAMES_IN_FLIGHT`]. More frames smooth out spikes
+    /// at the cost of latency. Independent of the swapchain image count.
+    pub frames_in_flight: usize,
+    /// Forces a specific GPU, [`GPU_ENV_VAR`] takes precedence when set.
+    pub gpu: Option<GpuSelector>,
+    /// Extra features and extensions for the application, merged with the engine's own.
+    pub device_requirements: DeviceRequirements,
+    /// Writes GPU breadcrumbs through `VK_NV_device_diagnostic_checkpoints` or
+    /// `VK_AMD_buffer_marker` when available, to locate the pass a device loss happened in.
+    pub device_diagnostics: bool,
+    /// Whether the application should [rebuild](crate::engine::Engine::rebuild) the engine
+    /// after a device loss instead of shutting down.
+    pub rebuild_on_device_lost: bool,
+    pub validation: ValidationConfig,
+    /// Measures the GPU time of every pass with timestamp queries, see
+    /// [`GpuProfiler`](crate::engine::profiler::GpuProfiler).
+    pub gpu_profiling: bool,
+    /// Directory the pipeline cache is kept in between runs, `None` to not persist it.
+    pub pipeline_cache_dir: Option<PathBuf>,
+    /// Tracks frames with a timeline semaphore instead of per frame fences when the
+    /// device supports `timelineSemaphore`.
+    pub timeline_semaphores: bool,
+    /// Threads recording the scene's queued jobs into secondary command buffers, clamped
+    /// to 1..=[`MAX_RECORDING_THREADS`].
+    pub recording_threads: usize,
+    pub background: Background,
+    /// Skips render objects outside of the camera's view frustum.
+    pub frustum_culling: bool,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            msaa_samples: MsaaSamples::X4,
+            vsync: true,
+            render_scale: 1.0,
+            frames_in_flight: 2,
+            gpu: None,
+            device_requirements: DeviceRequirements::default(),
+            device_diagnostics: cfg!(debug_assertions),
+            rebuild_on_device_lost: true,
+            validation: ValidationConfig::default(),
+            gpu_profiling: true,
+            pipeline_cache_dir: Some(PathBuf::from("pipeline-cache")),
+            timeline_semaphores: true,
+            recording_threads: std::thread::available_parallelism().map_or(1, |threads| threads.get().min(4)),
+            background: Background::Animated { period: 600 },
+            frustum_culling: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solid_background_ignores_the_frame() {
+        let color = [0.1, 0.2, 0.3, 1.0];
+        assert_eq!(Background::Solid(color).color(0), color);
+        assert_eq!(Background::Solid(color).color(12345), color);
+    }
+
+    #[test]
+    fn animated_background_repeats_every_period() {
+        let background = Background::Animated { period: 60 };
+        for frame_number in 0..60 {
+            assert_eq!(background.color(frame_number), background.color(frame_number + 60));
+        }
+        assert_ne!(background.color(0), background.color(30));
+    }
+
+    #[test]
+    fn animated_background_stays_dark_and_opaque() {
+        for period in [0, 1, 7, 600] {
+            for frame_number in 0..1200 {
+                let [r, g, b, a] = Background::Animated { period }.color(frame_number);
+                for channel in [r, g, b] {
+                    assert!((0.0..=0.5).contains(&channel), "{channel} out of range");
+                }
+                assert_eq!(a, 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn gpu_index_needs_a_hash() {
+        assert_eq!("#3".parse(), Ok(GpuSelector::Index(3)));
+        assert_eq!(" #0 ".parse(), Ok(GpuSelector::Index(0)));
+        assert_eq!("4090".parse(), Ok(GpuSelector::Name("4090".to_owned())));
+        assert_eq!("#gpu".parse(), Ok(GpuSelector::Name("#gpu".to_owned())));
+    }
+}
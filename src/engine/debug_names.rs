@@ -0,0 +1,93 @@
+use std::ffi::CString;
+
+use ash::{
+    vk::{DebugUtilsLabelEXT, DebugUtilsObjectNameInfoEXT, Handle, Queue},
+    Device, Instance,
+};
+use log::warn;
+
+use super::{buffers::AllocatedBuffer, command_encoder::CommandEncoder, images::AllocatedImage};
+
+/// Names objects and labels command buffers through `VK_EXT_debug_utils`, so captures
+/// and validation messages show what an object is. Does nothing without debug utils.
+pub struct DebugNames {
+    device: Option<ash::ext::debug_utils::Device>,
+}
+
+impl DebugNames {
+    pub fn new(instance: &Instance, device: &Device, enabled: bool) -> DebugNames {
+        DebugNames {
+            device: enabled.then(|| ash::ext::debug_utils::Device::new(instance, device)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.device.is_some()
+    }
+
+    /// Failing to name an object is logged but never an error.
+    pub fn name<H: Handle>(&self, object: H, name: &str) {
+        let (Some(device), Some(name)) = (&self.device, Self::c_string(name)) else {
+            return;
+        };
+        let name_info = DebugUtilsObjectNameInfoEXT::default()
+            .object_handle(object)
+            .object_name(&name);
+        if let Err(err) = unsafe { device.set_debug_utils_object_name(&name_info) } {
+            warn!("Failed to name {:?}: {err}", name);
+        }
+    }
+
+    /// Names the image, its view and its memory.
+    pub fn name_image(&self, image: &AllocatedImage, name: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        self.name(image.image, name);
+        self.name(image.image_view, &format!("{name} view"));
+        self.name(image.memory, &format!("{name} memory"));
+    }
+
+    /// Names the buffer and its memory.
+    pub fn name_buffer(&self, buffer: &AllocatedBuffer, name: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        self.name(buffer.buffer, name);
+        self.name(buffer.memory, &format!("{name} memory"));
+    }
+
+    /// Opens a label scope, every [`Self::begin_label`] needs a matching [`Self::end_label`]
+    /// in the same command buffer.
+    pub fn begin_label(&self, encoder: &mut CommandEncoder, name: &str, color: [f32; 4]) {
+        let (Some(device), Some(name)) = (&self.device, Self::c_string(name)) else {
+            return;
+        };
+        let label = DebugUtilsLabelEXT::default().label_name(&name).color(color);
+        encoder.begin_debug_label(device, &label);
+    }
+
+    pub fn end_label(&self, encoder: &mut CommandEncoder) {
+        if let Some(device) = &self.device {
+            encoder.end_debug_label(device);
+        }
+    }
+
+    pub fn begin_queue_label(&self, queue: Queue, name: &str, color: [f32; 4]) {
+        let (Some(device), Some(name)) = (&self.device, Self::c_string(name)) else {
+            return;
+        };
+        let label = DebugUtilsLabelEXT::default().label_name(&name).color(color);
+        unsafe { device.queue_begin_debug_utils_label(queue, &label) };
+    }
+
+    pub fn end_queue_label(&self, queue: Queue) {
+        if let Some(device) = &self.device {
+            unsafe { device.queue_end_debug_utils_label(queue) };
+        }
+    }
+
+    fn c_string(name: &str) -> Option<CString> {
+        CString::new(name).ok()
+    }
+}
@@ -1,63 +1,67 @@
 use std::ffi::c_void;
 
-use ash::{vk::{DebugUtilsMessageSeverityFlagsEXT, DebugUtilsMessageTypeFlagsEXT, DebugUtilsMessengerCallbackDataEXT, DebugUtilsMessengerCreateInfoEXT, DebugUtilsMessengerEXT}, Entry, Instance};
-use log::{error, info, warn};
-
-pub fn setup_debugger(entry: &Entry, instance: &Instance) -> (ash::ext::debug_utils::Instance, DebugUtilsMessengerEXT) {
-    let debug_create_info = DebugUtilsMessengerCreateInfoEXT::default()
-        .message_severity(
-           DebugUtilsMessageSeverityFlagsEXT::VERBOSE |
-           DebugUtilsMessageSeverityFlagsEXT::WARNING |
-           DebugUtilsMessageSeverityFlagsEXT::INFO |
-           DebugUtilsMessageSeverityFlagsEXT:: ERROR
-        ).message_type(
-        DebugUtilsMessageTypeFlagsEXT::GENERAL |
-        DebugUtilsMessageTypeFlagsEXT::VALIDATION |
-        DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
-        )
-        .pfn_user_callback(Some(debug_callback));
-    
-    let debug_instance = ash::ext::debug_utils::Instance::new(entry, instance);
-    let debug_messenger = unsafe { debug_instance.create_debug_utils_messenger(&debug_create_info, None).unwrap() };
-    (debug_instance, debug_messenger)
+use ash::{vk::{self, DebugUtilsMessageSeverityFlagsEXT, DebugUtilsMessageTypeFlagsEXT, DebugUtilsMessengerCallbackDataEXT, DebugUtilsMessengerCreateInfoEXT, DebugUtilsMessengerEXT}, Entry, Instance};
+
+use super::{
+    config::ValidationConfig,
+    errors::engine_error::{EngineError, VkResultExt},
+    validation::{ValidationCounts, ValidationMessage, ValidationSink},
+};
+
+/// The debug utils messenger, only created when the instance has `VK_EXT_debug_utils`.
+pub struct Debugger {
+    pub instance: ash::ext::debug_utils::Instance,
+    messenger: DebugUtilsMessengerEXT,
+    /// Boxed so the address handed to the messenger as user data stays put.
+    sink: Box<ValidationSink>,
 }
 
-pub unsafe extern "system" fn debug_callback(
-        message_severity: DebugUtilsMessageSeverityFlagsEXT,
-        message_type: DebugUtilsMessageTypeFlagsEXT,
-        callback_data: *const DebugUtilsMessengerCallbackDataEXT<'_>,
-        user_data: *mut c_void,
-    ) -> u32 {
-        unsafe {
-            let p_callback_data = *callback_data;
-            let message_id_name = p_callback_data
-                .message_id_name_as_c_str()
-                .unwrap()
-                .to_string_lossy();
-            let message_id_number = p_callback_data.message_id_number;
-            let message = p_callback_data
-                .message_as_c_str()
-                .unwrap()
-                .to_string_lossy();
-
-            match message_severity {
-                DebugUtilsMessageSeverityFlagsEXT::WARNING => {
-                    warn!(
-                        "{message_type:?} [{message_id_name} ({message_id_number})] : {message}\n"
-                    );
-                }
-                DebugUtilsMessageSeverityFlagsEXT::ERROR => {
-                    error!(
-                        "{message_type:?} [{message_id_name} ({message_id_number})] : {message}\n"
-                    )
-                }
-                _ => {
-                    info!(
-                        "{message_type:?} [{message_id_name} ({message_id_number})] : {message}\n"
-                    );
-                } 
-            }
-        }
-        0
+impl Debugger {
+    pub fn new(entry: &Entry, instance: &Instance, validation: &ValidationConfig) -> Result<Debugger, EngineError> {
+        let debug_instance = ash::ext::debug_utils::Instance::new(entry, instance);
+        let sink = Box::new(ValidationSink::new(validation));
+        let create_info = messenger_create_info(validation).user_data(&*sink as *const ValidationSink as *mut c_void);
+        let messenger = unsafe { debug_instance.create_debug_utils_messenger(&create_info, None) }
+            .vk_context("vkCreateDebugUtilsMessengerEXT")?;
+        Ok(Debugger {
+            instance: debug_instance,
+            messenger,
+            sink,
+        })
+    }
+
+    pub fn sink(&self) -> &ValidationSink {
+        &self.sink
+    }
+
+    pub fn validation_counts(&self) -> ValidationCounts {
+        self.sink.counts()
     }
 
+    pub fn destroy(&self) {
+        unsafe { self.instance.destroy_debug_utils_messenger(self.messenger, None) };
+    }
+}
+
+/// Shared by the messenger and the instance creation chain.
+pub fn messenger_create_info(validation: &ValidationConfig) -> DebugUtilsMessengerCreateInfoEXT<'static> {
+    DebugUtilsMessengerCreateInfoEXT::default()
+        .message_severity(validation.severities)
+        .message_type(validation.message_types)
+        .pfn_user_callback(Some(debug_callback))
+}
+
+pub unsafe extern "system" fn debug_callback(
+    message_severity: DebugUtilsMessageSeverityFlagsEXT,
+    message_type: DebugUtilsMessageTypeFlagsEXT,
+    callback_data: *const DebugUtilsMessengerCallbackDataEXT<'_>,
+    user_data: *mut c_void,
+) -> u32 {
+    let message = unsafe { ValidationMessage::from_callback_data(message_severity, message_type, &*callback_data) };
+    // Messages during vkCreateInstance and vkDestroyInstance come without a sink.
+    match unsafe { (user_data as *const ValidationSink).as_ref() } {
+        Some(sink) => sink.handle(message),
+        None => message.log(),
+    }
+    vk::FALSE
+}
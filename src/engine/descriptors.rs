@@ -0,0 +1,107 @@
+use anyhow::Error;
+use ash::{
+    vk::{
+        Buffer, DescriptorBufferInfo, DescriptorPool, DescriptorPoolCreateFlags,
+        DescriptorPoolCreateInfo, DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo,
+        DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo,
+        DescriptorType, DeviceSize, ShaderStageFlags, WriteDescriptorSet,
+    },
+    Device,
+};
+
+#[derive(Default)]
+pub struct DescriptorLayoutBuilder<'a> {
+    bindings: Vec<DescriptorSetLayoutBinding<'a>>,
+}
+
+impl<'a> DescriptorLayoutBuilder<'a> {
+    pub fn add_binding(
+        mut self,
+        binding: u32,
+        descriptor_type: DescriptorType,
+        stages: ShaderStageFlags,
+    ) -> Self {
+        self.bindings.push(
+            DescriptorSetLayoutBinding::default()
+                .binding(binding)
+                .descriptor_type(descriptor_type)
+                .descriptor_count(1)
+                .stage_flags(stages),
+        );
+        self
+    }
+
+    pub fn build(self, device: &Device) -> Result<DescriptorSetLayout, Error> {
+        let create_info = DescriptorSetLayoutCreateInfo::default().bindings(&self.bindings);
+        Ok(unsafe { device.create_descriptor_set_layout(&create_info, None)? })
+    }
+}
+
+/// A single descriptor pool sized from per-type ratios of `max_sets`.
+pub struct DescriptorAllocator {
+    pub pool: DescriptorPool,
+}
+
+impl DescriptorAllocator {
+    pub fn new(
+        device: &Device,
+        max_sets: u32,
+        pool_ratios: &[(DescriptorType, f32)],
+    ) -> Result<DescriptorAllocator, Error> {
+        let pool_sizes = pool_ratios
+            .iter()
+            .map(|(descriptor_type, ratio)| {
+                DescriptorPoolSize::default()
+                    .ty(*descriptor_type)
+                    .descriptor_count(((max_sets as f32 * ratio) as u32).max(1))
+            })
+            .collect::<Vec<_>>();
+        let create_info = DescriptorPoolCreateInfo::default()
+            .flags(DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET)
+            .max_sets(max_sets)
+            .pool_sizes(&pool_sizes);
+        let pool = unsafe { device.create_descriptor_pool(&create_info, None)? };
+        Ok(DescriptorAllocator { pool })
+    }
+
+    pub fn allocate(
+        &self,
+        device: &Device,
+        layout: DescriptorSetLayout,
+    ) -> Result<DescriptorSet, Error> {
+        let layouts = [layout];
+        let allocate_info = DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.pool)
+            .set_layouts(&layouts);
+        Ok(unsafe { device.allocate_descriptor_sets(&allocate_info)?[0] })
+    }
+
+    pub fn free(&self, device: &Device, set: DescriptorSet) -> Result<(), Error> {
+        unsafe { device.free_descriptor_sets(self.pool, &[set])? };
+        Ok(())
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        unsafe { device.destroy_descriptor_pool(self.pool, None) };
+    }
+}
+
+pub fn write_buffer_descriptor(
+    device: &Device,
+    set: DescriptorSet,
+    binding: u32,
+    descriptor_type: DescriptorType,
+    buffer: Buffer,
+    range: DeviceSize,
+) {
+    let buffer_infos = [DescriptorBufferInfo::default()
+        .buffer(buffer)
+        .offset(0)
+        .range(range)];
+    let write = WriteDescriptorSet::default()
+        .dst_set(set)
+        .dst_binding(binding)
+        .descriptor_type(descriptor_type)
+        .buffer_info(&buffer_infos);
+    unsafe { device.update_descriptor_sets(&[write], &[]) };
+}
@@ -3,35 +3,54 @@ use ash::{
 };
 use ash::vk::{
         DeviceCreateFlags, DeviceCreateInfo, DeviceQueueCreateFlags, DeviceQueueCreateInfo,
-        PhysicalDevice, SurfaceKHR, KHR_SWAPCHAIN_NAME,
+        PhysicalDevice,
     };
+use super::features::{EnabledFeatures, FeatureStructure};
 use super::queues::QueueIndices;
 
 use super::errors::device_error::DeviceError;
 
 
+/// Creates the logical device with exactly the features and extensions in `enabled`,
+/// chaining only the feature structures that have something enabled.
 pub fn create_device(
     instance: &Instance,
     physical_device: PhysicalDevice,
     queue_indices: QueueIndices,
+    enabled: &EnabledFeatures,
 ) -> Result<Device, DeviceError> {
-    let features = unsafe { instance.get_physical_device_features(physical_device) };
-    let device_extensions = vec![KHR_SWAPCHAIN_NAME.as_ptr()];
-    let device_queue_create_info = &[DeviceQueueCreateInfo::default()
-        .queue_family_index(queue_indices.graphics_queue_index.unwrap())
-        .queue_priorities(&[1.0])
-        .flags(DeviceQueueCreateFlags::default())]  ;
+    let device_extensions = enabled
+        .extensions
+        .iter()
+        .map(|extension| extension.as_ptr())
+        .collect::<Vec<_>>();
+    let device_queue_create_info = queue_indices
+        .unique_families()
+        .into_iter()
+        .map(|family_index| {
+            DeviceQueueCreateInfo::default()
+                .queue_family_index(family_index)
+                .queue_priorities(&[1.0])
+                .flags(DeviceQueueCreateFlags::default())
+        })
+        .collect::<Vec<_>>();
+    let mut features = enabled.feature_chain();
 
-    let create_info = DeviceCreateInfo::default()
-        .queue_create_infos(device_queue_create_info)
-        .enabled_features(&features)
+    let mut create_info = DeviceCreateInfo::default()
+        .queue_create_infos(&device_queue_create_info)
+        .enabled_features(&features.core)
         .enabled_extension_names(&device_extensions)
         .flags(DeviceCreateFlags::empty());
+    if enabled.uses_structure(FeatureStructure::Vulkan11) {
+        create_info = create_info.push_next(&mut features.vulkan_11);
+    }
+    if enabled.uses_structure(FeatureStructure::Vulkan12) {
+        create_info = create_info.push_next(&mut features.vulkan_12);
+    }
+    if enabled.uses_structure(FeatureStructure::Vulkan13) {
+        create_info = create_info.push_next(&mut features.vulkan_13);
+    }
 
-    let device = unsafe {
-        instance
-            .create_device(physical_device, &create_info, None)
-            .unwrap()
-    };
-    Ok(device)
+    unsafe { instance.create_device(physical_device, &create_info, None) }
+        .map_err(DeviceError::CreationFailed)
 }
@@ -0,0 +1,223 @@
+use std::{ffi::c_void, fmt, mem::size_of};
+
+use anyhow::Error;
+use ash::{
+    vk::{
+        BufferUsageFlags, CheckpointDataNV, DeviceSize, MemoryPropertyFlags,
+        PhysicalDeviceMemoryProperties, PipelineStageFlags, Queue,
+    },
+    Device, Instance,
+};
+
+use super::{
+    buffers::{create_buffer, AllocatedBuffer},
+    command_encoder::CommandEncoder,
+    debug_names::DebugNames,
+    features::{DeviceRequirements, EnabledFeatures},
+};
+
+/// Where the GPU got to before the device was lost, see [`DeviceDiagnostics::report`].
+#[derive(Debug, Clone, Default)]
+pub struct DeviceLostReport {
+    pub frame_number: u64,
+    /// Passes recorded for every frame slot that may still have been executing.
+    pub submitted_passes: Vec<(usize, Vec<&'static str>)>,
+    /// Checkpoints the graphics queue reached, from `VK_NV_device_diagnostic_checkpoints`.
+    pub checkpoints: Vec<String>,
+    /// Last pass started and finished per frame slot, from `VK_AMD_buffer_marker`.
+    pub markers: Vec<String>,
+}
+
+impl fmt::Display for DeviceLostReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Device lost during frame {}", self.frame_number)?;
+        for (frame_index, passes) in self.submitted_passes.iter() {
+            writeln!(f, "  frame slot {frame_index} submitted: {}", passes.join(" -> "))?;
+        }
+        for checkpoint in self.checkpoints.iter() {
+            writeln!(f, "  checkpoint: {checkpoint}")?;
+        }
+        for marker in self.markers.iter() {
+            writeln!(f, "  marker: {marker}")?;
+        }
+        if self.checkpoints.is_empty() && self.markers.is_empty() {
+            writeln!(f, "  no GPU breadcrumbs, neither checkpoints nor buffer markers are available")?;
+        }
+        Ok(())
+    }
+}
+
+enum Breadcrumbs {
+    None,
+    NvCheckpoints(ash::nv::device_diagnostic_checkpoints::Device),
+    /// Two markers per frame slot: the last pass started and the last pass finished.
+    AmdBufferMarker {
+        device: ash::amd::buffer_marker::Device,
+        buffer: AllocatedBuffer,
+    },
+}
+
+/// Keeps track of the passes recorded each frame and, where the driver supports it,
+/// writes GPU side breadcrumbs so a device loss can be traced to a pass.
+pub struct DeviceDiagnostics {
+    breadcrumbs: Breadcrumbs,
+    passes: Vec<Vec<&'static str>>,
+}
+
+impl DeviceDiagnostics {
+    /// Both extensions are only requested, the CPU side pass list works without them.
+    pub fn device_requirements() -> DeviceRequirements {
+        DeviceRequirements::default()
+            .optional_extension(ash::nv::device_diagnostic_checkpoints::NAME)
+            .optional_extension(ash::amd::buffer_marker::NAME)
+    }
+
+    pub fn new(
+        instance: &Instance,
+        device: &Device,
+        memory_properties: &PhysicalDeviceMemoryProperties,
+        enabled_features: &EnabledFeatures,
+        frames_in_flight: usize,
+    ) -> Result<DeviceDiagnostics, Error> {
+        let breadcrumbs = if enabled_features.has_extension(ash::nv::device_diagnostic_checkpoints::NAME) {
+            Breadcrumbs::NvCheckpoints(ash::nv::device_diagnostic_checkpoints::Device::new(instance, device))
+        } else if enabled_features.has_extension(ash::amd::buffer_marker::NAME) {
+            let buffer = create_buffer(
+                device,
+                memory_properties,
+                (frames_in_flight * 2 * size_of::<u32>()) as DeviceSize,
+                BufferUsageFlags::TRANSFER_DST,
+                MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+            )?;
+            buffer.write(&vec![0u32; frames_in_flight * 2])?;
+            Breadcrumbs::AmdBufferMarker {
+                device: ash::amd::buffer_marker::Device::new(instance, device),
+                buffer,
+            }
+        } else {
+            Breadcrumbs::None
+        };
+        Ok(DeviceDiagnostics {
+            breadcrumbs,
+            passes: vec![Vec::new(); frames_in_flight],
+        })
+    }
+
+    /// Forgets the passes of the frame previously recorded in this slot.
+    pub fn begin_frame(&mut self, frame_index: usize) {
+        self.passes[frame_index].clear();
+    }
+
+    pub fn begin_pass(&mut self, encoder: &mut CommandEncoder, frame_index: usize, name: &'static str) {
+        self.passes[frame_index].push(name);
+        let marker = self.marker(frame_index);
+        match &self.breadcrumbs {
+            Breadcrumbs::None => {}
+            Breadcrumbs::NvCheckpoints(checkpoints) => {
+                encoder.set_checkpoint(checkpoints, marker as usize as *const c_void)
+            }
+            Breadcrumbs::AmdBufferMarker { device: markers, buffer } => encoder.write_buffer_marker(
+                markers,
+                PipelineStageFlags::TOP_OF_PIPE,
+                buffer.buffer,
+                Self::marker_offset(frame_index, 0),
+                marker,
+            ),
+        }
+    }
+
+    pub fn end_pass(&mut self, encoder: &mut CommandEncoder, frame_index: usize) {
+        let marker = self.marker(frame_index);
+        if let Breadcrumbs::AmdBufferMarker { device: markers, buffer } = &self.breadcrumbs {
+            encoder.write_buffer_marker(
+                markers,
+                PipelineStageFlags::BOTTOM_OF_PIPE,
+                buffer.buffer,
+                Self::marker_offset(frame_index, 1),
+                marker,
+            );
+        }
+    }
+
+    /// Collects everything known about the GPU's progress. Only meaningful after a device loss.
+    pub fn report(&self, queue: Queue, frame_number: u64) -> DeviceLostReport {
+        let mut report = DeviceLostReport {
+            frame_number,
+            submitted_passes: self
+                .passes
+                .iter()
+                .cloned()
+                .enumerate()
+                .filter(|(_, passes)| !passes.is_empty())
+                .collect(),
+            ..Default::default()
+        };
+        match &self.breadcrumbs {
+            Breadcrumbs::None => {}
+            Breadcrumbs::NvCheckpoints(checkpoints) => {
+                let mut data = unsafe {
+                    vec![CheckpointDataNV::default(); checkpoints.get_queue_checkpoint_data_len(queue)]
+                };
+                unsafe { checkpoints.get_queue_checkpoint_data(queue, &mut data) };
+                report.checkpoints = data
+                    .iter()
+                    .map(|checkpoint| {
+                        let marker = checkpoint.p_checkpoint_marker as usize as u32;
+                        format!("{} reached {:?}", self.describe_marker(marker), checkpoint.stage)
+                    })
+                    .collect();
+            }
+            Breadcrumbs::AmdBufferMarker { buffer, .. } => {
+                let markers = buffer
+                    .read_at::<u32>(0, self.passes.len() * 2)
+                    .unwrap_or_default();
+                report.markers = markers
+                    .chunks_exact(2)
+                    .enumerate()
+                    .map(|(frame_index, markers)| {
+                        format!(
+                            "frame slot {frame_index} started {}, finished {}",
+                            self.describe_marker(markers[0]),
+                            self.describe_marker(markers[1])
+                        )
+                    })
+                    .collect();
+            }
+        }
+        report
+    }
+
+    pub fn name_objects(&self, names: &DebugNames) {
+        if let Breadcrumbs::AmdBufferMarker { buffer, .. } = &self.breadcrumbs {
+            names.name_buffer(buffer, "breadcrumb marker buffer");
+        }
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        if let Breadcrumbs::AmdBufferMarker { buffer, .. } = &self.breadcrumbs {
+            buffer.destroy(device);
+        }
+    }
+
+    /// Frame slot in the upper and 1-based pass index in the lower 16 bits, so 0 means nothing.
+    fn marker(&self, frame_index: usize) -> u32 {
+        ((frame_index as u32) << 16) | self.passes[frame_index].len() as u32
+    }
+
+    fn describe_marker(&self, marker: u32) -> String {
+        let frame_index = (marker >> 16) as usize;
+        let pass_index = (marker & 0xffff) as usize;
+        match self
+            .passes
+            .get(frame_index)
+            .and_then(|passes| passes.get(pass_index.wrapping_sub(1)))
+        {
+            Some(name) => format!("'{name}' (frame slot {frame_index})"),
+            None => "nothing".to_owned(),
+        }
+    }
+
+    fn marker_offset(frame_index: usize, slot: usize) -> DeviceSize {
+        ((frame_index * 2 + slot) * size_of::<u32>()) as DeviceSize
+    }
+}
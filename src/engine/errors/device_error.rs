@@ -1,7 +1,35 @@
 use thiserror::Error;
 
+/// Why a physical device was not picked.
+#[derive(Debug, Clone)]
+pub struct DeviceRejection {
+    pub index: usize,
+    pub name: String,
+    pub reasons: Vec<String>,
+}
+
 #[derive(Error, Debug)]
 pub enum DeviceError {
-    #[error("There is no suitable PhysicalDevice on this device")]
-    NoPhysicalDeviceFound
+    #[error("There is no suitable PhysicalDevice on this device{}", format_rejections(.rejections))]
+    NoPhysicalDeviceFound { rejections: Vec<DeviceRejection> },
+
+    #[error("Failed to enumerate the physical devices: {0}")]
+    EnumerationFailed(ash::vk::Result),
+
+    #[error("Failed to create the logical device: {0}")]
+    CreationFailed(ash::vk::Result),
+}
+
+fn format_rejections(rejections: &[DeviceRejection]) -> String {
+    rejections
+        .iter()
+        .map(|rejection| {
+            format!(
+                "\n  [{}] {}: {}",
+                rejection.index,
+                rejection.name,
+                rejection.reasons.join(", ")
+            )
+        })
+        .collect()
 }
@@ -0,0 +1,142 @@
+use ash::vk;
+use thiserror::Error;
+use winit::raw_window_handle::HandleError;
+
+use crate::engine::{
+    diagnostics::DeviceLostReport,
+    materials::MaterialId,
+    mesh::MeshId,
+    queues::QueueFamilyIndicesError,
+    swapchain::{SwapchainCreationError, SwapchainSupportError},
+    validation::ValidationMessage,
+};
+
+use super::{device_error::DeviceError, instance_errors::InstanceCreationError};
+
+#[derive(Error, Debug)]
+pub enum EngineError {
+    #[error("{call} failed{}: {result}", .object.as_ref().map(|object| format!(" for {object}")).unwrap_or_default())]
+    Vulkan {
+        call: &'static str,
+        object: Option<String>,
+        result: vk::Result,
+    },
+
+    #[error("{report}")]
+    DeviceLost { report: Box<DeviceLostReport> },
+
+    #[error(transparent)]
+    Instance(#[from] InstanceCreationError),
+
+    #[error(transparent)]
+    Device(#[from] DeviceError),
+
+    #[error(transparent)]
+    SwapchainSupport(#[from] SwapchainSupportError),
+
+    #[error(transparent)]
+    SwapchainCreation(#[from] SwapchainCreationError),
+
+    #[error(transparent)]
+    QueueFamily(#[from] QueueFamilyIndicesError),
+
+    #[error("Validation error with fail_on_error set: [{}] {}", .0.id_name, .0.message)]
+    Validation(Box<ValidationMessage>),
+
+    #[error("Mesh {0:?} doesn't exist")]
+    UnknownMesh(MeshId),
+
+    #[error("Material {0:?} doesn't exist")]
+    UnknownMaterial(MaterialId),
+
+    #[error("The window handle is unavailable: {0}")]
+    WindowHandle(#[from] HandleError),
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// How the caller should react to an [`EngineError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The swapchain no longer matches the surface and has to be recreated.
+    OutOfDate,
+    /// A wait ran out of time, the frame can be skipped and retried.
+    Timeout,
+    /// The GPU crashed or was reset, every device object is gone.
+    DeviceLost,
+    Fatal,
+}
+
+impl EngineError {
+    /// The Vulkan result behind this error, also when it was wrapped by another error.
+    pub fn vk_result(&self) -> Option<vk::Result> {
+        match self {
+            EngineError::Vulkan { result, .. } => Some(*result),
+            EngineError::DeviceLost { .. } => Some(vk::Result::ERROR_DEVICE_LOST),
+            EngineError::SwapchainSupport(SwapchainSupportError::FailedToGetSupportDetails(result)) => Some(*result),
+            EngineError::SwapchainCreation(SwapchainCreationError::SwapchainCreationError { err_message }) => {
+                Some(*err_message)
+            }
+            EngineError::Device(DeviceError::CreationFailed(result) | DeviceError::EnumerationFailed(result)) => {
+                Some(*result)
+            }
+            EngineError::Other(err) => err
+                .downcast_ref::<vk::Result>()
+                .copied()
+                .or_else(|| err.downcast_ref::<EngineError>().and_then(EngineError::vk_result)),
+            _ => None,
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self.vk_result() {
+            Some(vk::Result::ERROR_OUT_OF_DATE_KHR | vk::Result::SUBOPTIMAL_KHR) => ErrorKind::OutOfDate,
+            Some(vk::Result::TIMEOUT | vk::Result::NOT_READY) => ErrorKind::Timeout,
+            Some(vk::Result::ERROR_DEVICE_LOST) => ErrorKind::DeviceLost,
+            _ => ErrorKind::Fatal,
+        }
+    }
+
+    pub fn is_device_lost(&self) -> bool {
+        self.kind() == ErrorKind::DeviceLost
+    }
+}
+
+/// Attaches the failing call, and optionally the object it was made for, to a `vk::Result`.
+pub trait VkResultExt<T> {
+    fn vk_context(self, call: &'static str) -> Result<T, EngineError>;
+
+    fn vk_object_context(self, call: &'static str, object: impl Into<String>) -> Result<T, EngineError>;
+}
+
+impl<T> VkResultExt<T> for Result<T, vk::Result> {
+    fn vk_context(self, call: &'static str) -> Result<T, EngineError> {
+        self.map_err(|result| EngineError::Vulkan {
+            call,
+            object: None,
+            result,
+        })
+    }
+
+    fn vk_object_context(self, call: &'static str, object: impl Into<String>) -> Result<T, EngineError> {
+        self.map_err(|result| EngineError::Vulkan {
+            call,
+            object: Some(object.into()),
+            result,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_swapchains_ask_for_a_new_one() {
+        for result in [vk::Result::ERROR_OUT_OF_DATE_KHR, vk::Result::SUBOPTIMAL_KHR] {
+            let err = Err::<(), _>(result).vk_context("vkQueuePresentKHR").unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::OutOfDate);
+        }
+    }
+}
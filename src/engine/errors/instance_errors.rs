@@ -7,7 +7,4 @@ pub enum InstanceCreationError {
         path: String,
         msg: String,
     },
-
-    #[error("Failed the validation check")]
-    ValidationCheck
 }
@@ -1,2 +1,3 @@
 pub mod instance_errors;
 pub mod device_error;
+pub mod engine_error;
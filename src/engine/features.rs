@@ -0,0 +1,295 @@
+use std::{collections::BTreeSet, ffi::CStr, ptr};
+
+use ash::{
+    vk::{
+        self, PhysicalDevice, PhysicalDeviceFeatures, PhysicalDeviceFeatures2,
+        PhysicalDeviceVulkan11Features, PhysicalDeviceVulkan12Features,
+        PhysicalDeviceVulkan13Features,
+    },
+    Instance,
+};
+
+use super::physical_devices::supports_extension;
+
+/// Which structure of the `vkGetPhysicalDeviceFeatures2` chain a [`Feature`] lives in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureStructure {
+    Core,
+    Vulkan11,
+    Vulkan12,
+    Vulkan13,
+}
+
+macro_rules! device_features {
+    ($($structure:ident => $field_structure:ident {
+        $($feature:ident => $field:ident: $name:literal,)*
+    })*) => {
+        /// Device features the engine knows how to request, see [`DeviceRequirements`].
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub enum Feature {
+            $($($feature,)*)*
+        }
+
+        impl Feature {
+            pub const ALL: &'static [Feature] = &[$($(Feature::$feature,)*)*];
+
+            /// Name as spelled in the Vulkan specification.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $($(Feature::$feature => $name,)*)*
+                }
+            }
+
+            pub fn structure(self) -> FeatureStructure {
+                match self {
+                    $($(Feature::$feature => FeatureStructure::$structure,)*)*
+                }
+            }
+
+            pub fn is_set(self, chain: &FeatureChain) -> bool {
+                match self {
+                    $($(Feature::$feature => chain.$field_structure.$field == vk::TRUE,)*)*
+                }
+            }
+
+            fn set(self, chain: &mut FeatureChain) {
+                match self {
+                    $($(Feature::$feature => chain.$field_structure.$field = vk::TRUE,)*)*
+                }
+            }
+        }
+    };
+}
+
+device_features! {
+    Core => core {
+        GeometryShader => geometry_shader: "geometryShader",
+        TessellationShader => tessellation_shader: "tessellationShader",
+        SampleRateShading => sample_rate_shading: "sampleRateShading",
+        IndependentBlend => independent_blend: "independentBlend",
+        MultiDrawIndirect => multi_draw_indirect: "multiDrawIndirect",
+        DrawIndirectFirstInstance => draw_indirect_first_instance: "drawIndirectFirstInstance",
+        DepthClamp => depth_clamp: "depthClamp",
+        OcclusionQueryPrecise => occlusion_query_precise: "occlusionQueryPrecise",
+        FillModeNonSolid => fill_mode_non_solid: "fillModeNonSolid",
+        WideLines => wide_lines: "wideLines",
+        SamplerAnisotropy => sampler_anisotropy: "samplerAnisotropy",
+        TextureCompressionBc => texture_compression_bc: "textureCompressionBC",
+        PipelineStatisticsQuery => pipeline_statistics_query: "pipelineStatisticsQuery",
+        InheritedQueries => inherited_queries: "inheritedQueries",
+        ShaderInt64 => shader_int64: "shaderInt64",
+        ShaderFloat64 => shader_float64: "shaderFloat64",
+    }
+    Vulkan11 => vulkan_11 {
+        StorageBuffer16BitAccess => storage_buffer16_bit_access: "storageBuffer16BitAccess",
+        Multiview => multiview: "multiview",
+        ShaderDrawParameters => shader_draw_parameters: "shaderDrawParameters",
+    }
+    Vulkan12 => vulkan_12 {
+        DrawIndirectCount => draw_indirect_count: "drawIndirectCount",
+        ShaderFloat16 => shader_float16: "shaderFloat16",
+        DescriptorIndexing => descriptor_indexing: "descriptorIndexing",
+        DescriptorBindingPartiallyBound => descriptor_binding_partially_bound: "descriptorBindingPartiallyBound",
+        RuntimeDescriptorArray => runtime_descriptor_array: "runtimeDescriptorArray",
+        ScalarBlockLayout => scalar_block_layout: "scalarBlockLayout",
+        HostQueryReset => host_query_reset: "hostQueryReset",
+        TimelineSemaphore => timeline_semaphore: "timelineSemaphore",
+        BufferDeviceAddress => buffer_device_address: "bufferDeviceAddress",
+    }
+    Vulkan13 => vulkan_13 {
+        Synchronization2 => synchronization2: "synchronization2",
+        DynamicRendering => dynamic_rendering: "dynamicRendering",
+        Maintenance4 => maintenance4: "maintenance4",
+    }
+}
+
+/// The core and Vulkan 1.1 to 1.3 feature structures, unlinked so they can be stored.
+#[derive(Default, Clone, Copy)]
+pub struct FeatureChain {
+    pub core: PhysicalDeviceFeatures,
+    pub vulkan_11: PhysicalDeviceVulkan11Features<'static>,
+    pub vulkan_12: PhysicalDeviceVulkan12Features<'static>,
+    pub vulkan_13: PhysicalDeviceVulkan13Features<'static>,
+}
+
+impl FeatureChain {
+    /// Everything `physical_device` supports. Requires a Vulkan 1.3 device.
+    pub fn query(instance: &Instance, physical_device: PhysicalDevice) -> FeatureChain {
+        let mut chain = FeatureChain::default();
+        let mut features = PhysicalDeviceFeatures2::default()
+            .push_next(&mut chain.vulkan_11)
+            .push_next(&mut chain.vulkan_12)
+            .push_next(&mut chain.vulkan_13);
+        unsafe { instance.get_physical_device_features2(physical_device, &mut features) };
+        chain.core = features.features;
+        chain.unlink();
+        chain
+    }
+
+    /// A chain with exactly `features` set.
+    pub fn from_features<'a>(features: impl IntoIterator<Item = &'a Feature>) -> FeatureChain {
+        let mut chain = FeatureChain::default();
+        for feature in features {
+            feature.set(&mut chain);
+        }
+        chain
+    }
+
+    fn unlink(&mut self) {
+        self.vulkan_11.p_next = ptr::null_mut();
+        self.vulkan_12.p_next = ptr::null_mut();
+        self.vulkan_13.p_next = ptr::null_mut();
+    }
+}
+
+/// Features and extensions a device needs (or would like) to run the engine. Subsystems
+/// add theirs and the results are [merged](DeviceRequirements::merge) before device
+/// selection.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceRequirements {
+    required_features: BTreeSet<Feature>,
+    optional_features: BTreeSet<Feature>,
+    required_extensions: Vec<&'static CStr>,
+    optional_extensions: Vec<&'static CStr>,
+}
+
+impl DeviceRequirements {
+    pub fn require_feature(mut self, feature: Feature) -> Self {
+        self.required_features.insert(feature);
+        self
+    }
+
+    /// Enabled when available, a device without it is still suitable.
+    pub fn optional_feature(mut self, feature: Feature) -> Self {
+        self.optional_features.insert(feature);
+        self
+    }
+
+    pub fn require_extension(mut self, extension: &'static CStr) -> Self {
+        if !self.required_extensions.contains(&extension) {
+            self.required_extensions.push(extension);
+        }
+        self
+    }
+
+    /// Enabled when available, a device without it is still suitable.
+    pub fn optional_extension(mut self, extension: &'static CStr) -> Self {
+        if !self.optional_extensions.contains(&extension) {
+            self.optional_extensions.push(extension);
+        }
+        self
+    }
+
+    pub fn merge(mut self, other: DeviceRequirements) -> Self {
+        self.required_features.extend(other.required_features);
+        self.optional_features.extend(other.optional_features);
+        for extension in other.required_extensions {
+            self = self.require_extension(extension);
+        }
+        for extension in other.optional_extensions {
+            self = self.optional_extension(extension);
+        }
+        self
+    }
+
+    /// Describes every required feature or extension `physical_device` lacks.
+    pub fn missing(&self, instance: &Instance, physical_device: PhysicalDevice) -> Vec<String> {
+        let supported = FeatureChain::query(instance, physical_device);
+        let missing_features = self
+            .required_features
+            .iter()
+            .filter(|feature| !feature.is_set(&supported))
+            .map(|feature| format!("missing {}", feature.name()));
+        let missing_extensions = self
+            .required_extensions
+            .iter()
+            .filter(|extension| !supports_extension(instance, physical_device, extension))
+            .map(|extension| format!("missing {}", extension.to_string_lossy()));
+        missing_features.chain(missing_extensions).collect()
+    }
+
+    /// How many optional features and extensions `physical_device` supports.
+    pub fn optional_supported_count(
+        &self,
+        instance: &Instance,
+        physical_device: PhysicalDevice,
+    ) -> usize {
+        let supported = FeatureChain::query(instance, physical_device);
+        self.optional_features
+            .iter()
+            .filter(|feature| feature.is_set(&supported))
+            .count()
+            + self
+                .optional_extensions
+                .iter()
+                .filter(|extension| supports_extension(instance, physical_device, extension))
+                .count()
+    }
+
+    /// Resolves what will be enabled on a device that passed [`Self::missing`]: all
+    /// required and the supported optional entries.
+    pub fn negotiate(
+        &self,
+        instance: &Instance,
+        physical_device: PhysicalDevice,
+    ) -> EnabledFeatures {
+        let supported = FeatureChain::query(instance, physical_device);
+        let features = self
+            .required_features
+            .iter()
+            .chain(
+                self.optional_features
+                    .iter()
+                    .filter(|feature| feature.is_set(&supported)),
+            )
+            .copied()
+            .collect();
+        let mut extensions = self.required_extensions.clone();
+        extensions.extend(
+            self.optional_extensions
+                .iter()
+                .filter(|extension| !extensions.contains(extension))
+                .filter(|extension| supports_extension(instance, physical_device, extension))
+                .collect::<Vec<_>>(),
+        );
+        // Implementations layered on top of another API must have this enabled if they expose it.
+        let portability_subset = ash::khr::portability_subset::NAME;
+        if !extensions.contains(&portability_subset)
+            && supports_extension(instance, physical_device, portability_subset)
+        {
+            extensions.push(portability_subset);
+        }
+        EnabledFeatures {
+            features,
+            extensions,
+        }
+    }
+}
+
+/// What the logical device was actually created with.
+#[derive(Debug, Clone, Default)]
+pub struct EnabledFeatures {
+    pub features: BTreeSet<Feature>,
+    pub extensions: Vec<&'static CStr>,
+}
+
+impl EnabledFeatures {
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        self.features.contains(&feature)
+    }
+
+    pub fn has_extension(&self, extension: &CStr) -> bool {
+        self.extensions.contains(&extension)
+    }
+
+    pub fn feature_chain(&self) -> FeatureChain {
+        FeatureChain::from_features(&self.features)
+    }
+
+    /// Whether any feature of `structure` is enabled, so it has to be chained at device creation.
+    pub fn uses_structure(&self, structure: FeatureStructure) -> bool {
+        self.features
+            .iter()
+            .any(|feature| feature.structure() == structure)
+    }
+}
@@ -1,38 +1,98 @@
+use std::mem::size_of;
+
 use anyhow::Error;
 use ash::{
     vk::{
-        CommandBuffer, CommandBufferAllocateInfo, CommandBufferLevel, CommandPool, CommandPoolCreateFlags, CommandPoolCreateInfo, Fence, Instance, Semaphore
+        BufferUsageFlags, CommandBuffer, CommandPool, DescriptorSet, DescriptorType, DeviceSize, Fence, MemoryPropertyFlags, PhysicalDeviceMemoryProperties, Semaphore
     },
     Device,
 };
 
-use super::command_buffers::{create_command_buffer, create_command_pool};
+use super::{
+    buffers::{create_buffer, AllocatedBuffer},
+    command_buffers::{create_command_buffer, create_command_pool},
+    debug_names::DebugNames,
+    parallel_recording::ThreadCommandPools,
+    queries::FrameQueries,
+    descriptors::write_buffer_descriptor,
+    scene_data::{GpuSceneData, SCENE_DATA_BINDING},
+};
 
 
 pub struct FrameData {
     pub command_pool: CommandPool,
     pub command_buffer: CommandBuffer,
     pub swapchain_semaphore: Semaphore,
-    pub render_semaphore: Semaphore,
-    pub render_fence: Fence
+    pub render_fence: Fence,
+    pub scene_buffer: AllocatedBuffer,
+    pub global_descriptor: DescriptorSet,
+    pub queries: FrameQueries,
+    /// Pools of the threads recording secondary command buffers for this frame.
+    pub thread_pools: ThreadCommandPools,
 }
 
 impl FrameData {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         device: &Device,
+        memory_properties: &PhysicalDeviceMemoryProperties,
         queue_family_index: u32,
-        render_semaphore : Semaphore, 
         swapchain_semaphore: Semaphore,
-        render_fence: Fence
+        render_fence: Fence,
+        global_descriptor: DescriptorSet,
+        queries: FrameQueries,
+        recording_threads: usize,
     ) -> Result<FrameData, Error> {
         let command_pool = create_command_pool(device, queue_family_index)?;
         let command_buffer = create_command_buffer(device, command_pool)?;
+        let thread_pools = ThreadCommandPools::new(device, queue_family_index, recording_threads)?;
+        let scene_buffer_size = size_of::<GpuSceneData>() as DeviceSize;
+        let scene_buffer = create_buffer(
+            device,
+            memory_properties,
+            scene_buffer_size,
+            BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+        write_buffer_descriptor(
+            device,
+            global_descriptor,
+            SCENE_DATA_BINDING,
+            DescriptorType::UNIFORM_BUFFER,
+            scene_buffer.buffer,
+            scene_buffer_size,
+        );
         Ok(Self {
             command_pool,
             command_buffer,
             render_fence,
-            render_semaphore,
-            swapchain_semaphore
+            swapchain_semaphore,
+            scene_buffer,
+            global_descriptor,
+            queries,
+            thread_pools,
         })
     }
+
+    pub fn name_objects(&self, names: &DebugNames, frame_index: usize) {
+        names.name(self.command_pool, &format!("frame {frame_index} command pool"));
+        names.name(self.command_buffer, &format!("frame {frame_index} command buffer"));
+        names.name(self.swapchain_semaphore, &format!("frame {frame_index} swapchain semaphore"));
+        names.name(self.render_fence, &format!("frame {frame_index} render fence"));
+        names.name_buffer(&self.scene_buffer, &format!("frame {frame_index} scene buffer"));
+        names.name(self.global_descriptor, &format!("frame {frame_index} global descriptor set"));
+        self.queries.name_objects(names, frame_index);
+        self.thread_pools.name_objects(names, frame_index);
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        unsafe {
+            device.destroy_command_pool(self.command_pool, None);
+            device.destroy_fence(self.render_fence, None);
+            device.destroy_semaphore(self.swapchain_semaphore, None);
+        }
+        self.scene_buffer.destroy(device);
+        self.queries.destroy(device);
+        self.thread_pools.destroy(device);
+    }
 }
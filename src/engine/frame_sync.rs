@@ -0,0 +1,179 @@
+use ash::{
+    vk::{Fence, PipelineStageFlags2, Semaphore, SemaphoreSubmitInfo, SemaphoreWaitInfo},
+    Device,
+};
+use log::info;
+
+use super::{
+    debug_names::DebugNames,
+    errors::engine_error::{EngineError, VkResultExt},
+    features::{EnabledFeatures, Feature},
+    sync_objects::create_timeline_semaphore,
+};
+
+/// Work queued with [`FrameSync::after_frame`].
+pub type AfterFrame = Box<dyn FnOnce(&Device)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncMode {
+    /// Frame `n` signals value `n + 1` on a single timeline semaphore.
+    TimelineSemaphore,
+    /// Every frame slot has a binary fence, used without `timelineSemaphore`.
+    Fences,
+}
+
+/// Tracks which frames the GPU has finished, either with a timeline semaphore or the
+/// fences of the frame slots, and runs work that has to wait for a frame to complete.
+pub struct FrameSync {
+    timeline: Option<Semaphore>,
+    /// Value signaled by the last frame submitted from each slot, 0 if none was.
+    slot_values: Vec<u64>,
+    /// Frames known to be finished, i.e. the highest value signaled so far.
+    completed_value: u64,
+    after_frame: Vec<(u64, AfterFrame)>,
+}
+
+impl FrameSync {
+    pub fn new(
+        device: &Device,
+        enabled_features: &EnabledFeatures,
+        prefer_timeline: bool,
+        frames_in_flight: usize,
+    ) -> Result<FrameSync, EngineError> {
+        let timeline = match prefer_timeline && enabled_features.is_enabled(Feature::TimelineSemaphore) {
+            true => Some(create_timeline_semaphore(device, 0)?),
+            false => None,
+        };
+        let sync = FrameSync {
+            timeline,
+            slot_values: vec![0; frames_in_flight],
+            completed_value: 0,
+            after_frame: Vec::new(),
+        };
+        info!("Frame synchronization uses {:?}", sync.mode());
+        Ok(sync)
+    }
+
+    pub fn mode(&self) -> SyncMode {
+        match self.timeline {
+            Some(_) => SyncMode::TimelineSemaphore,
+            None => SyncMode::Fences,
+        }
+    }
+
+    /// Blocks until the frame last submitted from `frame_index` finished. `fence` is the
+    /// slot's fence, only waited on in [`SyncMode::Fences`].
+    pub fn wait_for_slot(
+        &mut self,
+        device: &Device,
+        frame_index: usize,
+        fence: Fence,
+        timeout: u64,
+    ) -> Result<(), EngineError> {
+        let value = self.slot_values[frame_index];
+        match self.timeline {
+            Some(timeline) => {
+                if value > self.completed_value {
+                    let semaphores = [timeline];
+                    let values = [value];
+                    let wait_info = SemaphoreWaitInfo::default().semaphores(&semaphores).values(&values);
+                    unsafe { device.wait_semaphores(&wait_info, timeout) }
+                        .vk_object_context("vkWaitSemaphores", format!("frame timeline value {value}"))?;
+                }
+            }
+            None => unsafe { device.wait_for_fences(&[fence], true, timeout) }
+                .vk_object_context("vkWaitForFences", format!("frame {frame_index}"))?,
+        }
+        self.completed_value = self.completed_value.max(value);
+        Ok(())
+    }
+
+    /// Call once it is certain the slot's frame will be submitted. In [`SyncMode::Fences`]
+    /// this resets the slot's fence, which must then be passed to the submit through
+    /// [`Self::submit_fence`], or the next wait never returns.
+    pub fn begin_submit(&self, device: &Device, fence: Fence) -> Result<(), EngineError> {
+        if self.timeline.is_none() {
+            unsafe { device.reset_fences(&[fence]) }.vk_context("vkResetFences")?;
+        }
+        Ok(())
+    }
+
+    /// Records that `frame_number` was submitted from `frame_index`.
+    pub fn submitted(&mut self, frame_index: usize, frame_number: u64) {
+        self.slot_values[frame_index] = frame_number + 1;
+    }
+
+    /// The fence to signal with the frame's submit, null with a timeline semaphore.
+    pub fn submit_fence(&self, fence: Fence) -> Fence {
+        match self.timeline {
+            Some(_) => Fence::null(),
+            None => fence,
+        }
+    }
+
+    /// The timeline signal to add to the submit of `frame_number`, if there is a timeline.
+    pub fn signal_semaphore_info(&self, frame_number: u64) -> Option<SemaphoreSubmitInfo<'static>> {
+        self.timeline.map(|timeline| {
+            SemaphoreSubmitInfo::default()
+                .semaphore(timeline)
+                .value(frame_number + 1)
+                .stage_mask(PipelineStageFlags2::ALL_COMMANDS)
+        })
+    }
+
+    /// Starts over with `frames_in_flight` empty slots. The device has to be idle, so every
+    /// submitted frame counts as finished.
+    pub fn set_frames_in_flight(&mut self, frames_in_flight: usize) {
+        let last_submitted = self.slot_values.iter().copied().max().unwrap_or(0);
+        self.completed_value = self.completed_value.max(last_submitted);
+        self.slot_values = vec![0; frames_in_flight];
+    }
+
+    /// How many frames the GPU has finished. Without a timeline this only advances when
+    /// a slot's fence is waited on.
+    pub fn completed_frames(&mut self, device: &Device) -> Result<u64, EngineError> {
+        if let Some(timeline) = self.timeline {
+            let value = unsafe { device.get_semaphore_counter_value(timeline) }
+                .vk_context("vkGetSemaphoreCounterValue")?;
+            self.completed_value = self.completed_value.max(value);
+        }
+        Ok(self.completed_value)
+    }
+
+    /// Runs `callback` once frame `frame_number` finished on the GPU, checked every frame.
+    pub fn after_frame(&mut self, frame_number: u64, callback: AfterFrame) {
+        self.after_frame.push((frame_number, callback));
+    }
+
+    /// Runs the queued work of every finished frame, in the order it was queued.
+    pub fn run_completed(&mut self, device: &Device) -> Result<(), EngineError> {
+        let completed_frames = self.completed_frames(device)?;
+        let (completed, pending) = std::mem::take(&mut self.after_frame)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(frame_number, _)| *frame_number < completed_frames);
+        self.after_frame = pending;
+        for (_, callback) in completed {
+            callback(device);
+        }
+        Ok(())
+    }
+
+    /// Runs all queued work regardless of frames, the device has to be idle.
+    pub fn run_all(&mut self, device: &Device) {
+        for (_, callback) in self.after_frame.drain(..) {
+            callback(device);
+        }
+    }
+
+    pub fn name_objects(&self, names: &DebugNames) {
+        if let Some(timeline) = self.timeline {
+            names.name(timeline, "frame timeline semaphore");
+        }
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        if let Some(timeline) = self.timeline {
+            unsafe { device.destroy_semaphore(timeline, None) };
+        }
+    }
+}
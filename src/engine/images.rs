@@ -0,0 +1,133 @@
+use anyhow::{anyhow, Error};
+use ash::{
+    vk::{
+        DeviceMemory, Extent2D, Extent3D, Format, Image, ImageAspectFlags, ImageCreateInfo,
+        ImageLayout, ImageTiling, ImageType, ImageUsageFlags, ImageView, ImageViewCreateInfo,
+        ImageViewType, MemoryAllocateInfo, MemoryPropertyFlags, PhysicalDeviceMemoryProperties,
+        SampleCountFlags, SharingMode,
+    },
+    Device,
+};
+
+use super::{memory::find_memory_type_index, util::image_sub_resource_range};
+
+pub struct AllocatedImage {
+    pub image: Image,
+    pub image_view: ImageView,
+    pub memory: DeviceMemory,
+    pub extent: Extent3D,
+}
+
+impl AllocatedImage {
+    pub fn extent_2d(&self) -> Extent2D {
+        Extent2D::default()
+            .width(self.extent.width)
+            .height(self.extent.height)
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        unsafe {
+            device.destroy_image_view(self.image_view, None);
+            device.destroy_image(self.image, None);
+            device.free_memory(self.memory, None);
+        }
+    }
+}
+
+pub fn create_allocated_image(
+    device: &Device,
+    memory_properties: &PhysicalDeviceMemoryProperties,
+    extent: Extent2D,
+    format: Format,
+    usage: ImageUsageFlags,
+    samples: SampleCountFlags,
+    aspect: ImageAspectFlags,
+) -> Result<AllocatedImage, Error> {
+    let extent = Extent3D::default()
+        .width(extent.width)
+        .height(extent.height)
+        .depth(1);
+    let create_info = ImageCreateInfo::default()
+        .image_type(ImageType::TYPE_2D)
+        .format(format)
+        .extent(extent)
+        .mip_levels(1)
+        .array_layers(1)
+        .samples(samples)
+        .tiling(ImageTiling::OPTIMAL)
+        .usage(usage)
+        .sharing_mode(SharingMode::EXCLUSIVE)
+        .initial_layout(ImageLayout::UNDEFINED);
+    let image = unsafe { device.create_image(&create_info, None)? };
+
+    let memory = match allocate_image_memory(device, memory_properties, image, format, usage) {
+        Ok(memory) => memory,
+        Err(err) => {
+            unsafe { device.destroy_image(image, None) };
+            return Err(err);
+        }
+    };
+
+    let view_create_info = ImageViewCreateInfo::default()
+        .image(image)
+        .view_type(ImageViewType::TYPE_2D)
+        .format(format)
+        .subresource_range(image_sub_resource_range(aspect));
+    let image_view = match unsafe { device.create_image_view(&view_create_info, None) } {
+        Ok(image_view) => image_view,
+        Err(err) => {
+            unsafe {
+                device.destroy_image(image, None);
+                device.free_memory(memory, None);
+            }
+            return Err(err.into());
+        }
+    };
+
+    Ok(AllocatedImage {
+        image,
+        image_view,
+        memory,
+        extent,
+    })
+}
+
+/// Allocates and binds memory for `image`. Nothing is left allocated on failure.
+fn allocate_image_memory(
+    device: &Device,
+    memory_properties: &PhysicalDeviceMemoryProperties,
+    image: Image,
+    format: Format,
+    usage: ImageUsageFlags,
+) -> Result<DeviceMemory, Error> {
+    let requirements = unsafe { device.get_image_memory_requirements(image) };
+    // Transient attachments can live in lazily allocated memory on tilers, which never
+    // backs them with real memory. Everyone else falls back to plain device local memory.
+    let lazy_memory_type = match usage.contains(ImageUsageFlags::TRANSIENT_ATTACHMENT) {
+        true => find_memory_type_index(
+            memory_properties,
+            &requirements,
+            MemoryPropertyFlags::DEVICE_LOCAL | MemoryPropertyFlags::LAZILY_ALLOCATED,
+        ),
+        false => None,
+    };
+    let memory_type_index = lazy_memory_type
+        .or_else(|| {
+            find_memory_type_index(
+                memory_properties,
+                &requirements,
+                MemoryPropertyFlags::DEVICE_LOCAL,
+            )
+        })
+        .ok_or_else(|| anyhow!("No device local memory type for image of format {format:?}"))?;
+
+    let allocate_info = MemoryAllocateInfo::default()
+        .allocation_size(requirements.size)
+        .memory_type_index(memory_type_index);
+    let memory = unsafe { device.allocate_memory(&allocate_info, None)? };
+    if let Err(err) = unsafe { device.bind_image_memory(image, memory, 0) } {
+        unsafe { device.free_memory(memory, None) };
+        return Err(err.into());
+    }
+    Ok(memory)
+}
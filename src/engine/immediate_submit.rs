@@ -0,0 +1,135 @@
+use ash::{
+    vk::{
+        AccessFlags2, Buffer, BufferMemoryBarrier2, CommandBuffer, CommandBufferUsageFlags,
+        CommandPool, Fence, FenceCreateFlags, PipelineStageFlags2, Queue, SubmitInfo2,
+        QUEUE_FAMILY_IGNORED, WHOLE_SIZE,
+    },
+    Device,
+};
+
+use super::{
+    command_buffers::{create_command_buffer, create_command_pool},
+    command_encoder::CommandEncoder,
+    debug_names::DebugNames,
+    errors::engine_error::{EngineError, VkResultExt},
+    sync_objects::create_fence,
+};
+
+/// Records and submits one-off work on a queue and waits for it, e.g. uploads on the
+/// transfer queue. Nothing is left in flight when [`ImmediateSubmit::submit`] returns, so
+/// callers don't need to synchronize with the work.
+pub struct ImmediateSubmit {
+    command_pool: CommandPool,
+    command_buffer: CommandBuffer,
+    fence: Fence,
+    queue: Queue,
+    pub queue_family_index: u32,
+}
+
+impl ImmediateSubmit {
+    pub fn new(device: &Device, queue: Queue, queue_family_index: u32) -> Result<ImmediateSubmit, EngineError> {
+        let command_pool = create_command_pool(device, queue_family_index)?;
+        Ok(ImmediateSubmit {
+            command_pool,
+            command_buffer: create_command_buffer(device, command_pool)?,
+            fence: create_fence(device, FenceCreateFlags::empty())?,
+            queue,
+            queue_family_index,
+        })
+    }
+
+    /// Records `record` into a fresh command buffer, submits it and blocks until it finished.
+    pub fn submit(&self, device: &Device, record: impl FnOnce(&mut CommandEncoder)) -> Result<(), EngineError> {
+        unsafe { device.reset_command_pool(self.command_pool, Default::default()) }
+            .vk_context("vkResetCommandPool")?;
+        let mut encoder = CommandEncoder::begin(device, self.command_buffer, CommandBufferUsageFlags::ONE_TIME_SUBMIT)?;
+        record(&mut encoder);
+        let command_buffers = [encoder.finish()?.submit_info()];
+        let submit_info = SubmitInfo2::default().command_buffer_infos(&command_buffers);
+        unsafe {
+            device
+                .queue_submit2(self.queue, &[submit_info], self.fence)
+                .vk_object_context("vkQueueSubmit2", format!("queue family {}", self.queue_family_index))?;
+            device
+                .wait_for_fences(&[self.fence], true, u64::MAX)
+                .vk_context("vkWaitForFences")?;
+            device.reset_fences(&[self.fence]).vk_context("vkResetFences")
+        }
+    }
+
+    pub fn name_objects(&self, names: &DebugNames, name: &str) {
+        names.name(self.command_pool, &format!("{name} command pool"));
+        names.name(self.command_buffer, &format!("{name} command buffer"));
+        names.name(self.fence, &format!("{name} fence"));
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        unsafe {
+            device.destroy_command_pool(self.command_pool, None);
+            device.destroy_fence(self.fence, None);
+        }
+    }
+}
+
+/// Half of a queue family ownership transfer of a whole buffer. The release is recorded
+/// on the source queue, the matching acquire on the destination queue before first use.
+#[derive(Debug, Clone, Copy)]
+pub struct BufferOwnershipTransfer {
+    pub buffer: Buffer,
+    pub src_queue_family_index: u32,
+    pub dst_queue_family_index: u32,
+}
+
+impl BufferOwnershipTransfer {
+    pub fn is_needed(&self) -> bool {
+        self.src_queue_family_index != self.dst_queue_family_index
+    }
+
+    /// Releases the buffer after transfer writes on the source queue. Without a family
+    /// change this is a plain barrier making the writes visible to later reads.
+    pub fn record_release(&self, encoder: &mut CommandEncoder) {
+        self.record_release_from(encoder, PipelineStageFlags2::TRANSFER, AccessFlags2::TRANSFER_WRITE);
+    }
+
+    /// Like [`Self::record_release`], for a buffer last accessed in `src_stage_mask`.
+    pub fn record_release_from(
+        &self,
+        encoder: &mut CommandEncoder,
+        src_stage_mask: PipelineStageFlags2,
+        src_access_mask: AccessFlags2,
+    ) {
+        let barrier = match self.is_needed() {
+            true => self
+                .barrier()
+                .src_stage_mask(src_stage_mask)
+                .src_access_mask(src_access_mask),
+            false => self
+                .barrier()
+                .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+                .src_stage_mask(src_stage_mask)
+                .src_access_mask(src_access_mask)
+                .dst_stage_mask(PipelineStageFlags2::ALL_COMMANDS)
+                .dst_access_mask(AccessFlags2::MEMORY_READ),
+        };
+        encoder.buffer_barriers(&[barrier]);
+    }
+
+    /// Acquires the buffer on the destination queue, only needed if [`Self::is_needed`].
+    pub fn record_acquire(&self, encoder: &mut CommandEncoder) {
+        let barrier = self
+            .barrier()
+            .dst_stage_mask(PipelineStageFlags2::ALL_COMMANDS)
+            .dst_access_mask(AccessFlags2::MEMORY_READ);
+        encoder.buffer_barriers(&[barrier]);
+    }
+
+    fn barrier(&self) -> BufferMemoryBarrier2<'static> {
+        BufferMemoryBarrier2::default()
+            .buffer(self.buffer)
+            .offset(0)
+            .size(WHOLE_SIZE)
+            .src_queue_family_index(self.src_queue_family_index)
+            .dst_queue_family_index(self.dst_queue_family_index)
+    }
+}
@@ -1,110 +1,152 @@
 use std::ffi::CStr;
 use ash::{
     vk::{
-        ApplicationInfo, DebugUtilsMessageSeverityFlagsEXT, DebugUtilsMessageTypeFlagsEXT,
-        DebugUtilsMessengerCreateInfoEXT, InstanceCreateFlags, InstanceCreateInfo,
-        EXT_DEBUG_UTILS_NAME, KHR_PORTABILITY_ENUMERATION_NAME,
+        ApplicationInfo, InstanceCreateFlags, InstanceCreateInfo, ValidationFeatureEnableEXT,
+        ValidationFeaturesEXT, API_VERSION_1_3, EXT_DEBUG_UTILS_NAME,
+        EXT_VALIDATION_FEATURES_NAME, KHR_PORTABILITY_ENUMERATION_NAME,
     },
     Entry, Instance,
 };
+use log::warn;
 use winit::{raw_window_handle::HasDisplayHandle, window::Window};
 
-use super::debugger::debug_callback;
-use crate::engine::errors::instance_errors::InstanceCreationError;
+use super::config::ValidationConfig;
+use super::debugger::messenger_create_info;
+use crate::engine::errors::{
+    engine_error::{EngineError, VkResultExt},
+    instance_errors::InstanceCreationError,
+};
 
 static VULKAN_LIBRARY_LOCATION: &str = "/Users/tufan/VulkanSDK/1.3.296.0/macOS/lib/libvulkan.dylib";
 static ENGINE_NAME: &CStr = c"Metapod";
 static APP_NAME: &CStr = c"METAPOD";
+static VALIDATION_LAYER_NAME: &CStr = c"VK_LAYER_KHRONOS_validation";
 
-pub fn create_instance(window: &Window) -> Result<(Entry, Instance), InstanceCreationError> {
-    let entry = unsafe {
-        match Entry::load_from(VULKAN_LIBRARY_LOCATION) {
-            Ok(entry) => entry,
-            Err(err) => {
-                panic!(
-                    "{}",
-                    InstanceCreationError::EntryInvalidLocation {
-                        path: VULKAN_LIBRARY_LOCATION.to_string(),
-                        msg: err.to_string()
-                    }
-                )
-            }
+/// What of the requested [`ValidationConfig`] the instance ended up with.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InstanceDebugSupport {
+    pub validation: bool,
+    pub debug_utils: bool,
+}
+
+pub fn create_instance(
+    window: &Window,
+    validation: &ValidationConfig,
+) -> Result<(Entry, Instance, InstanceDebugSupport), EngineError> {
+    let entry = unsafe { Entry::load_from(VULKAN_LIBRARY_LOCATION) }.map_err(|err| {
+        InstanceCreationError::EntryInvalidLocation {
+            path: VULKAN_LIBRARY_LOCATION.to_string(),
+            msg: err.to_string(),
         }
-    };
+    })?;
 
     let application_info = ApplicationInfo::default()
         .engine_name(ENGINE_NAME)
         .engine_version(1)
         .application_version(1)
-        .application_name(APP_NAME);
+        .application_name(APP_NAME)
+        .api_version(API_VERSION_1_3);
 
     let mut enabled_extension_names = get_enabled_extensions(window)?;
- /*   let mut enabled_layer_names = unsafe {
-        entry
-            .enumerate_instance_layer_properties()
-            .unwrap()
-            .iter()
-            .map(|layer| layer.layer_name.as_ptr())
-            .collect::<Vec<*const i8>>()
-    };*/
+    let mut flags = InstanceCreateFlags::empty();
+    if is_extension_available(&entry, None, KHR_PORTABILITY_ENUMERATION_NAME)? {
+        enabled_extension_names.push(KHR_PORTABILITY_ENUMERATION_NAME.as_ptr());
+        flags |= InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR;
+    }
 
-    match check_validation_layer_support(&entry).unwrap() {
-        true => enabled_extension_names.push(EXT_DEBUG_UTILS_NAME.as_ptr()),
-        false => return Err(InstanceCreationError::ValidationCheck),
+    let mut support = InstanceDebugSupport::default();
+    let mut enabled_layer_names = Vec::new();
+    if validation.enabled {
+        match is_layer_available(&entry, VALIDATION_LAYER_NAME)? {
+            true => {
+                enabled_layer_names.push(VALIDATION_LAYER_NAME.as_ptr());
+                support.validation = true;
+            }
+            false => warn!("Validation was requested but {VALIDATION_LAYER_NAME:?} is not installed"),
+        }
+    }
+    if validation.debug_utils {
+        match is_extension_available(&entry, None, EXT_DEBUG_UTILS_NAME)? {
+            true => {
+                enabled_extension_names.push(EXT_DEBUG_UTILS_NAME.as_ptr());
+                support.debug_utils = true;
+            }
+            false => warn!("{EXT_DEBUG_UTILS_NAME:?} is not available, debug messages and names are disabled"),
+        }
+    }
+
+    let validation_feature_enables = validation_feature_enables(validation);
+    let use_validation_features = support.validation
+        && !validation_feature_enables.is_empty()
+        && match is_extension_available(&entry, Some(VALIDATION_LAYER_NAME), EXT_VALIDATION_FEATURES_NAME)? {
+            true => true,
+            false => {
+                warn!("{EXT_VALIDATION_FEATURES_NAME:?} is not available, {validation_feature_enables:?} stay disabled");
+                false
+            }
+        };
+    if use_validation_features {
+        enabled_extension_names.push(EXT_VALIDATION_FEATURES_NAME.as_ptr());
     }
 
-    let mut debug_create_info = DebugUtilsMessengerCreateInfoEXT::default()
-        .message_severity(
-            DebugUtilsMessageSeverityFlagsEXT::VERBOSE
-                | DebugUtilsMessageSeverityFlagsEXT::WARNING
-                | DebugUtilsMessageSeverityFlagsEXT::INFO
-                | DebugUtilsMessageSeverityFlagsEXT::ERROR,
-        )
-        .message_type(
-            DebugUtilsMessageTypeFlagsEXT::GENERAL
-                | DebugUtilsMessageTypeFlagsEXT::VALIDATION
-                | DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
-        )
-        .pfn_user_callback(Some(debug_callback));
+    let mut validation_features =
+        ValidationFeaturesEXT::default().enabled_validation_features(&validation_feature_enables);
+    // Also reports problems in vkCreateInstance and vkDestroyInstance, which the messenger can't.
+    let mut debug_create_info = messenger_create_info(validation);
 
-    let instance_create_info = InstanceCreateInfo::default()
+    let mut instance_create_info = InstanceCreateInfo::default()
         .enabled_extension_names(&enabled_extension_names)
-//        .enabled_layer_names(&enabled_layer_names)
-        .flags(InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR)
-        .application_info(&application_info)
-        .push_next(&mut debug_create_info);
+        .enabled_layer_names(&enabled_layer_names)
+        .flags(flags)
+        .application_info(&application_info);
+    if support.debug_utils {
+        instance_create_info = instance_create_info.push_next(&mut debug_create_info);
+    }
+    if use_validation_features {
+        instance_create_info = instance_create_info.push_next(&mut validation_features);
+    }
 
-    let instance = unsafe { entry.create_instance(&instance_create_info, None).unwrap() };
-    Ok((entry, instance))
+    let instance = unsafe { entry.create_instance(&instance_create_info, None) }
+        .vk_context("vkCreateInstance")?;
+    Ok((entry, instance, support))
 }
 
-fn check_validation_layer_support(entry: &Entry) -> Result<bool, &str> {
-    let validation_layers = vec!["VK_LAYER_KHRONOS_validation"];
-    unsafe {
-        let available_layers = entry.enumerate_instance_layer_properties().unwrap();
-        for layer in validation_layers {
-            for available_layer in available_layers.iter() {
-                if layer.eq(available_layer
-                    .layer_name_as_c_str()
-                    .unwrap()
-                    .to_str()
-                    .unwrap())
-                {
-                    return Ok(true);
-                }
-            }
-        }
-    };
-    Err("Validation Layers are not present on this machine")
+fn validation_feature_enables(validation: &ValidationConfig) -> Vec<ValidationFeatureEnableEXT> {
+    let mut enables = Vec::new();
+    if validation.gpu_assisted {
+        enables.push(ValidationFeatureEnableEXT::GPU_ASSISTED);
+        enables.push(ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT);
+    }
+    if validation.best_practices {
+        enables.push(ValidationFeatureEnableEXT::BEST_PRACTICES);
+    }
+    if validation.synchronization {
+        enables.push(ValidationFeatureEnableEXT::SYNCHRONIZATION_VALIDATION);
+    }
+    enables
 }
 
-fn get_enabled_extensions(window: &Window) -> Result<Vec<*const i8>, InstanceCreationError> {
-    let mut enumerate_required_extensions =
-        ash_window::enumerate_required_extensions(window.display_handle().unwrap().as_raw())
-            .unwrap()
-            .to_vec();
+fn is_layer_available(entry: &Entry, layer: &CStr) -> Result<bool, EngineError> {
+    let available_layers = unsafe { entry.enumerate_instance_layer_properties() }
+        .vk_context("vkEnumerateInstanceLayerProperties")?;
+    Ok(available_layers
+        .iter()
+        .any(|available_layer| available_layer.layer_name_as_c_str() == Ok(layer)))
+}
 
-    enumerate_required_extensions.push(KHR_PORTABILITY_ENUMERATION_NAME.as_ptr());
-    enumerate_required_extensions.push(EXT_DEBUG_UTILS_NAME.as_ptr());
+/// Checks the instance extensions of the implementation, or those provided by `layer`.
+fn is_extension_available(entry: &Entry, layer: Option<&CStr>, extension: &CStr) -> Result<bool, EngineError> {
+    let available_extensions = unsafe { entry.enumerate_instance_extension_properties(layer) }
+        .vk_context("vkEnumerateInstanceExtensionProperties")?;
+    Ok(available_extensions
+        .iter()
+        .any(|available_extension| available_extension.extension_name_as_c_str() == Ok(extension)))
+}
+
+fn get_enabled_extensions(window: &Window) -> Result<Vec<*const i8>, EngineError> {
+    let enumerate_required_extensions =
+        ash_window::enumerate_required_extensions(window.display_handle()?.as_raw())
+            .vk_context("vkEnumerateInstanceExtensionProperties")?
+            .to_vec();
     Ok(enumerate_required_extensions)
 }
@@ -0,0 +1,765 @@
+use std::{cmp::Ordering, mem::size_of};
+
+use anyhow::{anyhow, Error};
+use ash::{
+    vk::{
+        Buffer, BufferUsageFlags, CompareOp, CullModeFlags, DescriptorSet,
+        DescriptorSetLayout, DescriptorType, DeviceSize, Extent2D, FrontFace, IndexType,
+        MemoryPropertyFlags, Offset2D, PhysicalDeviceMemoryProperties, Pipeline, PipelineBindPoint,
+        PipelineCache, PipelineLayout, PushConstantRange, Rect2D, SampleCountFlags,
+        ShaderStageFlags, Viewport, WriteDescriptorSet,
+    },
+    Device,
+};
+use bytemuck::{Pod, Zeroable};
+use cgmath::{Matrix4, MetricSpace, Point3, Transform};
+
+use super::{
+    buffers::{create_buffer, AllocatedBuffer},
+    command_encoder::{BindCommands, CommandEncoder, RenderingScope},
+    config::MAX_FRAMES_IN_FLIGHT,
+    debug_names::DebugNames,
+    descriptors::{write_buffer_descriptor, DescriptorAllocator, DescriptorLayoutBuilder},
+    errors::engine_error::EngineError,
+    mesh::{Mesh, Vertex},
+    pipelines::{create_pipeline_layout, include_shader, load_shader_module, BlendMode, GraphicsPipelineBuilder},
+    render_targets::{DEPTH_IMAGE_FORMAT, DRAW_IMAGE_FORMAT},
+    scene_data::bind_global_descriptor,
+};
+use textures::{TextureData, Textures};
+pub use textures::{TextureEncoding, TextureId};
+
+mod textures;
+
+/// Materials that can exist at once, the parameter buffers are sized for this many.
+pub const MAX_MATERIALS: usize = 256;
+const PARAMETER_SET: u32 = 1;
+const PARAMETERS_BINDING: u32 = 0;
+const TEXTURE_SET: u32 = 2;
+/// Base color, metallic-roughness, normal, occlusion and emissive, in binding order.
+const TEXTURE_BINDINGS: u32 = 5;
+/// Below this many draws per secondary command buffer, recording them on another thread
+/// costs more than it saves.
+const MIN_DRAWS_PER_PART: usize = 32;
+
+/// How a material is blended, each template has its own pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MaterialTemplate {
+    Opaque,
+    /// Opaque, but fragments with a base color alpha below the cutoff are discarded.
+    AlphaMasked,
+    /// Alpha blended without depth writes, drawn after everything else.
+    Transparent,
+}
+
+impl MaterialTemplate {
+    pub const ALL: [MaterialTemplate; 3] = [
+        MaterialTemplate::Opaque,
+        MaterialTemplate::AlphaMasked,
+        MaterialTemplate::Transparent,
+    ];
+
+    fn blend_mode(self) -> BlendMode {
+        match self {
+            MaterialTemplate::Opaque | MaterialTemplate::AlphaMasked => BlendMode::Opaque,
+            MaterialTemplate::Transparent => BlendMode::Alpha,
+        }
+    }
+
+    /// Transparent surfaces are seen from both sides and don't occlude each other.
+    fn cull_mode(self) -> CullModeFlags {
+        match self {
+            MaterialTemplate::Opaque | MaterialTemplate::AlphaMasked => CullModeFlags::BACK,
+            MaterialTemplate::Transparent => CullModeFlags::NONE,
+        }
+    }
+
+    fn depth_write(self) -> bool {
+        self != MaterialTemplate::Transparent
+    }
+}
+
+/// Metallic-roughness parameters as in glTF, each multiplied with its texture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaterialParameters {
+    /// Linear RGBA.
+    pub base_color: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Linear RGB.
+    pub emissive: [f32; 3],
+    /// Only used by [`MaterialTemplate::AlphaMasked`].
+    pub alpha_cutoff: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+}
+
+impl Default for MaterialParameters {
+    fn default() -> Self {
+        Self {
+            base_color: [1.0, 1.0, 1.0, 1.0],
+            metallic: 1.0,
+            roughness: 1.0,
+            emissive: [0.0, 0.0, 0.0],
+            alpha_cutoff: 0.5,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+        }
+    }
+}
+
+/// Textures of a material, missing ones are replaced by neutral defaults.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MaterialTextures {
+    /// [`TextureEncoding::Srgb`].
+    pub base_color: Option<TextureId>,
+    /// [`TextureEncoding::Linear`], roughness in green and metallic in blue.
+    pub metallic_roughness: Option<TextureId>,
+    /// [`TextureEncoding::Linear`], tangent space.
+    pub normal: Option<TextureId>,
+    /// [`TextureEncoding::Linear`], occlusion in red.
+    pub occlusion: Option<TextureId>,
+    /// [`TextureEncoding::Srgb`].
+    pub emissive: Option<TextureId>,
+}
+
+/// Handle of a material created with [`Engine::create_material`](crate::engine::Engine::create_material).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MaterialId(pub(in crate::engine) usize);
+
+/// Mirrors `Material` in `material.glsl`, vec4 members only to match std430.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct GpuMaterialParameters {
+    base_color_factor: [f32; 4],
+    /// rgb: emissive factor, a: unused.
+    emissive_factor: [f32; 4],
+    /// x: metallic, y: roughness, z: alpha cutoff, w: normal scale.
+    metallic_roughness: [f32; 4],
+    /// x: occlusion strength, y: template, zw: unused.
+    extra: [f32; 4],
+}
+
+impl GpuMaterialParameters {
+    fn new(template: MaterialTemplate, parameters: &MaterialParameters) -> GpuMaterialParameters {
+        let [emissive_r, emissive_g, emissive_b] = parameters.emissive;
+        GpuMaterialParameters {
+            base_color_factor: parameters.base_color,
+            emissive_factor: [emissive_r, emissive_g, emissive_b, 0.0],
+            metallic_roughness: [
+                parameters.metallic,
+                parameters.roughness,
+                parameters.alpha_cutoff,
+                parameters.normal_scale,
+            ],
+            extra: [parameters.occlusion_strength, template as u32 as f32, 0.0, 0.0],
+        }
+    }
+}
+
+/// Mirrors `PushConstants` in `material.glsl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct DrawPushConstants {
+    model: [[f32; 4]; 4],
+    material_index: u32,
+}
+
+struct Material {
+    template: MaterialTemplate,
+    parameters: MaterialParameters,
+    textures: MaterialTextures,
+    texture_set: DescriptorSet,
+}
+
+/// CPU-side copy of every texture and material, see [`MaterialSystem::restore`].
+#[derive(Debug, Clone)]
+pub struct MaterialSnapshot {
+    textures: Vec<TextureData>,
+    materials: Vec<(MaterialTemplate, MaterialParameters, MaterialTextures)>,
+}
+
+/// The parameters of every material as seen by one frame in flight.
+struct MaterialFrame {
+    parameter_buffer: AllocatedBuffer,
+    parameter_set: DescriptorSet,
+}
+
+/// Owns the material pipelines, textures and parameters. Parameters are copied into the
+/// frame's storage buffer every frame, so changing them never races the GPU.
+pub struct MaterialSystem {
+    pipeline_layout: PipelineLayout,
+    /// Indexed by [`MaterialTemplate`].
+    pipelines: Vec<Pipeline>,
+    parameter_set_layout: DescriptorSetLayout,
+    texture_set_layout: DescriptorSetLayout,
+    parameter_descriptors: DescriptorAllocator,
+    texture_descriptors: DescriptorAllocator,
+    frames: Vec<MaterialFrame>,
+    materials: Vec<Material>,
+    textures: Textures,
+    /// Stand-ins for missing textures, in [`MaterialTextures`] order.
+    default_textures: [TextureId; TEXTURE_BINDINGS as usize],
+    memory_properties: PhysicalDeviceMemoryProperties,
+}
+
+impl MaterialSystem {
+    pub fn new(
+        device: &Device,
+        memory_properties: &PhysicalDeviceMemoryProperties,
+        global_set_layout: DescriptorSetLayout,
+        samples: SampleCountFlags,
+        frames_in_flight: usize,
+        pipeline_cache: PipelineCache,
+    ) -> Result<MaterialSystem, Error> {
+        let parameter_set_layout = DescriptorLayoutBuilder::default()
+            .add_binding(
+                PARAMETERS_BINDING,
+                DescriptorType::STORAGE_BUFFER,
+                ShaderStageFlags::FRAGMENT,
+            )
+            .build(device)?;
+        let texture_set_layout = (0..TEXTURE_BINDINGS)
+            .fold(DescriptorLayoutBuilder::default(), |builder, binding| {
+                builder.add_binding(
+                    binding,
+                    DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    ShaderStageFlags::FRAGMENT,
+                )
+            })
+            .build(device)?;
+        let push_constant_ranges = [PushConstantRange::default()
+            .stage_flags(ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT)
+            .offset(0)
+            .size(size_of::<DrawPushConstants>() as u32)];
+        let pipeline_layout = create_pipeline_layout(
+            device,
+            global_set_layout,
+            &[parameter_set_layout, texture_set_layout],
+            &push_constant_ranges,
+        )?;
+        let parameter_descriptors = DescriptorAllocator::new(
+            device,
+            MAX_FRAMES_IN_FLIGHT as u32,
+            &[(DescriptorType::STORAGE_BUFFER, 1.0)],
+        )?;
+        let texture_descriptors = DescriptorAllocator::new(
+            device,
+            MAX_MATERIALS as u32,
+            &[(DescriptorType::COMBINED_IMAGE_SAMPLER, TEXTURE_BINDINGS as f32)],
+        )?;
+
+        let mut textures = Textures::new(device)?;
+        let mut default_texture = |encoding: TextureEncoding, rgba: [u8; 4]| {
+            textures.create(device, memory_properties, Extent2D { width: 1, height: 1 }, encoding, &rgba)
+        };
+        let white_srgb = default_texture(TextureEncoding::Srgb, [255, 255, 255, 255])?;
+        let white_linear = default_texture(TextureEncoding::Linear, [255, 255, 255, 255])?;
+        let flat_normal = default_texture(TextureEncoding::Linear, [128, 128, 255, 255])?;
+
+        let mut system = MaterialSystem {
+            pipeline_layout,
+            pipelines: Vec::new(),
+            parameter_set_layout,
+            texture_set_layout,
+            parameter_descriptors,
+            texture_descriptors,
+            frames: Vec::new(),
+            materials: Vec::new(),
+            textures,
+            default_textures: [white_srgb, white_linear, flat_normal, white_linear, white_srgb],
+            memory_properties: *memory_properties,
+        };
+        system.rebuild_pipelines(device, samples, pipeline_cache)?;
+        system.set_frames_in_flight(device, frames_in_flight)?;
+        Ok(system)
+    }
+
+    /// The device has to be idle.
+    pub fn rebuild_pipelines(
+        &mut self,
+        device: &Device,
+        samples: SampleCountFlags,
+        pipeline_cache: PipelineCache,
+    ) -> Result<(), Error> {
+        for pipeline in self.pipelines.drain(..) {
+            unsafe { device.destroy_pipeline(pipeline, None) };
+        }
+        let vertex_shader = load_shader_module(device, include_shader!("pbr.vert"))?;
+        let fragment_shader = match load_shader_module(device, include_shader!("pbr.frag")) {
+            Ok(fragment_shader) => fragment_shader,
+            Err(err) => {
+                unsafe { device.destroy_shader_module(vertex_shader, None) };
+                return Err(err);
+            }
+        };
+        let bindings = Vertex::bindings();
+        let attributes = Vertex::attributes();
+        let pipelines = MaterialTemplate::ALL
+            .into_iter()
+            .map(|template| {
+                GraphicsPipelineBuilder::default()
+                    .shader(ShaderStageFlags::VERTEX, vertex_shader)
+                    .shader(ShaderStageFlags::FRAGMENT, fragment_shader)
+                    .vertex_input(&bindings, &attributes)
+                    .cull_mode(template.cull_mode(), FrontFace::COUNTER_CLOCKWISE)
+                    .samples(samples)
+                    .blend_mode(template.blend_mode())
+                    // Reverse-Z, see `camera::Projection`.
+                    .depth_test(template.depth_write(), CompareOp::GREATER_OR_EQUAL)
+                    .color_format(DRAW_IMAGE_FORMAT)
+                    .depth_format(DEPTH_IMAGE_FORMAT)
+                    .build(device, self.pipeline_layout, pipeline_cache)
+            })
+            .collect::<Result<Vec<_>, Error>>();
+        unsafe {
+            device.destroy_shader_module(vertex_shader, None);
+            device.destroy_shader_module(fragment_shader, None);
+        }
+        self.pipelines = pipelines?;
+        Ok(())
+    }
+
+    /// Adds or removes parameter buffers. The device has to be idle.
+    pub fn set_frames_in_flight(&mut self, device: &Device, frames_in_flight: usize) -> Result<(), Error> {
+        for frame in self.frames.drain(..) {
+            frame.parameter_buffer.destroy(device);
+            self.parameter_descriptors.free(device, frame.parameter_set)?;
+        }
+        let buffer_size = (MAX_MATERIALS * size_of::<GpuMaterialParameters>()) as DeviceSize;
+        for _ in 0..frames_in_flight {
+            let parameter_buffer = create_buffer(
+                device,
+                &self.memory_properties,
+                buffer_size,
+                BufferUsageFlags::STORAGE_BUFFER,
+                MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+            )?;
+            let parameter_set = self.parameter_descriptors.allocate(device, self.parameter_set_layout)?;
+            write_buffer_descriptor(
+                device,
+                parameter_set,
+                PARAMETERS_BINDING,
+                DescriptorType::STORAGE_BUFFER,
+                parameter_buffer.buffer,
+                buffer_size,
+            );
+            self.frames.push(MaterialFrame {
+                parameter_buffer,
+                parameter_set,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn create_texture(
+        &mut self,
+        device: &Device,
+        extent: Extent2D,
+        encoding: TextureEncoding,
+        rgba: &[u8],
+    ) -> Result<TextureId, Error> {
+        self.textures.create(device, &self.memory_properties, extent, encoding, rgba)
+    }
+
+    pub fn create_material(
+        &mut self,
+        device: &Device,
+        template: MaterialTemplate,
+        parameters: MaterialParameters,
+        textures: MaterialTextures,
+    ) -> Result<MaterialId, Error> {
+        if self.materials.len() >= MAX_MATERIALS {
+            return Err(anyhow!("Can't create more than {MAX_MATERIALS} materials"));
+        }
+        let texture_ids = [
+            textures.base_color,
+            textures.metallic_roughness,
+            textures.normal,
+            textures.occlusion,
+            textures.emissive,
+        ];
+        if let Some(texture) = texture_ids.iter().flatten().find(|texture| !self.textures.contains(**texture)) {
+            return Err(anyhow!("Unknown texture {texture:?}"));
+        }
+
+        let texture_set = self.texture_descriptors.allocate(device, self.texture_set_layout)?;
+        let image_infos = texture_ids
+            .iter()
+            .zip(self.default_textures)
+            .map(|(texture, default)| [self.textures.image_info(texture.unwrap_or(default))])
+            .collect::<Vec<_>>();
+        let writes = image_infos
+            .iter()
+            .enumerate()
+            .map(|(binding, image_info)| {
+                WriteDescriptorSet::default()
+                    .dst_set(texture_set)
+                    .dst_binding(binding as u32)
+                    .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(image_info)
+            })
+            .collect::<Vec<_>>();
+        unsafe { device.update_descriptor_sets(&writes, &[]) };
+
+        self.materials.push(Material {
+            template,
+            parameters,
+            textures,
+            texture_set,
+        });
+        Ok(MaterialId(self.materials.len() - 1))
+    }
+
+    pub fn snapshot(&self) -> MaterialSnapshot {
+        MaterialSnapshot {
+            textures: self.textures.data().to_vec(),
+            materials: self
+                .materials
+                .iter()
+                .map(|material| (material.template, material.parameters, material.textures))
+                .collect(),
+        }
+    }
+
+    /// Creates the textures and materials of `snapshot` again, e.g. after a device loss.
+    /// Must be called on a new system, so every texture and material gets its old id back.
+    pub fn restore(&mut self, device: &Device, snapshot: &MaterialSnapshot) -> Result<(), Error> {
+        // `new` already created the default textures, in the same order as before.
+        for data in snapshot.textures.iter().skip(self.textures.data().len()) {
+            self.create_texture(device, data.extent, data.encoding, &data.rgba)?;
+        }
+        for (template, parameters, textures) in snapshot.materials.iter() {
+            self.create_material(device, *template, *parameters, *textures)?;
+        }
+        Ok(())
+    }
+
+    /// Takes effect with the next frame.
+    pub fn set_parameters(&mut self, material: MaterialId, parameters: MaterialParameters) -> Result<(), EngineError> {
+        self.materials
+            .get_mut(material.0)
+            .ok_or(EngineError::UnknownMaterial(material))?
+            .parameters = parameters;
+        Ok(())
+    }
+
+    pub fn contains(&self, material: MaterialId) -> bool {
+        material.0 < self.materials.len()
+    }
+
+    /// Writes the parameters of every material into the frame's buffer. Must be called
+    /// once the fence of `frame_index` has been waited on.
+    pub fn begin_frame(&self, frame_index: usize) -> Result<(), Error> {
+        let parameters = self
+            .materials
+            .iter()
+            .map(|material| GpuMaterialParameters::new(material.template, &material.parameters))
+            .collect::<Vec<_>>();
+        self.frames[frame_index].parameter_buffer.write(&parameters)
+    }
+
+    /// See [`Textures::record_uploads`].
+    pub fn record_uploads(&mut self, encoder: &mut CommandEncoder) -> Vec<AllocatedBuffer> {
+        self.textures.record_uploads(encoder)
+    }
+
+    /// Resolves `draws` into what has to be recorded, ordered by template so opaque
+    /// surfaces come first and transparent ones last, see [`PreparedDraw::draw_order`].
+    pub fn draw_list<'a>(
+        &self,
+        frame_index: usize,
+        global_descriptor: DescriptorSet,
+        extent: Extent2D,
+        camera_position: Point3<f32>,
+        draws: impl IntoIterator<Item = (&'a Mesh, MaterialId, Matrix4<f32>)>,
+    ) -> Result<DrawList, EngineError> {
+        let mut draws = draws
+            .into_iter()
+            .map(|(mesh, material_id, transform)| {
+                let material = self
+                    .materials
+                    .get(material_id.0)
+                    .ok_or(EngineError::UnknownMaterial(material_id))?;
+                Ok(PreparedDraw {
+                    template: material.template,
+                    material: material_id,
+                    texture_set: material.texture_set,
+                    vertex_buffer: mesh.vertex_buffer.buffer,
+                    index_buffer: mesh.index_buffer.buffer,
+                    index_count: mesh.index_count,
+                    push_constants: DrawPushConstants {
+                        model: transform.into(),
+                        material_index: material_id.0 as u32,
+                    },
+                    camera_distance: transform
+                        .transform_point(mesh.bounds.center())
+                        .distance2(camera_position),
+                })
+            })
+            .collect::<Result<Vec<_>, EngineError>>()?;
+        draws.sort_by(PreparedDraw::draw_order);
+        Ok(DrawList {
+            pipeline_layout: self.pipeline_layout,
+            pipelines: self.pipelines.clone(),
+            global_descriptor,
+            parameter_set: self.frames[frame_index].parameter_set,
+            extent,
+            draws,
+        })
+    }
+
+    pub fn name_objects(&self, names: &DebugNames) {
+        names.name(self.pipeline_layout, "material pipeline layout");
+        for (template, pipeline) in MaterialTemplate::ALL.iter().zip(&self.pipelines) {
+            names.name(*pipeline, &format!("{template:?} material pipeline"));
+        }
+        names.name(self.parameter_set_layout, "material parameter set layout");
+        names.name(self.texture_set_layout, "material texture set layout");
+        names.name(self.parameter_descriptors.pool, "material parameter descriptor pool");
+        names.name(self.texture_descriptors.pool, "material texture descriptor pool");
+        for (frame_index, frame) in self.frames.iter().enumerate() {
+            names.name_buffer(&frame.parameter_buffer, &format!("frame {frame_index} material parameters"));
+        }
+        self.textures.name_objects(names);
+    }
+
+    pub fn destroy(&mut self, device: &Device) {
+        for frame in self.frames.drain(..) {
+            frame.parameter_buffer.destroy(device);
+        }
+        self.textures.destroy(device);
+        self.parameter_descriptors.destroy(device);
+        self.texture_descriptors.destroy(device);
+        unsafe {
+            for pipeline in self.pipelines.drain(..) {
+                device.destroy_pipeline(pipeline, None);
+            }
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_descriptor_set_layout(self.parameter_set_layout, None);
+            device.destroy_descriptor_set_layout(self.texture_set_layout, None);
+        }
+    }
+}
+
+#[derive(Clone)]
+struct PreparedDraw {
+    template: MaterialTemplate,
+    material: MaterialId,
+    texture_set: DescriptorSet,
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    index_count: u32,
+    push_constants: DrawPushConstants,
+    /// Squared distance of the mesh's center to the camera.
+    camera_distance: f32,
+}
+
+impl PreparedDraw {
+    /// Groups draws by pipeline, then material, then mesh to save state changes. Blending
+    /// needs transparent draws back to front instead, which costs a material change for
+    /// about every draw.
+    fn draw_order(&self, other: &PreparedDraw) -> Ordering {
+        self.template.cmp(&other.template).then_with(|| match self.template {
+            MaterialTemplate::Transparent => other.camera_distance.total_cmp(&self.camera_distance),
+            _ => (self.material, self.vertex_buffer).cmp(&(other.material, other.vertex_buffer)),
+        })
+    }
+}
+
+/// Mesh draws with everything needed to record them, so they can be recorded on any
+/// thread into a primary or secondary command buffer inside the scene's rendering.
+pub struct DrawList {
+    pipeline_layout: PipelineLayout,
+    pipelines: Vec<Pipeline>,
+    global_descriptor: DescriptorSet,
+    parameter_set: DescriptorSet,
+    extent: Extent2D,
+    draws: Vec<PreparedDraw>,
+}
+
+impl DrawList {
+    /// Splits the draws into at most `parts` lists of consecutive draws, fewer if they
+    /// would get less than [`MIN_DRAWS_PER_PART`] draws each. Recording the parts in order
+    /// gives the same result as recording the whole list.
+    pub fn split(self, parts: usize) -> Vec<DrawList> {
+        let parts = parts.min(self.draws.len() / MIN_DRAWS_PER_PART).max(1);
+        if parts == 1 {
+            return vec![self];
+        }
+        let part_size = self.draws.len().div_ceil(parts);
+        self.draws
+            .chunks(part_size)
+            .map(|draws| DrawList {
+                pipelines: self.pipelines.clone(),
+                draws: draws.to_vec(),
+                ..self
+            })
+            .collect()
+    }
+
+    pub fn record(&self, rendering: &mut RenderingScope) {
+        if self.draws.is_empty() {
+            return;
+        }
+        let viewport = Viewport::default()
+            .width(self.extent.width as f32)
+            .height(self.extent.height as f32)
+            .min_depth(0.0)
+            .max_depth(1.0);
+        let scissor = Rect2D {
+            offset: Offset2D::default(),
+            extent: self.extent,
+        };
+        let (mut bound_template, mut bound_texture_set, mut bound_mesh) = (None, None, None);
+        for draw in &self.draws {
+            if bound_template != Some(draw.template) {
+                rendering.bind_pipeline(PipelineBindPoint::GRAPHICS, self.pipelines[draw.template as usize]);
+                if bound_template.is_none() {
+                    rendering.set_viewport(viewport);
+                    rendering.set_scissor(scissor);
+                    bind_global_descriptor(
+                        rendering,
+                        PipelineBindPoint::GRAPHICS,
+                        self.pipeline_layout,
+                        self.global_descriptor,
+                    );
+                    rendering.bind_descriptor_sets(
+                        PipelineBindPoint::GRAPHICS,
+                        self.pipeline_layout,
+                        PARAMETER_SET,
+                        &[self.parameter_set],
+                    );
+                }
+                bound_template = Some(draw.template);
+            }
+            if bound_texture_set != Some(draw.texture_set) {
+                rendering.bind_descriptor_sets(
+                    PipelineBindPoint::GRAPHICS,
+                    self.pipeline_layout,
+                    TEXTURE_SET,
+                    &[draw.texture_set],
+                );
+                bound_texture_set = Some(draw.texture_set);
+            }
+            rendering.push_constants(
+                self.pipeline_layout,
+                ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&draw.push_constants),
+            );
+            if bound_mesh != Some(draw.vertex_buffer) {
+                rendering.bind_vertex_buffer(0, draw.vertex_buffer, 0);
+                rendering.bind_index_buffer(draw.index_buffer, 0, IndexType::UINT32);
+                bound_mesh = Some(draw.vertex_buffer);
+            }
+            rendering.draw_indexed(draw.index_count, 1, 0, 0, 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ash::vk::Handle;
+
+    use super::*;
+
+    fn draw(template: MaterialTemplate, material: usize, mesh: u64, camera_distance: f32) -> PreparedDraw {
+        PreparedDraw {
+            template,
+            material: MaterialId(material),
+            texture_set: DescriptorSet::null(),
+            vertex_buffer: Buffer::from_raw(mesh),
+            index_buffer: Buffer::from_raw(mesh),
+            index_count: 3,
+            push_constants: DrawPushConstants::zeroed(),
+            camera_distance,
+        }
+    }
+
+    fn sorted(mut draws: Vec<PreparedDraw>) -> Vec<(MaterialTemplate, usize, u64)> {
+        draws.sort_by(PreparedDraw::draw_order);
+        draws
+            .iter()
+            .map(|draw| (draw.template, draw.material.0, draw.vertex_buffer.as_raw()))
+            .collect()
+    }
+
+    #[test]
+    fn opaque_draws_come_before_masked_and_transparent_ones() {
+        use MaterialTemplate::*;
+        let order = sorted(vec![
+            draw(Transparent, 0, 1, 1.0),
+            draw(AlphaMasked, 1, 1, 1.0),
+            draw(Opaque, 2, 1, 1.0),
+            draw(Transparent, 3, 1, 1.0),
+            draw(Opaque, 4, 1, 1.0),
+            draw(AlphaMasked, 5, 1, 1.0),
+        ]);
+        let templates = order.iter().map(|(template, ..)| *template).collect::<Vec<_>>();
+        assert_eq!(
+            templates,
+            [Opaque, Opaque, AlphaMasked, AlphaMasked, Transparent, Transparent]
+        );
+    }
+
+    #[test]
+    fn opaque_draws_are_grouped_by_material_then_mesh() {
+        use MaterialTemplate::*;
+        let order = sorted(vec![
+            draw(Opaque, 1, 2, 1.0),
+            draw(Opaque, 0, 2, 5.0),
+            draw(Opaque, 1, 1, 9.0),
+            draw(Opaque, 0, 1, 3.0),
+        ]);
+        assert_eq!(
+            order,
+            [(Opaque, 0, 1), (Opaque, 0, 2), (Opaque, 1, 1), (Opaque, 1, 2)]
+        );
+    }
+
+    #[test]
+    fn transparent_draws_are_sorted_back_to_front() {
+        use MaterialTemplate::*;
+        let order = sorted(vec![
+            draw(Transparent, 0, 1, 4.0),
+            draw(Transparent, 0, 2, 16.0),
+            draw(Opaque, 0, 3, 100.0),
+            draw(Transparent, 1, 4, 1.0),
+            draw(Transparent, 1, 5, 9.0),
+        ]);
+        let meshes = order.iter().map(|(.., mesh)| *mesh).collect::<Vec<_>>();
+        assert_eq!(meshes, [3, 2, 5, 1, 4]);
+    }
+
+    fn draw_list(draw_count: u64) -> DrawList {
+        DrawList {
+            pipeline_layout: PipelineLayout::null(),
+            pipelines: Vec::new(),
+            global_descriptor: DescriptorSet::null(),
+            parameter_set: DescriptorSet::null(),
+            extent: Extent2D::default(),
+            draws: (0..draw_count).map(|mesh| draw(MaterialTemplate::Opaque, 0, mesh, 1.0)).collect(),
+        }
+    }
+
+    fn meshes(list: &DrawList) -> Vec<u64> {
+        list.draws.iter().map(|draw| draw.vertex_buffer.as_raw()).collect()
+    }
+
+    #[test]
+    fn split_keeps_the_draw_order() {
+        let draw_count = MIN_DRAWS_PER_PART as u64 * 3 + 1;
+        let parts = draw_list(draw_count).split(4);
+        assert_eq!(parts.len(), 3);
+        let order = parts.iter().flat_map(meshes).collect::<Vec<_>>();
+        assert_eq!(order, (0..draw_count).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn small_lists_are_not_split() {
+        let parts = draw_list(MIN_DRAWS_PER_PART as u64 * 2 - 1).split(4);
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].draws.len(), MIN_DRAWS_PER_PART * 2 - 1);
+    }
+}
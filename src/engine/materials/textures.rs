@@ -0,0 +1,208 @@
+use anyhow::{anyhow, Error};
+use ash::{
+    vk::{
+        BufferImageCopy, BufferUsageFlags, DescriptorImageInfo, Extent2D, Extent3D, Filter, Format,
+        Image, ImageAspectFlags, ImageLayout, ImageSubresourceLayers, ImageUsageFlags,
+        MemoryPropertyFlags, PhysicalDeviceMemoryProperties, SampleCountFlags, Sampler,
+        SamplerAddressMode, SamplerCreateInfo, SamplerMipmapMode,
+    },
+    Device,
+};
+
+use crate::engine::{
+    buffers::{create_buffer, AllocatedBuffer},
+    command_encoder::CommandEncoder,
+    debug_names::DebugNames,
+    images::{create_allocated_image, AllocatedImage},
+};
+
+/// Handle of a texture created with [`Engine::create_texture`](crate::engine::Engine::create_texture).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureId(usize);
+
+/// How the 8 bit channels of a texture are interpreted when sampled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureEncoding {
+    /// Colors, i.e. base color and emissive textures.
+    Srgb,
+    /// Data, i.e. metallic-roughness, normal and occlusion textures.
+    Linear,
+}
+
+impl TextureEncoding {
+    fn format(self) -> Format {
+        match self {
+            TextureEncoding::Srgb => Format::R8G8B8A8_SRGB,
+            TextureEncoding::Linear => Format::R8G8B8A8_UNORM,
+        }
+    }
+}
+
+/// The texels a texture was created from, kept so it can be created again on a new device.
+#[derive(Debug, Clone)]
+pub struct TextureData {
+    pub extent: Extent2D,
+    pub encoding: TextureEncoding,
+    pub rgba: Vec<u8>,
+}
+
+/// A texture waiting for its copy to be recorded.
+struct TextureUpload {
+    staging_buffer: AllocatedBuffer,
+    image: Image,
+    extent: Extent2D,
+}
+
+/// RGBA8 textures sampled by materials, all with the same linear repeating sampler.
+/// Uploads are recorded into the next frame's command buffer.
+pub struct Textures {
+    images: Vec<AllocatedImage>,
+    /// Indexed like `images`.
+    data: Vec<TextureData>,
+    pending_uploads: Vec<TextureUpload>,
+    pub sampler: Sampler,
+}
+
+impl Textures {
+    pub fn new(device: &Device) -> Result<Textures, Error> {
+        let sampler_create_info = SamplerCreateInfo::default()
+            .mag_filter(Filter::LINEAR)
+            .min_filter(Filter::LINEAR)
+            .mipmap_mode(SamplerMipmapMode::LINEAR)
+            .address_mode_u(SamplerAddressMode::REPEAT)
+            .address_mode_v(SamplerAddressMode::REPEAT)
+            .address_mode_w(SamplerAddressMode::REPEAT);
+        let sampler = unsafe { device.create_sampler(&sampler_create_info, None)? };
+        Ok(Textures {
+            images: Vec::new(),
+            data: Vec::new(),
+            pending_uploads: Vec::new(),
+            sampler,
+        })
+    }
+
+    /// `rgba` holds `width * height` tightly packed RGBA8 texels.
+    pub fn create(
+        &mut self,
+        device: &Device,
+        memory_properties: &PhysicalDeviceMemoryProperties,
+        extent: Extent2D,
+        encoding: TextureEncoding,
+        rgba: &[u8],
+    ) -> Result<TextureId, Error> {
+        let expected_size = extent.width as usize * extent.height as usize * 4;
+        if rgba.len() != expected_size || expected_size == 0 {
+            return Err(anyhow!(
+                "Texture of {}x{} needs {expected_size} bytes of RGBA8, got {}",
+                extent.width,
+                extent.height,
+                rgba.len()
+            ));
+        }
+        let staging_buffer = create_buffer(
+            device,
+            memory_properties,
+            rgba.len() as u64,
+            BufferUsageFlags::TRANSFER_SRC,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+        if let Err(err) = staging_buffer.write(rgba) {
+            staging_buffer.destroy(device);
+            return Err(err);
+        }
+        let image = match create_allocated_image(
+            device,
+            memory_properties,
+            extent,
+            encoding.format(),
+            ImageUsageFlags::SAMPLED | ImageUsageFlags::TRANSFER_DST,
+            SampleCountFlags::TYPE_1,
+            ImageAspectFlags::COLOR,
+        ) {
+            Ok(image) => image,
+            Err(err) => {
+                staging_buffer.destroy(device);
+                return Err(err);
+            }
+        };
+        self.pending_uploads.push(TextureUpload {
+            staging_buffer,
+            image: image.image,
+            extent,
+        });
+        self.images.push(image);
+        self.data.push(TextureData {
+            extent,
+            encoding,
+            rgba: rgba.to_vec(),
+        });
+        Ok(TextureId(self.images.len() - 1))
+    }
+
+    /// Copies every pending texture and leaves it in SHADER_READ_ONLY_OPTIMAL. Returns the
+    /// staging buffers, which have to live until the GPU finished the frame.
+    pub fn record_uploads(&mut self, encoder: &mut CommandEncoder) -> Vec<AllocatedBuffer> {
+        self.pending_uploads
+            .drain(..)
+            .map(|upload| {
+                encoder.transition_image(upload.image, ImageLayout::UNDEFINED, ImageLayout::TRANSFER_DST_OPTIMAL);
+                let region = BufferImageCopy::default()
+                    .image_subresource(
+                        ImageSubresourceLayers::default()
+                            .aspect_mask(ImageAspectFlags::COLOR)
+                            .layer_count(1),
+                    )
+                    .image_extent(Extent3D {
+                        width: upload.extent.width,
+                        height: upload.extent.height,
+                        depth: 1,
+                    });
+                encoder.copy_buffer_to_image(
+                    upload.staging_buffer.buffer,
+                    upload.image,
+                    ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[region],
+                );
+                encoder.transition_image(
+                    upload.image,
+                    ImageLayout::TRANSFER_DST_OPTIMAL,
+                    ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                );
+                upload.staging_buffer
+            })
+            .collect()
+    }
+
+    pub fn image_info(&self, texture: TextureId) -> DescriptorImageInfo {
+        DescriptorImageInfo::default()
+            .sampler(self.sampler)
+            .image_view(self.images[texture.0].image_view)
+            .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+    }
+
+    pub fn contains(&self, texture: TextureId) -> bool {
+        texture.0 < self.images.len()
+    }
+
+    /// Every texture in id order.
+    pub fn data(&self) -> &[TextureData] {
+        &self.data
+    }
+
+    pub fn name_objects(&self, names: &DebugNames) {
+        names.name(self.sampler, "material sampler");
+        for (index, image) in self.images.iter().enumerate() {
+            names.name_image(image, &format!("material texture {index}"));
+        }
+    }
+
+    pub fn destroy(&mut self, device: &Device) {
+        for upload in self.pending_uploads.drain(..) {
+            upload.staging_buffer.destroy(device);
+        }
+        for image in &self.images {
+            image.destroy(device);
+        }
+        unsafe { device.destroy_sampler(self.sampler, None) };
+    }
+}
@@ -0,0 +1,62 @@
+use ash::{
+    vk::{
+        DeviceSize, MemoryHeapFlags, MemoryPropertyFlags, MemoryRequirements, PhysicalDevice,
+        PhysicalDeviceMemoryBudgetPropertiesEXT, PhysicalDeviceMemoryProperties,
+        PhysicalDeviceMemoryProperties2,
+    },
+    Instance,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct HeapBudget {
+    pub size: DeviceSize,
+    pub device_local: bool,
+    /// How much this process can allocate before running into trouble, the heap size
+    /// when `VK_EXT_memory_budget` is not available.
+    pub budget: DeviceSize,
+    /// Current usage by this process, only known with `VK_EXT_memory_budget`.
+    pub usage: Option<DeviceSize>,
+}
+
+pub fn find_memory_type_index(
+    memory_properties: &PhysicalDeviceMemoryProperties,
+    requirements: &MemoryRequirements,
+    flags: MemoryPropertyFlags,
+) -> Option<u32> {
+    memory_properties.memory_types[..memory_properties.memory_type_count as usize]
+        .iter()
+        .enumerate()
+        .find(|(index, memory_type)| {
+            requirements.memory_type_bits & (1 << index) != 0
+                && memory_type.property_flags.contains(flags)
+        })
+        .map(|(index, _)| index as u32)
+}
+
+pub fn query_memory_budget(
+    instance: &Instance,
+    physical_device: PhysicalDevice,
+    memory_budget_enabled: bool,
+) -> Vec<HeapBudget> {
+    let mut budget_properties = PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+    let mut properties = PhysicalDeviceMemoryProperties2::default();
+    if memory_budget_enabled {
+        properties = properties.push_next(&mut budget_properties);
+    }
+    unsafe { instance.get_physical_device_memory_properties2(physical_device, &mut properties) };
+
+    let memory_properties = properties.memory_properties;
+    memory_properties.memory_heaps[..memory_properties.memory_heap_count as usize]
+        .iter()
+        .enumerate()
+        .map(|(index, heap)| HeapBudget {
+            size: heap.size,
+            device_local: heap.flags.contains(MemoryHeapFlags::DEVICE_LOCAL),
+            budget: match memory_budget_enabled {
+                true => budget_properties.heap_budget[index],
+                false => heap.size,
+            },
+            usage: memory_budget_enabled.then(|| budget_properties.heap_usage[index]),
+        })
+        .collect()
+}
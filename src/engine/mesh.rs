@@ -0,0 +1,68 @@
+use std::mem::{offset_of, size_of};
+
+use ash::{
+    vk::{Format, VertexInputAttributeDescription, VertexInputBindingDescription, VertexInputRate},
+    Device,
+};
+use bytemuck::{Pod, Zeroable};
+
+use super::{buffers::AllocatedBuffer, scene::bounds::Aabb};
+
+/// Vertex layout of every mesh, see `pbr.vert`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Pod, Zeroable)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    /// xyz: tangent, w: handedness of the bitangent. All zero disables normal mapping.
+    pub tangent: [f32; 4],
+}
+
+impl Vertex {
+    pub fn bindings() -> [VertexInputBindingDescription; 1] {
+        [VertexInputBindingDescription::default()
+            .binding(0)
+            .stride(size_of::<Vertex>() as u32)
+            .input_rate(VertexInputRate::VERTEX)]
+    }
+
+    pub fn attributes() -> [VertexInputAttributeDescription; 4] {
+        let attribute = |location: u32, format: Format, offset: usize| {
+            VertexInputAttributeDescription::default()
+                .location(location)
+                .binding(0)
+                .format(format)
+                .offset(offset as u32)
+        };
+        [
+            attribute(0, Format::R32G32B32_SFLOAT, offset_of!(Vertex, position)),
+            attribute(1, Format::R32G32B32_SFLOAT, offset_of!(Vertex, normal)),
+            attribute(2, Format::R32G32_SFLOAT, offset_of!(Vertex, uv)),
+            attribute(3, Format::R32G32B32A32_SFLOAT, offset_of!(Vertex, tangent)),
+        ]
+    }
+}
+
+/// Handle of a mesh uploaded with [`Engine::upload_mesh`](crate::engine::Engine::upload_mesh).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MeshId(pub(super) usize);
+
+/// Indexed triangle list in device local buffers with 32 bit indices.
+pub struct Mesh {
+    pub vertex_buffer: AllocatedBuffer,
+    pub index_buffer: AllocatedBuffer,
+    pub index_count: u32,
+    /// Object space bounds of the vertices.
+    pub bounds: Aabb,
+    /// CPU-side copies of the buffer contents, to upload the mesh again after a device loss.
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn destroy(&self, device: &Device) {
+        self.vertex_buffer.destroy(device);
+        self.index_buffer.destroy(device);
+    }
+}
@@ -0,0 +1,255 @@
+use std::mem::size_of;
+
+use anyhow::anyhow;
+use ash::{
+    vk::{
+        AccessFlags2, Buffer, BufferCopy, BufferMemoryBarrier2, BufferUsageFlags, DescriptorSet, DescriptorSetLayout,
+        DescriptorType, DeviceSize, MemoryPropertyFlags, PhysicalDeviceMemoryProperties, Pipeline,
+        PipelineBindPoint, PipelineCache, PipelineLayout, PipelineStageFlags2, PushConstantRange, Queue,
+        ShaderStageFlags, QUEUE_FAMILY_IGNORED, WHOLE_SIZE,
+    },
+    Device,
+};
+use bytemuck::{Pod, Zeroable};
+
+use super::{
+    buffers::{create_buffer, AllocatedBuffer},
+    command_encoder::{BindCommands, CommandEncoder},
+    debug_names::DebugNames,
+    descriptors::{write_buffer_descriptor, DescriptorAllocator, DescriptorLayoutBuilder},
+    errors::engine_error::EngineError,
+    immediate_submit::{BufferOwnershipTransfer, ImmediateSubmit},
+    mesh::Vertex,
+    pipelines::{create_compute_pipeline, create_pipeline_layout, load_shader_module},
+};
+
+const VERTICES_SET: u32 = 1;
+const VERTICES_BINDING: u32 = 0;
+/// `local_size_x` every generator shader has to use.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Push constants of every generator shader, see `ground.comp`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct GeneratorPushConstants {
+    parameters: [f32; 4],
+    vertex_count: u32,
+    _padding: [u32; 3],
+}
+
+/// Vertices written by a generator shader, in a device local vertex buffer released to
+/// the graphics queue, and read back for the mesh's bounds and CPU-side copy.
+pub struct GeneratedVertices {
+    pub vertex_buffer: AllocatedBuffer,
+    pub vertices: Vec<Vertex>,
+    /// Has to be acquired on the graphics queue before the buffer is used, if needed.
+    pub transfer: BufferOwnershipTransfer,
+}
+
+/// Runs compute shaders writing mesh vertices on the async compute queue. A shader gets
+/// the vertex buffer as a storage buffer at set 1, the global scene data set 0 is unused.
+pub struct MeshGenerator {
+    set_layout: DescriptorSetLayout,
+    pipeline_layout: PipelineLayout,
+    descriptors: DescriptorAllocator,
+    compute_context: ImmediateSubmit,
+    /// Family the vertex buffers are released to.
+    graphics_family: u32,
+}
+
+impl MeshGenerator {
+    pub fn new(
+        device: &Device,
+        global_set_layout: DescriptorSetLayout,
+        compute_queue: Queue,
+        compute_family: u32,
+        graphics_family: u32,
+    ) -> Result<MeshGenerator, EngineError> {
+        let compute_context = ImmediateSubmit::new(device, compute_queue, compute_family)?;
+        let descriptors = match DescriptorAllocator::new(device, 1, &[(DescriptorType::STORAGE_BUFFER, 1.0)]) {
+            Ok(descriptors) => descriptors,
+            Err(err) => {
+                compute_context.destroy(device);
+                return Err(err.into());
+            }
+        };
+        let mut generator = MeshGenerator {
+            set_layout: DescriptorSetLayout::null(),
+            pipeline_layout: PipelineLayout::null(),
+            descriptors,
+            compute_context,
+            graphics_family,
+        };
+        // Destroying null handles is a no-op, so whatever was created so far can go.
+        if let Err(err) = generator.create_layouts(device, global_set_layout) {
+            generator.destroy(device);
+            return Err(err.into());
+        }
+        Ok(generator)
+    }
+
+    fn create_layouts(&mut self, device: &Device, global_set_layout: DescriptorSetLayout) -> Result<(), anyhow::Error> {
+        self.set_layout = DescriptorLayoutBuilder::default()
+            .add_binding(VERTICES_BINDING, DescriptorType::STORAGE_BUFFER, ShaderStageFlags::COMPUTE)
+            .build(device)?;
+        let push_constant_ranges = [PushConstantRange::default()
+            .stage_flags(ShaderStageFlags::COMPUTE)
+            .offset(0)
+            .size(size_of::<GeneratorPushConstants>() as u32)];
+        self.pipeline_layout =
+            create_pipeline_layout(device, global_set_layout, &[self.set_layout], &push_constant_ranges)?;
+        Ok(())
+    }
+
+    /// Runs `shader` once per vertex and blocks until the vertices are read back. The
+    /// compute queue only keeps the work off the graphics queue, like uploads it doesn't
+    /// overlap with recording frames.
+    pub fn generate(
+        &self,
+        device: &Device,
+        memory_properties: &PhysicalDeviceMemoryProperties,
+        pipeline_cache: PipelineCache,
+        shader: &[u8],
+        vertex_count: u32,
+        parameters: [f32; 4],
+    ) -> Result<GeneratedVertices, EngineError> {
+        if vertex_count == 0 {
+            return Err(anyhow!("Can't generate a mesh without vertices").into());
+        }
+        let size = vertex_count as DeviceSize * size_of::<Vertex>() as DeviceSize;
+        let vertex_buffer = create_buffer(
+            device,
+            memory_properties,
+            size,
+            BufferUsageFlags::VERTEX_BUFFER | BufferUsageFlags::STORAGE_BUFFER | BufferUsageFlags::TRANSFER_SRC,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+        let readback = match create_buffer(
+            device,
+            memory_properties,
+            size,
+            BufferUsageFlags::TRANSFER_DST,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        ) {
+            Ok(readback) => readback,
+            Err(err) => {
+                vertex_buffer.destroy(device);
+                return Err(err.into());
+            }
+        };
+        let transfer = BufferOwnershipTransfer {
+            buffer: vertex_buffer.buffer,
+            src_queue_family_index: self.compute_context.queue_family_index,
+            dst_queue_family_index: self.graphics_family,
+        };
+        let push_constants = GeneratorPushConstants {
+            parameters,
+            vertex_count,
+            _padding: [0; 3],
+        };
+        let result = self
+            .run(device, pipeline_cache, shader, &vertex_buffer, |encoder, pipeline, set| {
+                encoder.bind_pipeline(PipelineBindPoint::COMPUTE, pipeline);
+                encoder.bind_descriptor_sets(PipelineBindPoint::COMPUTE, self.pipeline_layout, VERTICES_SET, &[set]);
+                encoder.push_constants(
+                    self.pipeline_layout,
+                    ShaderStageFlags::COMPUTE,
+                    0,
+                    bytemuck::bytes_of(&push_constants),
+                );
+                encoder.dispatch(vertex_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+                encoder.buffer_barriers(&[whole_buffer_barrier(vertex_buffer.buffer)
+                    .src_stage_mask(PipelineStageFlags2::COMPUTE_SHADER)
+                    .src_access_mask(AccessFlags2::SHADER_STORAGE_WRITE)
+                    .dst_stage_mask(PipelineStageFlags2::COPY)
+                    .dst_access_mask(AccessFlags2::TRANSFER_READ)]);
+                encoder.copy_buffer(vertex_buffer.buffer, readback.buffer, &[BufferCopy::default().size(size)]);
+                encoder.buffer_barriers(&[whole_buffer_barrier(readback.buffer)
+                    .src_stage_mask(PipelineStageFlags2::COPY)
+                    .src_access_mask(AccessFlags2::TRANSFER_WRITE)
+                    .dst_stage_mask(PipelineStageFlags2::HOST)
+                    .dst_access_mask(AccessFlags2::HOST_READ)]);
+                transfer.record_release_from(
+                    encoder,
+                    PipelineStageFlags2::COMPUTE_SHADER | PipelineStageFlags2::COPY,
+                    AccessFlags2::SHADER_STORAGE_WRITE,
+                );
+            })
+            .and_then(|()| Ok(readback.read_at::<Vertex>(0, vertex_count as usize)?));
+        readback.destroy(device);
+        match result {
+            Ok(vertices) => Ok(GeneratedVertices {
+                vertex_buffer,
+                vertices,
+                transfer,
+            }),
+            Err(err) => {
+                vertex_buffer.destroy(device);
+                Err(err)
+            }
+        }
+    }
+
+    /// Creates the pipeline and descriptor set for one run of `shader` and submits `record`.
+    fn run(
+        &self,
+        device: &Device,
+        pipeline_cache: PipelineCache,
+        shader: &[u8],
+        vertex_buffer: &AllocatedBuffer,
+        record: impl FnOnce(&mut CommandEncoder, Pipeline, DescriptorSet),
+    ) -> Result<(), EngineError> {
+        let shader_module = load_shader_module(device, shader)?;
+        let pipeline = create_compute_pipeline(device, self.pipeline_layout, shader_module, pipeline_cache);
+        unsafe { device.destroy_shader_module(shader_module, None) };
+        let pipeline = pipeline?;
+        let set = match self.descriptors.allocate(device, self.set_layout) {
+            Ok(set) => set,
+            Err(err) => {
+                unsafe { device.destroy_pipeline(pipeline, None) };
+                return Err(err.into());
+            }
+        };
+        write_buffer_descriptor(
+            device,
+            set,
+            VERTICES_BINDING,
+            DescriptorType::STORAGE_BUFFER,
+            vertex_buffer.buffer,
+            vertex_buffer.size,
+        );
+        let result = self
+            .compute_context
+            .submit(device, |encoder| record(encoder, pipeline, set));
+        // Nothing is in flight anymore once the submit returned, even if it failed.
+        let freed = self.descriptors.free(device, set);
+        unsafe { device.destroy_pipeline(pipeline, None) };
+        result?;
+        Ok(freed?)
+    }
+
+    pub fn name_objects(&self, names: &DebugNames) {
+        names.name(self.set_layout, "mesh generator set layout");
+        names.name(self.pipeline_layout, "mesh generator pipeline layout");
+        names.name(self.descriptors.pool, "mesh generator descriptor pool");
+        self.compute_context.name_objects(names, "mesh generator");
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        unsafe {
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_descriptor_set_layout(self.set_layout, None);
+        }
+        self.descriptors.destroy(device);
+        self.compute_context.destroy(device);
+    }
+}
+
+fn whole_buffer_barrier(buffer: Buffer) -> BufferMemoryBarrier2<'static> {
+    BufferMemoryBarrier2::default()
+        .buffer(buffer)
+        .offset(0)
+        .size(WHOLE_SIZE)
+        .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+}
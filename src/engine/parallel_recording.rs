@@ -0,0 +1,179 @@
+use std::thread;
+
+use ash::{
+    vk::{
+        CommandBuffer, CommandBufferBeginInfo, CommandBufferInheritanceInfo,
+        CommandBufferInheritanceRenderingInfo, CommandBufferUsageFlags, CommandPool,
+        CommandPoolResetFlags, Format, QueryControlFlags, QueryPipelineStatisticFlags,
+        SampleCountFlags,
+    },
+    Device,
+};
+
+use super::{
+    command_buffers::{create_command_pool, create_secondary_command_buffers},
+    command_encoder::{CommandEncoder, RenderingScope},
+    debug_names::DebugNames,
+    errors::engine_error::{EngineError, VkResultExt},
+};
+
+/// Records part of a pass into a secondary command buffer on a worker thread. The command
+/// buffer is already begun inside the pass's rendering and is ended afterwards.
+pub type RecordJob = Box<dyn FnOnce(&mut RenderingScope) + Send>;
+
+/// What a secondary command buffer needs to know about the dynamic rendering it continues.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderingInheritance {
+    pub color_format: Format,
+    pub depth_format: Format,
+    pub samples: SampleCountFlags,
+    /// Statistics of the pipeline statistics query active in the primary, empty if none is.
+    pub pipeline_statistics: QueryPipelineStatisticFlags,
+    /// Flags of the occlusion query active in the primary, `None` if none is.
+    pub occlusion_query: Option<QueryControlFlags>,
+}
+
+struct ThreadCommandPool {
+    command_pool: CommandPool,
+    command_buffers: Vec<CommandBuffer>,
+    used: usize,
+}
+
+/// Command pools of one frame in flight, one per recording thread, as a pool may only be
+/// used by one thread at a time. Secondary command buffers are allocated on demand and
+/// reused once the frame slot comes around again.
+pub struct ThreadCommandPools {
+    pools: Vec<ThreadCommandPool>,
+}
+
+impl ThreadCommandPools {
+    pub fn new(
+        device: &Device,
+        queue_family_index: u32,
+        threads: usize,
+    ) -> Result<ThreadCommandPools, EngineError> {
+        let pools = (0..threads.max(1))
+            .map(|_| {
+                Ok(ThreadCommandPool {
+                    command_pool: create_command_pool(device, queue_family_index)?,
+                    command_buffers: Vec::new(),
+                    used: 0,
+                })
+            })
+            .collect::<Result<_, EngineError>>()?;
+        Ok(ThreadCommandPools { pools })
+    }
+
+    /// Must be called after the slot's fence was waited on.
+    pub fn reset(&mut self, device: &Device) -> Result<(), EngineError> {
+        for pool in self.pools.iter_mut().filter(|pool| pool.used > 0) {
+            unsafe { device.reset_command_pool(pool.command_pool, CommandPoolResetFlags::empty()) }
+                .vk_context("vkResetCommandPool")?;
+            pool.used = 0;
+        }
+        Ok(())
+    }
+
+    /// Records every job into its own secondary command buffer, spreading the jobs over the
+    /// threads round robin. The command buffers are returned in the order of `jobs`, so
+    /// executing them gives the same result no matter which thread finished first.
+    pub fn record(
+        &mut self,
+        device: &Device,
+        inheritance: &RenderingInheritance,
+        jobs: Vec<RecordJob>,
+    ) -> Result<Vec<CommandBuffer>, EngineError> {
+        let thread_count = self.pools.len();
+        let mut thread_jobs = (0..thread_count).map(|_| Vec::new()).collect::<Vec<_>>();
+        let job_count = jobs.len();
+        for (job_index, job) in jobs.into_iter().enumerate() {
+            thread_jobs[job_index % thread_count].push(job);
+        }
+
+        // Allocating needs the pool too, so it happens before the threads start.
+        let mut thread_command_buffers = Vec::with_capacity(thread_count);
+        for (pool, jobs) in self.pools.iter_mut().zip(&thread_jobs) {
+            let needed = pool.used + jobs.len();
+            if needed > pool.command_buffers.len() {
+                let missing = (needed - pool.command_buffers.len()) as u32;
+                let allocated = create_secondary_command_buffers(device, pool.command_pool, missing)?;
+                pool.command_buffers.extend(allocated);
+            }
+            thread_command_buffers.push(pool.command_buffers[pool.used..needed].to_vec());
+            pool.used = needed;
+        }
+
+        let recorded = match thread_count {
+            1 => vec![record_jobs(
+                device,
+                inheritance,
+                thread_jobs.pop().unwrap_or_default(),
+                &thread_command_buffers[0],
+            )],
+            _ => thread::scope(|scope| {
+                let handles = thread_jobs
+                    .into_iter()
+                    .zip(&thread_command_buffers)
+                    .filter(|(jobs, _)| !jobs.is_empty())
+                    .map(|(jobs, command_buffers)| {
+                        scope.spawn(move || record_jobs(device, inheritance, jobs, command_buffers))
+                    })
+                    .collect::<Vec<_>>();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+                    .collect()
+            }),
+        };
+        for result in recorded {
+            result?;
+        }
+
+        // Job `i` was the `i / thread_count`th job of thread `i % thread_count`.
+        Ok((0..job_count)
+            .map(|job_index| thread_command_buffers[job_index % thread_count][job_index / thread_count])
+            .collect())
+    }
+
+    pub fn name_objects(&self, names: &DebugNames, frame_index: usize) {
+        for (thread_index, pool) in self.pools.iter().enumerate() {
+            names.name(
+                pool.command_pool,
+                &format!("frame {frame_index} recording thread {thread_index} command pool"),
+            );
+        }
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        for pool in &self.pools {
+            unsafe { device.destroy_command_pool(pool.command_pool, None) };
+        }
+    }
+}
+
+fn record_jobs(
+    device: &Device,
+    inheritance: &RenderingInheritance,
+    jobs: Vec<RecordJob>,
+    command_buffers: &[CommandBuffer],
+) -> Result<(), EngineError> {
+    let color_formats = [inheritance.color_format];
+    for (job, &command_buffer) in jobs.into_iter().zip(command_buffers) {
+        let mut rendering_info = CommandBufferInheritanceRenderingInfo::default()
+            .color_attachment_formats(&color_formats)
+            .depth_attachment_format(inheritance.depth_format)
+            .rasterization_samples(inheritance.samples);
+        let inheritance_info = CommandBufferInheritanceInfo::default()
+            .pipeline_statistics(inheritance.pipeline_statistics)
+            .occlusion_query_enable(inheritance.occlusion_query.is_some())
+            .query_flags(inheritance.occlusion_query.unwrap_or_default())
+            .push_next(&mut rendering_info);
+        let begin_info = CommandBufferBeginInfo::default()
+            .flags(CommandBufferUsageFlags::ONE_TIME_SUBMIT | CommandBufferUsageFlags::RENDER_PASS_CONTINUE)
+            .inheritance_info(&inheritance_info);
+        let mut encoder = CommandEncoder::begin_with(device, command_buffer, &begin_info)?;
+        job(&mut encoder.continue_rendering());
+        encoder.finish()?;
+    }
+    Ok(())
+}
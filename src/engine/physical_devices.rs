@@ -1,103 +1,229 @@
-use std::collections::{BTreeSet, HashSet};
+use std::ffi::CStr;
 
-use crate::engine::{swapchain::SwapchainSupportDetails};
+use crate::engine::config::GpuSelector;
 use crate::engine::queues::QueueIndices;
-use ash::vk::QueueFlags;
+use crate::engine::swapchain::SwapchainSupportDetails;
+use crate::engine::features::{DeviceRequirements, Feature, FeatureChain};
+use ash::vk::{
+    self, MemoryHeapFlags, PhysicalDeviceIDProperties, PhysicalDeviceProperties,
+    PhysicalDeviceProperties2, PhysicalDeviceType, QueueFamilyProperties, QueueFlags,
+    SampleCountFlags,
+};
 use ash::{
-    vk::{ExtensionProperties, PhysicalDevice, SurfaceKHR},
+    vk::{PhysicalDevice, SurfaceKHR},
     Instance,
 };
-use log::{debug, info};
+use log::{info, warn};
+
+use crate::engine::errors::device_error::{DeviceError, DeviceRejection};
 
-use crate::engine::errors::device_error::DeviceError;
+pub const REQUIRED_API_VERSION: u32 = vk::API_VERSION_1_3;
 
+/// Picks the device meeting `requirements` with the highest [`score_device`], or the one
+/// matching `selector`. Every device that was passed over is reported with its reasons.
 pub fn find_physical_device(
     instance: &Instance,
     surface_instance: &ash::khr::surface::Instance,
     surface: SurfaceKHR,
+    requirements: &DeviceRequirements,
+    selector: Option<&GpuSelector>,
 ) -> Result<PhysicalDevice, DeviceError> {
-    let enumerated_devices = unsafe { instance.enumerate_physical_devices() };
-    debug!("{:?}", enumerated_devices);
-    let physical_devices_vec: Vec<PhysicalDevice> = match enumerated_devices {
-        Ok(physical_devices) => physical_devices,
-        Err(err) => {
-            return Err(DeviceError::NoPhysicalDeviceFound);
+    let physical_devices = unsafe { instance.enumerate_physical_devices() }
+        .map_err(DeviceError::EnumerationFailed)?;
+
+    let mut rejections = Vec::new();
+    let mut candidates = Vec::new();
+    for (index, physical_device) in physical_devices.iter().copied().enumerate() {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let name = device_name(&properties);
+        let mut reasons =
+            unsuitable_reasons(physical_device, instance, surface_instance, surface, requirements);
+        if let Some(selector) = selector {
+            if !matches_selector(instance, physical_device, &properties, index, selector) {
+                reasons.push(format!("does not match the GPU override ({selector})"));
+            }
+        }
+
+        if reasons.is_empty() {
+            let score = score_device(instance, physical_device, &properties, requirements);
+            info!("[{index}] {name} ({:?}): score {score}", properties.device_type);
+            candidates.push((score, physical_device, name));
+        } else {
+            info!("[{index}] {name} rejected: {}", reasons.join(", "));
+            rejections.push(DeviceRejection { index, name, reasons });
         }
+    }
+
+    match candidates.into_iter().max_by_key(|(score, _, _)| *score) {
+        Some((_, physical_device, name)) => {
+            info!("Selected {name}");
+            Ok(physical_device)
+        }
+        None => Err(DeviceError::NoPhysicalDeviceFound { rejections }),
+    }
+}
+
+/// Higher is better. Device type dominates, then video memory, API version and supported
+/// optional features and extensions break ties between devices of the same kind.
+pub fn score_device(
+    instance: &Instance,
+    physical_device: PhysicalDevice,
+    properties: &PhysicalDeviceProperties,
+    requirements: &DeviceRequirements,
+) -> u64 {
+    let type_score = match properties.device_type {
+        PhysicalDeviceType::DISCRETE_GPU => 100_000,
+        PhysicalDeviceType::INTEGRATED_GPU => 50_000,
+        PhysicalDeviceType::VIRTUAL_GPU => 20_000,
+        PhysicalDeviceType::CPU => 1_000,
+        _ => 0,
     };
 
-    let physical_devices = physical_devices_vec
+    let memory_properties =
+        unsafe { instance.get_physical_device_memory_properties(physical_device) };
+    let device_local_mib = memory_properties.memory_heaps
+        [..memory_properties.memory_heap_count as usize]
         .iter()
-        .filter(|&device| is_device_suitable(*device, instance, &surface_instance, surface))
-        .collect::<Vec<&PhysicalDevice>>();
+        .filter(|heap| heap.flags.contains(MemoryHeapFlags::DEVICE_LOCAL))
+        .map(|heap| heap.size / (1024 * 1024))
+        .max()
+        .unwrap_or(0);
+    // One point per 64 MiB, capped so a huge heap cannot outweigh the device type.
+    let memory_score = (device_local_mib / 64).min(10_000);
+
+    let api_score = vk::api_version_minor(properties.api_version) as u64 * 100;
 
-    info!("Found {} physical devices", physical_devices.len());
-    Ok(**physical_devices.first().unwrap())
+    let optional_score = requirements.optional_supported_count(instance, physical_device) as u64 * 50;
+
+    type_score + memory_score + api_score + optional_score
 }
 
-fn check_device_extensions(
+fn matches_selector(
     instance: &Instance,
     physical_device: PhysicalDevice,
-    device_extension_properties: Vec<&str>,
+    properties: &PhysicalDeviceProperties,
+    index: usize,
+    selector: &GpuSelector,
 ) -> bool {
-    let mut count = 0;
-    let available_extension_properties = unsafe {
-        instance
-            .enumerate_device_extension_properties(physical_device)
-            .unwrap()
+    match selector {
+        GpuSelector::Name(name) => device_name(properties)
+            .to_lowercase()
+            .contains(&name.to_lowercase()),
+        GpuSelector::Index(selected) => *selected == index,
+        GpuSelector::Id {
+            vendor_id,
+            device_id,
+        } => properties.vendor_id == *vendor_id && properties.device_id == *device_id,
+        GpuSelector::Uuid(uuid) => {
+            let mut id_properties = PhysicalDeviceIDProperties::default();
+            let mut properties2 = PhysicalDeviceProperties2::default().push_next(&mut id_properties);
+            unsafe { instance.get_physical_device_properties2(physical_device, &mut properties2) };
+            id_properties.device_uuid == *uuid
+        }
+    }
+}
+
+fn device_name(properties: &PhysicalDeviceProperties) -> String {
+    properties
+        .device_name_as_c_str()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Sample counts usable for both color and depth framebuffer attachments.
+pub fn supported_sample_counts(instance: &Instance, physical_device: PhysicalDevice) -> SampleCountFlags {
+    let limits = unsafe { instance.get_physical_device_properties(physical_device) }.limits;
+    limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts
+}
+
+pub fn supports_extension(instance: &Instance, physical_device: PhysicalDevice, extension: &CStr) -> bool {
+    unsafe { instance.enumerate_device_extension_properties(physical_device) }
+        .map(|extensions| {
+            extensions
+                .iter()
+                .any(|properties| properties.extension_name_as_c_str() == Ok(extension))
+        })
+        .unwrap_or(false)
+}
+
+/// Static information about the selected device, shown in the inspector.
+pub struct DeviceInfo {
+    pub properties: PhysicalDeviceProperties,
+    /// Every known feature and whether the device supports it.
+    pub features: Vec<(Feature, bool)>,
+    pub queue_families: Vec<QueueFamilyProperties>,
+}
+
+impl DeviceInfo {
+    pub fn query(instance: &Instance, physical_device: PhysicalDevice) -> DeviceInfo {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let queue_families =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+
+        let supported = FeatureChain::query(instance, physical_device);
+        let features = Feature::ALL
             .iter()
-            .map(|extension| {
-                extension
-                    .extension_name_as_c_str()
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .to_owned()
-            })
-            .collect::<Vec<_>>()
-    };
+            .map(|feature| (*feature, feature.is_set(&supported)))
+            .collect();
 
-    for extension in device_extension_properties.clone() {
-        debug!("extension={:?}, test={:?}", extension, available_extension_properties[0]);
-        if available_extension_properties.contains(&extension.to_owned()) {
-            count = count + 1;
+        DeviceInfo {
+            properties,
+            features,
+            queue_families,
         }
     }
-    debug!("count={}", count);
-    count == device_extension_properties.len()
+
+    pub fn name(&self) -> String {
+        device_name(&self.properties)
+    }
 }
 
-fn is_device_suitable(
+/// Everything that rules `physical_device` out, empty if it can run the engine.
+fn unsuitable_reasons(
     physical_device: PhysicalDevice,
     instance: &Instance,
     surface_instance: &ash::khr::surface::Instance,
     surface: SurfaceKHR,
-) -> bool {
-    let device_properties = unsafe { instance.get_physical_device_properties(physical_device) };
-    let device_features = unsafe { instance.get_physical_device_features(physical_device) };
-    let extensions = vec![ash::khr::swapchain::NAME.to_str().to_owned().unwrap()];
-    let queue_family_indices = match QueueIndices::find_queue_family_indices(
+    requirements: &DeviceRequirements,
+) -> Vec<String> {
+    let mut reasons = Vec::new();
+    let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+    if properties.api_version < REQUIRED_API_VERSION {
+        reasons.push(format!(
+            "Vulkan {}.{} is below the required 1.3",
+            vk::api_version_major(properties.api_version),
+            vk::api_version_minor(properties.api_version)
+        ));
+        // Querying 1.3 features on an older device is not valid.
+        return reasons;
+    }
+
+    reasons.extend(requirements.missing(instance, physical_device));
+
+    match QueueIndices::find_queue_family_indices(
         physical_device,
         instance,
-        &surface_instance,
+        surface_instance,
         &surface,
-        QueueFlags::GRAPHICS
+        QueueFlags::GRAPHICS,
     ) {
-        Ok(q_family) => q_family,
-        Err(err) => panic!("{}", err),
-    };
+        Ok(queue_indices) if queue_indices.is_complete() => {}
+        Ok(_) => reasons.push("no queue family can present to the surface".to_owned()),
+        Err(err) => reasons.push(format!("no graphics queue family ({err})")),
+    }
 
-    let extensions_supported = check_device_extensions(instance, physical_device, extensions);
-    let swapchain_support = match SwapchainSupportDetails::query_swapchain_support(
-        surface_instance,
-        physical_device,
-        surface,
-    ) {
-        Ok(support_details) => support_details,
-        Err(err) => panic!("{:?}", err),
-    };
-    debug!("{}", extensions_supported);
-    queue_family_indices.is_complete()
-        && extensions_supported
-        && !swapchain_support.surface_formats.is_empty()
-        && !swapchain_support.present_modes.is_empty()
+    match SwapchainSupportDetails::query_swapchain_support(surface_instance, physical_device, surface) {
+        Ok(support) if support.surface_formats.is_empty() => {
+            reasons.push("no surface formats".to_owned())
+        }
+        Ok(support) if support.present_modes.is_empty() => {
+            reasons.push("no present modes".to_owned())
+        }
+        Ok(_) => {}
+        Err(err) => {
+            warn!("Failed to query the swapchain support: {err}");
+            reasons.push("swapchain support could not be queried".to_owned());
+        }
+    }
+    reasons
 }
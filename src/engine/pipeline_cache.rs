@@ -0,0 +1,137 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ash::{
+    vk::{PhysicalDeviceProperties, PipelineCache, PipelineCacheCreateInfo, UUID_SIZE},
+    Device,
+};
+use log::{info, warn};
+
+use super::errors::engine_error::{EngineError, VkResultExt};
+
+/// Size of `VkPipelineCacheHeaderVersionOne`.
+const HEADER_SIZE: usize = 16 + UUID_SIZE;
+/// `VK_PIPELINE_CACHE_HEADER_VERSION_ONE`.
+const HEADER_VERSION_ONE: u32 = 1;
+
+/// A `VkPipelineCache` loaded from and saved to a file specific to the device and driver.
+/// Files that don't match the device or are corrupt are discarded.
+pub struct PersistentPipelineCache {
+    pub cache: PipelineCache,
+    /// `None` if the cache is not persisted.
+    path: Option<PathBuf>,
+}
+
+impl PersistentPipelineCache {
+    /// Loads the cache for this device from `directory`, or starts empty.
+    pub fn new(
+        device: &Device,
+        properties: &PhysicalDeviceProperties,
+        directory: Option<&Path>,
+    ) -> Result<PersistentPipelineCache, EngineError> {
+        let path = directory.map(|directory| directory.join(Self::file_name(properties)));
+        let initial_data = path
+            .as_deref()
+            .and_then(|path| Self::load(path, properties))
+            .unwrap_or_default();
+        let create_info = PipelineCacheCreateInfo::default().initial_data(&initial_data);
+        let cache = match unsafe { device.create_pipeline_cache(&create_info, None) } {
+            Ok(cache) => cache,
+            // The header matched but the driver still rejected the data.
+            Err(err) if !initial_data.is_empty() => {
+                warn!("Discarding the pipeline cache, the driver rejected it: {err}");
+                unsafe { device.create_pipeline_cache(&PipelineCacheCreateInfo::default(), None) }
+                    .vk_context("vkCreatePipelineCache")?
+            }
+            Err(err) => return Err(err).vk_context("vkCreatePipelineCache"),
+        };
+        Ok(PersistentPipelineCache { cache, path })
+    }
+
+    /// Writes the cache next to the old file and renames it, so a crash never leaves a
+    /// half written cache behind. Failures are only logged.
+    pub fn save(&self, device: &Device) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let data = match unsafe { device.get_pipeline_cache_data(self.cache) } {
+            Ok(data) => data,
+            Err(err) => {
+                warn!("Failed to read the pipeline cache data: {err}");
+                return;
+            }
+        };
+        let temporary_path = path.with_extension("tmp");
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&temporary_path, &data))
+            .and_then(|_| fs::rename(&temporary_path, path));
+        match result {
+            Ok(()) => info!("Saved {} bytes of pipeline cache to {}", data.len(), path.display()),
+            Err(err) => warn!("Failed to save the pipeline cache to {}: {err}", path.display()),
+        }
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        unsafe { device.destroy_pipeline_cache(self.cache, None) };
+    }
+
+    /// Keyed by everything that invalidates a cache. The driver version isn't part of the
+    /// header, so it only lives in the name.
+    fn file_name(properties: &PhysicalDeviceProperties) -> String {
+        let uuid = properties
+            .pipeline_cache_uuid
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        format!(
+            "{:04x}-{:04x}-{:08x}-{uuid}.bin",
+            properties.vendor_id, properties.device_id, properties.driver_version
+        )
+    }
+
+    /// The file's contents if its header matches `properties`. Mismatched or corrupt
+    /// files are deleted.
+    fn load(path: &Path, properties: &PhysicalDeviceProperties) -> Option<Vec<u8>> {
+        let data = fs::read(path).ok()?;
+        match Self::validate_header(&data, properties) {
+            Ok(()) => {
+                info!("Loaded {} bytes of pipeline cache from {}", data.len(), path.display());
+                Some(data)
+            }
+            Err(reason) => {
+                warn!("Discarding the pipeline cache at {}: {reason}", path.display());
+                let _ = fs::remove_file(path);
+                None
+            }
+        }
+    }
+
+    fn validate_header(data: &[u8], properties: &PhysicalDeviceProperties) -> Result<(), String> {
+        if data.len() < HEADER_SIZE {
+            return Err(format!("only {} bytes long", data.len()));
+        }
+        let read_u32 = |offset: usize| u32::from_ne_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]);
+        let header_size = read_u32(0) as usize;
+        if header_size < HEADER_SIZE || header_size > data.len() {
+            return Err(format!("invalid header size {header_size}"));
+        }
+        if read_u32(4) != HEADER_VERSION_ONE {
+            return Err(format!("unknown header version {}", read_u32(4)));
+        }
+        if (read_u32(8), read_u32(12)) != (properties.vendor_id, properties.device_id) {
+            return Err(format!(
+                "created for device {:04x}:{:04x}",
+                read_u32(8),
+                read_u32(12)
+            ));
+        }
+        if data[16..HEADER_SIZE] != properties.pipeline_cache_uuid {
+            return Err("pipeline cache UUID mismatch".to_owned());
+        }
+        Ok(())
+    }
+}
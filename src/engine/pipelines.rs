@@ -0,0 +1,265 @@
+use std::{ffi::CStr, io::Cursor};
+
+use anyhow::{anyhow, bail, Context, Error};
+use ash::{
+    util::read_spv,
+    vk::{
+        BlendFactor, BlendOp, ColorComponentFlags, CompareOp, ComputePipelineCreateInfo,
+        CullModeFlags, DescriptorSetLayout, DynamicState, Format, FrontFace,
+        GraphicsPipelineCreateInfo, Pipeline, PipelineCache,
+        PipelineColorBlendAttachmentState, PipelineColorBlendStateCreateInfo,
+        PipelineDepthStencilStateCreateInfo, PipelineDynamicStateCreateInfo,
+        PipelineInputAssemblyStateCreateInfo, PipelineLayout, PipelineLayoutCreateInfo,
+        PipelineMultisampleStateCreateInfo, PipelineRasterizationStateCreateInfo,
+        PipelineRenderingCreateInfo, PipelineShaderStageCreateInfo,
+        PipelineVertexInputStateCreateInfo, PipelineViewportStateCreateInfo, PolygonMode,
+        PrimitiveTopology, PushConstantRange, SampleCountFlags, ShaderModule,
+        ShaderModuleCreateInfo, ShaderStageFlags, VertexInputAttributeDescription,
+        VertexInputBindingDescription,
+    },
+    Device,
+};
+
+static SHADER_ENTRY_POINT: &CStr = c"main";
+
+/// Includes the SPIR-V `build.rs` compiled a shader in the `shaders` directory to, e.g.
+/// `include_shader!("ui.vert")`.
+macro_rules! include_shader {
+    ($name:literal) => {
+        include_bytes!(concat!(env!("OUT_DIR"), "/", $name, ".spv")).as_slice()
+    };
+}
+pub(crate) use include_shader;
+
+/// Creates a shader module from SPIR-V included with [`include_shader!`].
+pub fn load_shader_module(device: &Device, spirv: &[u8]) -> Result<ShaderModule, Error> {
+    if spirv.is_empty() {
+        bail!("Shader is empty, glslc was missing when the crate was built");
+    }
+    // `include_bytes!` doesn't align to words, `read_spv` copies into words.
+    let code = read_spv(&mut Cursor::new(spirv)).context("Failed to read SPIR-V")?;
+    let create_info = ShaderModuleCreateInfo::default().code(&code);
+    Ok(unsafe { device.create_shader_module(&create_info, None)? })
+}
+
+/// Creates a pipeline layout whose set 0 is the global scene data layout, followed by
+/// the pipeline specific `set_layouts` starting at set 1.
+pub fn create_pipeline_layout(
+    device: &Device,
+    global_set_layout: DescriptorSetLayout,
+    set_layouts: &[DescriptorSetLayout],
+    push_constant_ranges: &[PushConstantRange],
+) -> Result<PipelineLayout, Error> {
+    let layouts = std::iter::once(global_set_layout)
+        .chain(set_layouts.iter().copied())
+        .collect::<Vec<_>>();
+    let create_info = PipelineLayoutCreateInfo::default()
+        .set_layouts(&layouts)
+        .push_constant_ranges(push_constant_ranges);
+    Ok(unsafe { device.create_pipeline_layout(&create_info, None)? })
+}
+
+pub fn create_compute_pipeline(
+    device: &Device,
+    layout: PipelineLayout,
+    shader: ShaderModule,
+    pipeline_cache: PipelineCache,
+) -> Result<Pipeline, Error> {
+    let stage = PipelineShaderStageCreateInfo::default()
+        .stage(ShaderStageFlags::COMPUTE)
+        .module(shader)
+        .name(SHADER_ENTRY_POINT);
+    let create_info = ComputePipelineCreateInfo::default().stage(stage).layout(layout);
+    let pipelines = unsafe {
+        device
+            .create_compute_pipelines(pipeline_cache, &[create_info], None)
+            .map_err(|(_, err)| anyhow!("Failed to create compute pipeline: {err}"))?
+    };
+    Ok(pipelines[0])
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    Opaque,
+    /// Straight alpha, `src * a + dst * (1 - a)`.
+    Alpha,
+    /// Colors are already multiplied by alpha, `src + dst * (1 - a)`.
+    PremultipliedAlpha,
+}
+
+/// Builds triangle list pipelines for dynamic rendering. Viewport and scissor are always dynamic.
+pub struct GraphicsPipelineBuilder {
+    shader_stages: Vec<(ShaderStageFlags, ShaderModule)>,
+    vertex_bindings: Vec<VertexInputBindingDescription>,
+    vertex_attributes: Vec<VertexInputAttributeDescription>,
+    cull_mode: CullModeFlags,
+    front_face: FrontFace,
+    samples: SampleCountFlags,
+    blend_mode: BlendMode,
+    depth_test: Option<(bool, CompareOp)>,
+    color_formats: Vec<Format>,
+    depth_format: Format,
+}
+
+impl Default for GraphicsPipelineBuilder {
+    fn default() -> Self {
+        Self {
+            shader_stages: Vec::new(),
+            vertex_bindings: Vec::new(),
+            vertex_attributes: Vec::new(),
+            cull_mode: CullModeFlags::NONE,
+            front_face: FrontFace::COUNTER_CLOCKWISE,
+            samples: SampleCountFlags::TYPE_1,
+            blend_mode: BlendMode::Opaque,
+            depth_test: None,
+            color_formats: Vec::new(),
+            depth_format: Format::UNDEFINED,
+        }
+    }
+}
+
+impl GraphicsPipelineBuilder {
+    pub fn shader(mut self, stage: ShaderStageFlags, module: ShaderModule) -> Self {
+        self.shader_stages.push((stage, module));
+        self
+    }
+
+    pub fn vertex_input(
+        mut self,
+        bindings: &[VertexInputBindingDescription],
+        attributes: &[VertexInputAttributeDescription],
+    ) -> Self {
+        self.vertex_bindings = bindings.to_vec();
+        self.vertex_attributes = attributes.to_vec();
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: CullModeFlags, front_face: FrontFace) -> Self {
+        self.cull_mode = cull_mode;
+        self.front_face = front_face;
+        self
+    }
+
+    pub fn samples(mut self, samples: SampleCountFlags) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    pub fn depth_test(mut self, write: bool, compare_op: CompareOp) -> Self {
+        self.depth_test = Some((write, compare_op));
+        self
+    }
+
+    pub fn color_format(mut self, format: Format) -> Self {
+        self.color_formats = vec![format];
+        self
+    }
+
+    pub fn depth_format(mut self, format: Format) -> Self {
+        self.depth_format = format;
+        self
+    }
+
+    pub fn build(
+        self,
+        device: &Device,
+        layout: PipelineLayout,
+        pipeline_cache: PipelineCache,
+    ) -> Result<Pipeline, Error> {
+        let shader_stages = self
+            .shader_stages
+            .iter()
+            .map(|(stage, module)| {
+                PipelineShaderStageCreateInfo::default()
+                    .stage(*stage)
+                    .module(*module)
+                    .name(SHADER_ENTRY_POINT)
+            })
+            .collect::<Vec<_>>();
+        let vertex_input_state = PipelineVertexInputStateCreateInfo::default()
+            .vertex_binding_descriptions(&self.vertex_bindings)
+            .vertex_attribute_descriptions(&self.vertex_attributes);
+        let input_assembly_state =
+            PipelineInputAssemblyStateCreateInfo::default().topology(PrimitiveTopology::TRIANGLE_LIST);
+        let viewport_state = PipelineViewportStateCreateInfo::default()
+            .viewport_count(1)
+            .scissor_count(1);
+        let rasterization_state = PipelineRasterizationStateCreateInfo::default()
+            .polygon_mode(PolygonMode::FILL)
+            .cull_mode(self.cull_mode)
+            .front_face(self.front_face)
+            .line_width(1.0);
+        let multisample_state = PipelineMultisampleStateCreateInfo::default()
+            .rasterization_samples(self.samples)
+            .min_sample_shading(1.0);
+
+        let blend_attachment = match self.blend_mode {
+            BlendMode::Opaque => PipelineColorBlendAttachmentState::default(),
+            BlendMode::Alpha => PipelineColorBlendAttachmentState::default()
+                .blend_enable(true)
+                .src_color_blend_factor(BlendFactor::SRC_ALPHA)
+                .dst_color_blend_factor(BlendFactor::ONE_MINUS_SRC_ALPHA)
+                .color_blend_op(BlendOp::ADD)
+                .src_alpha_blend_factor(BlendFactor::ONE)
+                .dst_alpha_blend_factor(BlendFactor::ONE_MINUS_SRC_ALPHA)
+                .alpha_blend_op(BlendOp::ADD),
+            BlendMode::PremultipliedAlpha => PipelineColorBlendAttachmentState::default()
+                .blend_enable(true)
+                .src_color_blend_factor(BlendFactor::ONE)
+                .dst_color_blend_factor(BlendFactor::ONE_MINUS_SRC_ALPHA)
+                .color_blend_op(BlendOp::ADD)
+                .src_alpha_blend_factor(BlendFactor::ONE_MINUS_DST_ALPHA)
+                .dst_alpha_blend_factor(BlendFactor::ONE)
+                .alpha_blend_op(BlendOp::ADD),
+        }
+        .color_write_mask(ColorComponentFlags::RGBA);
+        let blend_attachments = vec![blend_attachment; self.color_formats.len()];
+        let color_blend_state =
+            PipelineColorBlendStateCreateInfo::default().attachments(&blend_attachments);
+
+        let depth_stencil_state = match self.depth_test {
+            Some((write, compare_op)) => PipelineDepthStencilStateCreateInfo::default()
+                .depth_test_enable(true)
+                .depth_write_enable(write)
+                .depth_compare_op(compare_op)
+                .min_depth_bounds(0.0)
+                .max_depth_bounds(1.0),
+            None => PipelineDepthStencilStateCreateInfo::default()
+                .depth_compare_op(CompareOp::NEVER)
+                .min_depth_bounds(0.0)
+                .max_depth_bounds(1.0),
+        };
+
+        let dynamic_states = [DynamicState::VIEWPORT, DynamicState::SCISSOR];
+        let dynamic_state = PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+
+        let mut rendering_info = PipelineRenderingCreateInfo::default()
+            .color_attachment_formats(&self.color_formats)
+            .depth_attachment_format(self.depth_format);
+
+        let create_info = GraphicsPipelineCreateInfo::default()
+            .stages(&shader_stages)
+            .vertex_input_state(&vertex_input_state)
+            .input_assembly_state(&input_assembly_state)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .color_blend_state(&color_blend_state)
+            .depth_stencil_state(&depth_stencil_state)
+            .dynamic_state(&dynamic_state)
+            .layout(layout)
+            .push_next(&mut rendering_info);
+
+        let pipelines = unsafe {
+            device
+                .create_graphics_pipelines(pipeline_cache, &[create_info], None)
+                .map_err(|(_, err)| anyhow!("Failed to create graphics pipeline: {err}"))?
+        };
+        Ok(pipelines[0])
+    }
+}
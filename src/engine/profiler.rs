@@ -0,0 +1,370 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    io,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use ash::{
+    vk::{
+        self, PhysicalDeviceLimits, PipelineStageFlags2, QueryPool, QueryPoolCreateInfo,
+        QueryResultFlags, QueryType,
+    },
+    Device,
+};
+
+use super::{
+    command_encoder::CommandEncoder,
+    debug_names::DebugNames,
+    errors::engine_error::{EngineError, VkResultExt},
+};
+
+/// Passes per frame that get timestamps, later ones are not measured.
+pub const MAX_PROFILED_PASSES: usize = 32;
+/// Samples the rolling statistics are computed over.
+const HISTORY_LENGTH: usize = 120;
+/// Frames kept for [`GpuProfiler::chrome_trace`].
+const MAX_TRACE_FRAMES: usize = 600;
+
+/// Rolling statistics of one measurement, all in milliseconds.
+#[derive(Debug, Clone, Default)]
+pub struct TimingStats {
+    pub last_ms: f32,
+    pub average_ms: f32,
+    pub min_ms: f32,
+    pub max_ms: f32,
+    history: VecDeque<f32>,
+}
+
+impl TimingStats {
+    fn push(&mut self, milliseconds: f32) {
+        if self.history.len() == HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back(milliseconds);
+        self.last_ms = milliseconds;
+        self.average_ms = self.history.iter().sum::<f32>() / self.history.len() as f32;
+        self.min_ms = self.history.iter().copied().fold(f32::INFINITY, f32::min);
+        self.max_ms = self.history.iter().copied().fold(0.0, f32::max);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PassTiming {
+    pub name: &'static str,
+    pub gpu: TimingStats,
+}
+
+/// One finished frame for the trace, times in microseconds since the profiler started.
+struct TraceFrame {
+    frame_number: u64,
+    cpu_start_us: f64,
+    record_duration_us: f64,
+    /// Start relative to the frame's first timestamp and duration of every pass.
+    gpu_passes: Vec<(&'static str, f64, f64)>,
+    gpu_duration_us: f64,
+}
+
+/// Timestamp queries of one frame slot. Query 0 and 1 enclose the whole command buffer,
+/// pass `n` writes `2 + 2n` and `3 + 2n`.
+struct FrameQueries {
+    query_pool: QueryPool,
+    passes: Vec<&'static str>,
+    open_passes: Vec<usize>,
+    frame_number: u64,
+    cpu_start: Instant,
+    record_duration: Duration,
+    recorded: bool,
+}
+
+/// Measures the GPU time of every pass with timestamp queries and keeps rolling
+/// statistics of them and of the CPU frame time. Results are read back once the frame
+/// slot comes around again, after its fence was waited on.
+pub struct GpuProfiler {
+    frames: Vec<FrameQueries>,
+    gpu_supported: bool,
+    /// Nanoseconds per timestamp tick.
+    timestamp_period: f32,
+    timestamp_mask: u64,
+    epoch: Instant,
+    last_frame_start: Option<Instant>,
+    cpu_frame: TimingStats,
+    cpu_record: TimingStats,
+    gpu_frame: TimingStats,
+    passes: Vec<PassTiming>,
+    trace: VecDeque<TraceFrame>,
+}
+
+impl GpuProfiler {
+    /// Without timestamp support on the graphics queue (`timestamp_valid_bits` of 0)
+    /// only the CPU side is measured.
+    pub fn new(
+        device: &Device,
+        limits: &PhysicalDeviceLimits,
+        timestamp_valid_bits: u32,
+        frames_in_flight: usize,
+    ) -> Result<GpuProfiler, EngineError> {
+        let gpu_supported = timestamp_valid_bits > 0 && limits.timestamp_period > 0.0;
+        Ok(GpuProfiler {
+            frames: Self::create_frames(device, gpu_supported, frames_in_flight)?,
+            gpu_supported,
+            timestamp_period: limits.timestamp_period,
+            timestamp_mask: match timestamp_valid_bits {
+                64.. => u64::MAX,
+                bits => (1 << bits) - 1,
+            },
+            epoch: Instant::now(),
+            last_frame_start: None,
+            cpu_frame: TimingStats::default(),
+            cpu_record: TimingStats::default(),
+            gpu_frame: TimingStats::default(),
+            passes: Vec::new(),
+            trace: VecDeque::with_capacity(MAX_TRACE_FRAMES),
+        })
+    }
+
+    pub fn is_gpu_supported(&self) -> bool {
+        self.gpu_supported
+    }
+
+    /// Recreates the query pools of every slot, keeping the statistics. The device has to
+    /// be idle, results not read back yet are lost.
+    pub fn set_frames_in_flight(&mut self, device: &Device, frames_in_flight: usize) -> Result<(), EngineError> {
+        self.destroy(device);
+        self.frames = Self::create_frames(device, self.gpu_supported, frames_in_flight)?;
+        Ok(())
+    }
+
+    /// Reads back the timestamps of the frame previously recorded in this slot. Must be
+    /// called once the slot's fence has been waited on.
+    pub fn begin_frame(&mut self, device: &Device, frame_index: usize, frame_number: u64) -> Result<(), EngineError> {
+        let now = Instant::now();
+        if let Some(last_frame_start) = self.last_frame_start.replace(now) {
+            self.cpu_frame.push(milliseconds(now - last_frame_start));
+        }
+        if !self.is_gpu_supported() {
+            return Ok(());
+        }
+        if self.frames[frame_index].recorded {
+            self.collect(device, frame_index)?;
+        }
+        let frame = &mut self.frames[frame_index];
+        frame.passes.clear();
+        frame.open_passes.clear();
+        frame.frame_number = frame_number;
+        frame.cpu_start = now;
+        frame.recorded = false;
+        Ok(())
+    }
+
+    /// Resets the slot's queries and writes the frame start timestamp, at the very start of
+    /// the command buffer.
+    pub fn begin_commands(&mut self, encoder: &mut CommandEncoder, frame_index: usize) {
+        let Some(frame) = self.frames.get(frame_index) else {
+            return;
+        };
+        encoder.reset_query_pool(frame.query_pool, 0, Self::query_count(MAX_PROFILED_PASSES));
+        encoder.write_timestamp(PipelineStageFlags2::TOP_OF_PIPE, frame.query_pool, 0);
+    }
+
+    pub fn begin_pass(&mut self, encoder: &mut CommandEncoder, frame_index: usize, name: &'static str) {
+        let Some(frame) = self.frames.get_mut(frame_index) else {
+            return;
+        };
+        let pass_index = frame.passes.len();
+        frame.passes.push(name);
+        frame.open_passes.push(pass_index);
+        if pass_index < MAX_PROFILED_PASSES {
+            encoder.write_timestamp(
+                PipelineStageFlags2::TOP_OF_PIPE,
+                frame.query_pool,
+                2 + 2 * pass_index as u32,
+            );
+        }
+    }
+
+    pub fn end_pass(&mut self, encoder: &mut CommandEncoder, frame_index: usize) {
+        let Some(frame) = self.frames.get_mut(frame_index) else {
+            return;
+        };
+        let Some(pass_index) = frame.open_passes.pop() else {
+            return;
+        };
+        if pass_index < MAX_PROFILED_PASSES {
+            encoder.write_timestamp(
+                PipelineStageFlags2::BOTTOM_OF_PIPE,
+                frame.query_pool,
+                3 + 2 * pass_index as u32,
+            );
+        }
+    }
+
+    /// Writes the frame end timestamp, right before the command buffer is ended.
+    pub fn end_commands(&mut self, encoder: &mut CommandEncoder, frame_index: usize) {
+        let record_duration = self
+            .last_frame_start
+            .map(|start| start.elapsed())
+            .unwrap_or_default();
+        self.cpu_record.push(milliseconds(record_duration));
+        let Some(frame) = self.frames.get_mut(frame_index) else {
+            return;
+        };
+        encoder.write_timestamp(PipelineStageFlags2::BOTTOM_OF_PIPE, frame.query_pool, 1);
+        frame.record_duration = record_duration;
+        frame.recorded = true;
+    }
+
+    /// Time between the starts of consecutive frames.
+    pub fn cpu_frame(&self) -> &TimingStats {
+        &self.cpu_frame
+    }
+
+    /// Time spent recording a frame until its command buffer is ended.
+    pub fn cpu_record(&self) -> &TimingStats {
+        &self.cpu_record
+    }
+
+    /// GPU time from the start to the end of a frame's command buffer.
+    pub fn gpu_frame(&self) -> &TimingStats {
+        &self.gpu_frame
+    }
+
+    /// Passes in the order they were first seen.
+    pub fn passes(&self) -> &[PassTiming] {
+        &self.passes
+    }
+
+    /// The recent frames in the Chrome trace event format, viewable in `chrome://tracing`
+    /// or Perfetto. GPU passes are placed on their own track, starting at the CPU time
+    /// their frame was recorded since the two clocks aren't related.
+    pub fn chrome_trace(&self) -> String {
+        let mut events = Vec::new();
+        for frame in self.trace.iter() {
+            events.push(trace_event("record", "cpu", 1, frame.cpu_start_us, frame.record_duration_us, frame.frame_number));
+            events.push(trace_event("gpu frame", "gpu", 2, frame.cpu_start_us, frame.gpu_duration_us, frame.frame_number));
+            for (name, start_us, duration_us) in frame.gpu_passes.iter() {
+                events.push(trace_event(name, "gpu", 2, frame.cpu_start_us + start_us, *duration_us, frame.frame_number));
+            }
+        }
+        let mut trace = String::from("{\"traceEvents\":[\n");
+        trace += &events.join(",\n");
+        trace += "\n],\"displayTimeUnit\":\"ms\"}\n";
+        trace
+    }
+
+    pub fn write_chrome_trace(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.chrome_trace())
+    }
+
+    pub fn name_objects(&self, names: &DebugNames) {
+        for (frame_index, frame) in self.frames.iter().enumerate() {
+            names.name(frame.query_pool, &format!("frame {frame_index} timestamp query pool"));
+        }
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        for frame in self.frames.iter() {
+            unsafe { device.destroy_query_pool(frame.query_pool, None) };
+        }
+    }
+
+    fn collect(&mut self, device: &Device, frame_index: usize) -> Result<(), EngineError> {
+        let frame = &self.frames[frame_index];
+        let measured_passes = frame.passes.len().min(MAX_PROFILED_PASSES);
+        let mut timestamps = vec![0u64; Self::query_count(measured_passes) as usize];
+        match unsafe {
+            device.get_query_pool_results(frame.query_pool, 0, &mut timestamps, QueryResultFlags::TYPE_64)
+        } {
+            // The frame was recorded but never submitted, e.g. because recording failed.
+            Err(vk::Result::NOT_READY) => return Ok(()),
+            result => result.vk_object_context("vkGetQueryPoolResults", format!("frame {frame_index} timestamps"))?,
+        }
+
+        let to_us = |start: u64, end: u64| {
+            let ticks = end.wrapping_sub(start) & self.timestamp_mask;
+            ticks as f64 * self.timestamp_period as f64 / 1000.0
+        };
+        let frame_start = timestamps[0];
+        let gpu_duration_us = to_us(frame_start, timestamps[1]);
+        let gpu_passes = frame.passes[..measured_passes]
+            .iter()
+            .enumerate()
+            .map(|(pass_index, name)| {
+                let start = timestamps[2 + 2 * pass_index];
+                let end = timestamps[3 + 2 * pass_index];
+                (*name, to_us(frame_start, start), to_us(start, end))
+            })
+            .collect::<Vec<_>>();
+
+        self.gpu_frame.push((gpu_duration_us / 1000.0) as f32);
+        for (name, _, duration_us) in gpu_passes.iter() {
+            let position = match self.passes.iter().position(|pass| pass.name == *name) {
+                Some(position) => position,
+                None => {
+                    self.passes.push(PassTiming {
+                        name,
+                        gpu: TimingStats::default(),
+                    });
+                    self.passes.len() - 1
+                }
+            };
+            self.passes[position].gpu.push((duration_us / 1000.0) as f32);
+        }
+
+        if self.trace.len() == MAX_TRACE_FRAMES {
+            self.trace.pop_front();
+        }
+        self.trace.push_back(TraceFrame {
+            frame_number: frame.frame_number,
+            cpu_start_us: (frame.cpu_start - self.epoch).as_secs_f64() * 1_000_000.0,
+            record_duration_us: frame.record_duration.as_secs_f64() * 1_000_000.0,
+            gpu_passes,
+            gpu_duration_us,
+        });
+        Ok(())
+    }
+
+    fn create_frames(
+        device: &Device,
+        gpu_supported: bool,
+        frames_in_flight: usize,
+    ) -> Result<Vec<FrameQueries>, EngineError> {
+        if !gpu_supported {
+            return Ok(Vec::new());
+        }
+        (0..frames_in_flight)
+            .map(|_| {
+                let create_info = QueryPoolCreateInfo::default()
+                    .query_type(QueryType::TIMESTAMP)
+                    .query_count(Self::query_count(MAX_PROFILED_PASSES));
+                let query_pool = unsafe { device.create_query_pool(&create_info, None) }
+                    .vk_context("vkCreateQueryPool")?;
+                Ok(FrameQueries {
+                    query_pool,
+                    passes: Vec::new(),
+                    open_passes: Vec::new(),
+                    frame_number: 0,
+                    cpu_start: Instant::now(),
+                    record_duration: Duration::ZERO,
+                    recorded: false,
+                })
+            })
+            .collect()
+    }
+
+    fn query_count(passes: usize) -> u32 {
+        2 + 2 * passes as u32
+    }
+}
+
+fn milliseconds(duration: Duration) -> f32 {
+    duration.as_secs_f32() * 1000.0
+}
+
+fn trace_event(name: &str, category: &str, thread: u32, start_us: f64, duration_us: f64, frame_number: u64) -> String {
+    format!(
+        "{{\"name\":\"{}\",\"cat\":\"{category}\",\"ph\":\"X\",\"pid\":1,\"tid\":{thread},\"ts\":{start_us:.3},\"dur\":{duration_us:.3},\"args\":{{\"frame\":{frame_number}}}}}",
+        name.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
@@ -0,0 +1,293 @@
+use ash::{
+    vk::{
+        self, QueryControlFlags, QueryPipelineStatisticFlags, QueryPool,
+        QueryPoolCreateInfo, QueryResultFlags, QueryType,
+    },
+    Device,
+};
+
+use super::{
+    command_encoder::CommandEncoder,
+    debug_names::DebugNames,
+    errors::engine_error::{EngineError, VkResultExt},
+    features::{DeviceRequirements, EnabledFeatures, Feature},
+};
+
+/// Queries of each kind a frame can use, later requests return `None`.
+pub const MAX_QUERIES_PER_FRAME: u32 = 64;
+
+/// The statistics collected by every pipeline statistics query, in the order the values
+/// are written (lowest flag bit first).
+const PIPELINE_STATISTICS: QueryPipelineStatisticFlags = QueryPipelineStatisticFlags::from_raw(
+    QueryPipelineStatisticFlags::INPUT_ASSEMBLY_VERTICES.as_raw()
+        | QueryPipelineStatisticFlags::INPUT_ASSEMBLY_PRIMITIVES.as_raw()
+        | QueryPipelineStatisticFlags::VERTEX_SHADER_INVOCATIONS.as_raw()
+        | QueryPipelineStatisticFlags::CLIPPING_INVOCATIONS.as_raw()
+        | QueryPipelineStatisticFlags::CLIPPING_PRIMITIVES.as_raw()
+        | QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS.as_raw()
+        | QueryPipelineStatisticFlags::COMPUTE_SHADER_INVOCATIONS.as_raw(),
+);
+const PIPELINE_STATISTICS_COUNT: usize = 7;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PipelineStatistics {
+    pub input_assembly_vertices: u64,
+    pub input_assembly_primitives: u64,
+    pub vertex_shader_invocations: u64,
+    pub clipping_invocations: u64,
+    pub clipping_primitives: u64,
+    pub fragment_shader_invocations: u64,
+    pub compute_shader_invocations: u64,
+}
+
+impl PipelineStatistics {
+    fn from_values(values: &[u64]) -> PipelineStatistics {
+        PipelineStatistics {
+            input_assembly_vertices: values[0],
+            input_assembly_primitives: values[1],
+            vertex_shader_invocations: values[2],
+            clipping_invocations: values[3],
+            clipping_primitives: values[4],
+            fragment_shader_invocations: values[5],
+            compute_shader_invocations: values[6],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QueryKind {
+    PipelineStatistics,
+    Occlusion,
+}
+
+/// A query begun with [`FrameQueries::begin_statistics`] or [`FrameQueries::begin_occlusion`],
+/// to be passed to [`FrameQueries::end`].
+#[derive(Debug, Clone, Copy)]
+pub struct QueryId {
+    kind: QueryKind,
+    index: u32,
+}
+
+/// Results of the queries a frame slot recorded last time it was used. Queries whose
+/// results weren't available yet are missing.
+#[derive(Debug, Clone, Default)]
+pub struct QueryResults {
+    pub frame_number: u64,
+    pub statistics: Vec<(&'static str, PipelineStatistics)>,
+    /// Samples that passed the depth and stencil tests. Without `occlusionQueryPrecise`
+    /// only zero and non-zero are meaningful.
+    pub occlusion: Vec<(&'static str, u64)>,
+}
+
+/// Query pools of one frame in flight for pipeline statistics and occlusion queries.
+/// Results are read without waiting once the frame slot comes around again.
+pub struct FrameQueries {
+    /// `None` if the device doesn't support `pipelineStatisticsQuery`.
+    statistics_pool: Option<QueryPool>,
+    occlusion_pool: QueryPool,
+    statistics_names: Vec<&'static str>,
+    occlusion_names: Vec<&'static str>,
+    precise_occlusion: bool,
+    inherited_queries: bool,
+    frame_number: u64,
+    recorded: bool,
+}
+
+impl FrameQueries {
+    /// The features are only requested, queries that need a missing one are skipped.
+    pub fn device_requirements() -> DeviceRequirements {
+        DeviceRequirements::default()
+            .optional_feature(Feature::PipelineStatisticsQuery)
+            .optional_feature(Feature::OcclusionQueryPrecise)
+            .optional_feature(Feature::InheritedQueries)
+    }
+
+    pub fn new(device: &Device, enabled_features: &EnabledFeatures) -> Result<FrameQueries, EngineError> {
+        let statistics_pool = match enabled_features.is_enabled(Feature::PipelineStatisticsQuery) {
+            true => Some(create_query_pool(
+                device,
+                QueryPoolCreateInfo::default()
+                    .query_type(QueryType::PIPELINE_STATISTICS)
+                    .pipeline_statistics(PIPELINE_STATISTICS),
+            )?),
+            false => None,
+        };
+        let occlusion_pool = create_query_pool(device, QueryPoolCreateInfo::default().query_type(QueryType::OCCLUSION))?;
+        Ok(FrameQueries {
+            statistics_pool,
+            occlusion_pool,
+            statistics_names: Vec::new(),
+            occlusion_names: Vec::new(),
+            precise_occlusion: enabled_features.is_enabled(Feature::OcclusionQueryPrecise),
+            inherited_queries: enabled_features.is_enabled(Feature::InheritedQueries),
+            frame_number: 0,
+            recorded: false,
+        })
+    }
+
+    /// Whether queries may be active while secondary command buffers are executed.
+    pub fn supports_inherited_queries(&self) -> bool {
+        self.inherited_queries
+    }
+
+    /// The statistics secondary command buffers executed inside `query` have to inherit.
+    pub fn inherited_statistics(&self, query: Option<QueryId>) -> QueryPipelineStatisticFlags {
+        match query {
+            Some(QueryId {
+                kind: QueryKind::PipelineStatistics,
+                ..
+            }) => PIPELINE_STATISTICS,
+            _ => QueryPipelineStatisticFlags::empty(),
+        }
+    }
+
+    /// The flags secondary command buffers executed inside `query` have to inherit, `None`
+    /// unless it is an occlusion query.
+    pub fn inherited_occlusion(&self, query: Option<QueryId>) -> Option<QueryControlFlags> {
+        match query {
+            Some(QueryId {
+                kind: QueryKind::Occlusion,
+                ..
+            }) => Some(self.occlusion_flags()),
+            _ => None,
+        }
+    }
+
+    /// Reads what is available of the previous use of this slot, then resets the pools at
+    /// the start of `command_buffer`. Must be called after the slot's fence was waited on.
+    pub fn begin_frame(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        frame_number: u64,
+    ) -> Result<Option<QueryResults>, EngineError> {
+        let results = match self.recorded {
+            true => Some(self.read_results(device)?),
+            false => None,
+        };
+        self.statistics_names.clear();
+        self.occlusion_names.clear();
+        self.frame_number = frame_number;
+        self.recorded = true;
+        if let Some(statistics_pool) = self.statistics_pool {
+            encoder.reset_query_pool(statistics_pool, 0, MAX_QUERIES_PER_FRAME);
+        }
+        encoder.reset_query_pool(self.occlusion_pool, 0, MAX_QUERIES_PER_FRAME);
+        Ok(results)
+    }
+
+    /// `None` without `pipelineStatisticsQuery` or once the frame ran out of queries.
+    pub fn begin_statistics(&mut self, encoder: &mut CommandEncoder, name: &'static str) -> Option<QueryId> {
+        let statistics_pool = self.statistics_pool?;
+        let index = Self::next_index(&mut self.statistics_names, name)?;
+        encoder.begin_query(statistics_pool, index, QueryControlFlags::empty());
+        Some(QueryId {
+            kind: QueryKind::PipelineStatistics,
+            index,
+        })
+    }
+
+    /// Has to be ended in the same render pass instance it was begun in, if any.
+    pub fn begin_occlusion(&mut self, encoder: &mut CommandEncoder, name: &'static str) -> Option<QueryId> {
+        let index = Self::next_index(&mut self.occlusion_names, name)?;
+        encoder.begin_query(self.occlusion_pool, index, self.occlusion_flags());
+        Some(QueryId {
+            kind: QueryKind::Occlusion,
+            index,
+        })
+    }
+
+    pub fn end(&self, encoder: &mut CommandEncoder, query: QueryId) {
+        let query_pool = match query.kind {
+            QueryKind::PipelineStatistics => match self.statistics_pool {
+                Some(statistics_pool) => statistics_pool,
+                None => return,
+            },
+            QueryKind::Occlusion => self.occlusion_pool,
+        };
+        encoder.end_query(query_pool, query.index);
+    }
+
+    pub fn name_objects(&self, names: &DebugNames, frame_index: usize) {
+        if let Some(statistics_pool) = self.statistics_pool {
+            names.name(statistics_pool, &format!("frame {frame_index} pipeline statistics query pool"));
+        }
+        names.name(self.occlusion_pool, &format!("frame {frame_index} occlusion query pool"));
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        unsafe {
+            if let Some(statistics_pool) = self.statistics_pool {
+                device.destroy_query_pool(statistics_pool, None);
+            }
+            device.destroy_query_pool(self.occlusion_pool, None);
+        }
+    }
+
+    fn occlusion_flags(&self) -> QueryControlFlags {
+        match self.precise_occlusion {
+            true => QueryControlFlags::PRECISE,
+            false => QueryControlFlags::empty(),
+        }
+    }
+
+    fn next_index(names: &mut Vec<&'static str>, name: &'static str) -> Option<u32> {
+        let index = names.len() as u32;
+        if index >= MAX_QUERIES_PER_FRAME {
+            return None;
+        }
+        names.push(name);
+        Some(index)
+    }
+
+    fn read_results(&self, device: &Device) -> Result<QueryResults, EngineError> {
+        let mut results = QueryResults {
+            frame_number: self.frame_number,
+            ..Default::default()
+        };
+        if let (Some(statistics_pool), false) = (self.statistics_pool, self.statistics_names.is_empty()) {
+            // The values followed by the availability of each query.
+            let mut values = vec![[0u64; PIPELINE_STATISTICS_COUNT + 1]; self.statistics_names.len()];
+            get_available_results(device, statistics_pool, &mut values)?;
+            results.statistics = self
+                .statistics_names
+                .iter()
+                .zip(values.iter())
+                .filter(|(_, values)| values[PIPELINE_STATISTICS_COUNT] != 0)
+                .map(|(name, values)| (*name, PipelineStatistics::from_values(values)))
+                .collect();
+        }
+        if !self.occlusion_names.is_empty() {
+            let mut values = vec![[0u64; 2]; self.occlusion_names.len()];
+            get_available_results(device, self.occlusion_pool, &mut values)?;
+            results.occlusion = self
+                .occlusion_names
+                .iter()
+                .zip(values.iter())
+                .filter(|(_, [_, available])| *available != 0)
+                .map(|(name, [samples, _])| (*name, *samples))
+                .collect();
+        }
+        Ok(results)
+    }
+}
+
+fn create_query_pool(device: &Device, create_info: QueryPoolCreateInfo) -> Result<QueryPool, EngineError> {
+    let create_info = create_info.query_count(MAX_QUERIES_PER_FRAME);
+    unsafe { device.create_query_pool(&create_info, None) }.vk_context("vkCreateQueryPool")
+}
+
+/// Never waits, unavailable results are marked by a zero in the last value of their entry.
+fn get_available_results<T>(device: &Device, query_pool: QueryPool, data: &mut [T]) -> Result<(), EngineError> {
+    match unsafe {
+        device.get_query_pool_results(
+            query_pool,
+            0,
+            data,
+            QueryResultFlags::TYPE_64 | QueryResultFlags::WITH_AVAILABILITY,
+        )
+    } {
+        Err(vk::Result::NOT_READY) => Ok(()),
+        result => result.vk_context("vkGetQueryPoolResults"),
+    }
+}
@@ -1,19 +1,35 @@
 use ash::{
-    vk::{PhysicalDevice, QueueFlags, SurfaceKHR},
-    Instance,
+    vk::{PhysicalDevice, Queue, QueueFamilyProperties, QueueFlags, SurfaceKHR},
+    Device, Instance,
 };
 use thiserror::Error;
 
 #[derive(Debug, Clone, Copy)]
 pub struct QueueIndices {
-    pub graphics_queue_index: Option<u32>,
+    pub graphics_queue_index: u32,
     pub presentation_queue_index: Option<u32>,
+    /// Family with TRANSFER but without GRAPHICS, usually backed by a DMA engine.
+    pub transfer_queue_index: Option<u32>,
+    /// Family with COMPUTE but without GRAPHICS, for async compute.
+    pub compute_queue_index: Option<u32>,
 }
 
 #[derive(Error, Debug)]
 pub enum QueueFamilyIndicesError {
     #[error("No families found")]
     NotFoundError,
+    #[error("No queue family can present to the surface")]
+    NoPresentationFamily,
+}
+
+/// One queue of every family in [`QueueIndices`]. Families without a dedicated queue
+/// share the graphics queue.
+#[derive(Debug, Clone, Copy)]
+pub struct Queues {
+    pub graphics: Queue,
+    pub presentation: Queue,
+    pub transfer: Queue,
+    pub compute: Queue,
 }
 
 impl QueueIndices {
@@ -27,7 +43,7 @@ impl QueueIndices {
         let q_family_properties =
             unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
 
-        let (queue_idx, queue_fam) = match q_family_properties
+        let (queue_idx, _) = match q_family_properties
             .iter()
             .enumerate()
             .find(|&q_family| q_family.1.queue_flags.contains(queue_type))
@@ -36,23 +52,95 @@ impl QueueIndices {
             None => return Err(QueueFamilyIndicesError::NotFoundError),
         };
 
-        let surface_support = unsafe {
+        let supports_present = |family_index: u32| unsafe {
             surface_instance
-                .get_physical_device_surface_support(physical_device, queue_idx as u32, *surface)
-                .unwrap()
-        };
-        let presentation_queue_index = match surface_support {
-            true => Some(queue_idx as u32),
-            false => None,
+                .get_physical_device_surface_support(physical_device, family_index, *surface)
+                .unwrap_or(false)
         };
+        // Presenting from the graphics family avoids sharing the swapchain images.
+        let presentation_queue_index = std::iter::once(queue_idx as u32)
+            .chain(0..q_family_properties.len() as u32)
+            .find(|family_index| supports_present(*family_index));
 
         Ok(QueueIndices {
-            graphics_queue_index: Some(queue_idx as u32),
+            graphics_queue_index: queue_idx as u32,
             presentation_queue_index,
+            transfer_queue_index: find_dedicated_family(
+                &q_family_properties,
+                QueueFlags::TRANSFER,
+                &[QueueFlags::GRAPHICS | QueueFlags::COMPUTE, QueueFlags::GRAPHICS],
+            ),
+            compute_queue_index: find_dedicated_family(
+                &q_family_properties,
+                QueueFlags::COMPUTE,
+                &[QueueFlags::GRAPHICS],
+            ),
         })
     }
 
     pub fn is_complete(self) -> bool {
-        self.graphics_queue_index.is_some() && self.presentation_queue_index.is_some()
+        self.presentation_queue_index.is_some()
     }
+
+    /// Family used for uploads, the graphics family if there is no dedicated one.
+    pub fn transfer_family(self) -> u32 {
+        self.transfer_queue_index
+            .unwrap_or(self.graphics_queue_index)
+    }
+
+    /// Family used for async compute, the graphics family if there is no dedicated one.
+    pub fn compute_family(self) -> u32 {
+        self.compute_queue_index
+            .unwrap_or(self.graphics_queue_index)
+    }
+
+    /// Every distinct family a queue has to be created for.
+    pub fn unique_families(self) -> Vec<u32> {
+        let mut families = [
+            Some(self.graphics_queue_index),
+            self.presentation_queue_index,
+            self.transfer_queue_index,
+            self.compute_queue_index,
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        families.sort_unstable();
+        families.dedup();
+        families
+    }
+}
+
+impl Queues {
+    pub fn new(device: &Device, queue_indices: QueueIndices) -> Result<Queues, QueueFamilyIndicesError> {
+        let presentation_family = queue_indices
+            .presentation_queue_index
+            .ok_or(QueueFamilyIndicesError::NoPresentationFamily)?;
+        let get_queue = |family_index: u32| unsafe { device.get_device_queue(family_index, 0) };
+        Ok(Queues {
+            graphics: get_queue(queue_indices.graphics_queue_index),
+            presentation: get_queue(presentation_family),
+            transfer: get_queue(queue_indices.transfer_family()),
+            compute: get_queue(queue_indices.compute_family()),
+        })
+    }
+}
+
+/// Finds a family with `flags`, trying to exclude each set of `avoid` flags in order
+/// before settling for one that only excludes the later sets.
+fn find_dedicated_family(
+    families: &[QueueFamilyProperties],
+    flags: QueueFlags,
+    avoid: &[QueueFlags],
+) -> Option<u32> {
+    avoid.iter().find_map(|avoid| {
+        families
+            .iter()
+            .position(|family| {
+                family.queue_count > 0
+                    && family.queue_flags.contains(flags)
+                    && !family.queue_flags.intersects(*avoid)
+            })
+            .map(|index| index as u32)
+    })
 }
@@ -0,0 +1,193 @@
+use anyhow::Error;
+use ash::{
+    vk::{
+        AttachmentLoadOp, AttachmentStoreOp, ClearColorValue, ClearDepthStencilValue, ClearValue,
+        Extent2D, Format, ImageAspectFlags, ImageLayout, ImageUsageFlags, Offset2D,
+        PhysicalDeviceMemoryProperties, QueryControlFlags, QueryPipelineStatisticFlags, Rect2D,
+        RenderingAttachmentInfo, RenderingFlags, RenderingInfo, ResolveModeFlags,
+        SampleCountFlags,
+    },
+    Device,
+};
+
+use super::{
+    command_encoder::{CommandEncoder, RenderingScope},
+    config::MsaaSamples,
+    debug_names::DebugNames,
+    images::{create_allocated_image, AllocatedImage},
+    parallel_recording::RenderingInheritance,
+};
+
+pub static DRAW_IMAGE_FORMAT: Format = Format::R16G16B16A16_SFLOAT;
+pub static DEPTH_IMAGE_FORMAT: Format = Format::D32_SFLOAT;
+
+/// The images the scene is rendered into before it reaches the swapchain.
+///
+/// With MSAA enabled the scene is rendered into transient multisampled color and depth
+/// targets and the color target is resolved into `draw_image` at the end of the pass.
+/// Without MSAA the scene is rendered into `draw_image` directly.
+pub struct RenderTargets {
+    pub draw_image: AllocatedImage,
+    pub depth_image: AllocatedImage,
+    pub msaa_color_image: Option<AllocatedImage>,
+    pub samples: MsaaSamples,
+}
+
+impl RenderTargets {
+    pub fn new(
+        device: &Device,
+        memory_properties: &PhysicalDeviceMemoryProperties,
+        extent: Extent2D,
+        samples: MsaaSamples,
+    ) -> Result<RenderTargets, Error> {
+        let draw_image = create_allocated_image(
+            device,
+            memory_properties,
+            extent,
+            DRAW_IMAGE_FORMAT,
+            ImageUsageFlags::TRANSFER_SRC | ImageUsageFlags::STORAGE | ImageUsageFlags::COLOR_ATTACHMENT,
+            SampleCountFlags::TYPE_1,
+            ImageAspectFlags::COLOR,
+        )?;
+
+        let msaa_color_image = match samples.is_multisampled() {
+            true => Some(create_allocated_image(
+                device,
+                memory_properties,
+                extent,
+                DRAW_IMAGE_FORMAT,
+                ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::TRANSIENT_ATTACHMENT,
+                samples.sample_count_flags(),
+                ImageAspectFlags::COLOR,
+            )?),
+            false => None,
+        };
+
+        let depth_usage = match samples.is_multisampled() {
+            true => {
+                ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | ImageUsageFlags::TRANSIENT_ATTACHMENT
+            }
+            false => ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+        };
+        let depth_image = create_allocated_image(
+            device,
+            memory_properties,
+            extent,
+            DEPTH_IMAGE_FORMAT,
+            depth_usage,
+            samples.sample_count_flags(),
+            ImageAspectFlags::DEPTH,
+        )?;
+
+        Ok(RenderTargets {
+            draw_image,
+            depth_image,
+            msaa_color_image,
+            samples,
+        })
+    }
+
+    pub fn extent(&self) -> Extent2D {
+        self.draw_image.extent_2d()
+    }
+
+    /// What secondary command buffers recorded for [`Self::begin_rendering`] inherit.
+    pub fn inheritance(
+        &self,
+        pipeline_statistics: QueryPipelineStatisticFlags,
+        occlusion_query: Option<QueryControlFlags>,
+    ) -> RenderingInheritance {
+        RenderingInheritance {
+            color_format: DRAW_IMAGE_FORMAT,
+            depth_format: DEPTH_IMAGE_FORMAT,
+            samples: self.samples.sample_count_flags(),
+            pipeline_statistics,
+            occlusion_query,
+        }
+    }
+
+    /// Transitions all targets into attachment layouts and begins dynamic rendering.
+    /// The previous contents of every target are discarded and the color target, the MSAA
+    /// target if there is one, is cleared to `clear_color` when rendering begins.
+    /// With [`RenderingFlags::CONTENTS_SECONDARY_COMMAND_BUFFERS`] the rendering may only be
+    /// continued by executing secondary command buffers.
+    pub fn begin_rendering<'a>(
+        &self,
+        encoder: &'a mut CommandEncoder,
+        clear_color: [f32; 4],
+        flags: RenderingFlags,
+    ) -> RenderingScope<'a> {
+        // With MSAA the draw image is only written by the resolve at the end of the pass.
+        for color_target in [Some(&self.draw_image), self.msaa_color_image.as_ref()].into_iter().flatten() {
+            encoder.transition_image(
+                color_target.image,
+                ImageLayout::UNDEFINED,
+                ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            );
+        }
+        encoder.transition_image(
+            self.depth_image.image,
+            ImageLayout::UNDEFINED,
+            ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+        );
+
+        let color_attachment = match &self.msaa_color_image {
+            Some(msaa_color_image) => RenderingAttachmentInfo::default()
+                .image_view(msaa_color_image.image_view)
+                .image_layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .resolve_mode(ResolveModeFlags::AVERAGE)
+                .resolve_image_view(self.draw_image.image_view)
+                .resolve_image_layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .load_op(AttachmentLoadOp::CLEAR)
+                .store_op(AttachmentStoreOp::DONT_CARE),
+            None => RenderingAttachmentInfo::default()
+                .image_view(self.draw_image.image_view)
+                .image_layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .load_op(AttachmentLoadOp::CLEAR)
+                .store_op(AttachmentStoreOp::STORE),
+        }
+        .clear_value(ClearValue {
+            color: ClearColorValue { float32: clear_color },
+        });
+        let depth_attachment = RenderingAttachmentInfo::default()
+            .image_view(self.depth_image.image_view)
+            .image_layout(ImageLayout::DEPTH_ATTACHMENT_OPTIMAL)
+            .load_op(AttachmentLoadOp::CLEAR)
+            .store_op(AttachmentStoreOp::DONT_CARE)
+            .clear_value(ClearValue {
+                // Reverse-Z, see `camera::Projection`.
+                depth_stencil: ClearDepthStencilValue {
+                    depth: 0.0,
+                    stencil: 0,
+                },
+            });
+
+        let color_attachments = [color_attachment];
+        let rendering_info = RenderingInfo::default()
+            .flags(flags)
+            .render_area(Rect2D {
+                offset: Offset2D::default(),
+                extent: self.extent(),
+            })
+            .layer_count(1)
+            .color_attachments(&color_attachments)
+            .depth_attachment(&depth_attachment);
+        encoder.begin_rendering(&rendering_info)
+    }
+
+    pub fn name_objects(&self, names: &DebugNames) {
+        names.name_image(&self.draw_image, "draw image");
+        names.name_image(&self.depth_image, "depth image");
+        if let Some(msaa_color_image) = &self.msaa_color_image {
+            names.name_image(msaa_color_image, "msaa color image");
+        }
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        self.draw_image.destroy(device);
+        self.depth_image.destroy(device);
+        if let Some(msaa_color_image) = &self.msaa_color_image {
+            msaa_color_image.destroy(device);
+        }
+    }
+}
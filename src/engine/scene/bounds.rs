@@ -0,0 +1,53 @@
+use cgmath::{EuclideanSpace, Matrix4, Point3, Transform, Vector3};
+
+/// Axis aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    /// `None` if there are no points.
+    pub fn from_points(points: impl IntoIterator<Item = Point3<f32>>) -> Option<Aabb> {
+        points.into_iter().fold(None, |bounds, point| {
+            Some(match bounds {
+                None => Aabb {
+                    min: point,
+                    max: point,
+                },
+                Some(Aabb { min, max }) => Aabb {
+                    min: Point3::new(min.x.min(point.x), min.y.min(point.y), min.z.min(point.z)),
+                    max: Point3::new(max.x.max(point.x), max.y.max(point.y), max.z.max(point.z)),
+                },
+            })
+        })
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    /// Half the size along each axis.
+    pub fn extents(&self) -> Vector3<f32> {
+        (self.max - self.min) / 2.0
+    }
+
+    /// The smallest box containing this one after `transform`, which is a bit larger than
+    /// the transformed box if the transform rotates.
+    pub fn transformed(&self, transform: &Matrix4<f32>) -> Aabb {
+        let center = transform.transform_point(self.center());
+        let extents = self.extents();
+        // Each axis of the new box gets the absolute contribution of every old axis.
+        let axis_extent = |row: usize| {
+            transform.x[row].abs() * extents.x
+                + transform.y[row].abs() * extents.y
+                + transform.z[row].abs() * extents.z
+        };
+        let new_extents = Vector3::new(axis_extent(0), axis_extent(1), axis_extent(2));
+        Aabb {
+            min: center - new_extents,
+            max: center + new_extents,
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use cgmath::{InnerSpace, Matrix, Matrix4, Vector3, Vector4};
+
+use super::{bounds::Aabb, RenderObject};
+
+/// The planes bounding what a camera sees, with normals pointing inwards.
+#[derive(Debug, Clone)]
+pub struct Frustum {
+    /// xyz: normal, w: distance, so a point `p` is inside when `dot(normal, p) + w >= 0`.
+    planes: Vec<Vector4<f32>>,
+}
+
+impl Frustum {
+    /// Extracts the planes from a view-projection matrix into Vulkan clip space, where a
+    /// point is visible when `-w <= x, y <= w` and `0 <= z <= w`.
+    ///
+    /// Planes without a normal are dropped: the far plane of an infinite reverse-Z
+    /// projection is `z >= 0`, which every point in front of the camera passes.
+    pub fn from_view_projection(view_projection: Matrix4<f32>) -> Frustum {
+        let rows = view_projection.transpose();
+        let planes = [
+            rows.w + rows.x,
+            rows.w - rows.x,
+            rows.w + rows.y,
+            rows.w - rows.y,
+            rows.w - rows.z,
+            rows.z,
+        ]
+        .into_iter()
+        .filter_map(|plane| {
+            let length = plane.truncate().magnitude();
+            (length > f32::EPSILON).then(|| plane / length)
+        })
+        .collect();
+        Frustum { planes }
+    }
+
+    /// Tests the corner furthest along each plane's normal, so a box is only rejected when
+    /// it lies fully behind one plane. Boxes near a frustum corner may pass anyway.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            let corner = Vector3::new(
+                if plane.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+            plane.truncate().dot(corner) + plane.w >= 0.0
+        })
+    }
+}
+
+/// How many render objects a frame had and how many of them were culled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CullingStats {
+    pub objects: usize,
+    pub culled: usize,
+}
+
+impl CullingStats {
+    pub fn drawn(&self) -> usize {
+        self.objects - self.culled
+    }
+}
+
+/// Removes the objects whose bounding box is outside of `frustum`.
+pub fn cull_render_objects(
+    frustum: &Frustum,
+    render_objects: &mut Vec<RenderObject>,
+) -> CullingStats {
+    let objects = render_objects.len();
+    render_objects.retain(|object| frustum.intersects_aabb(&object.bounds));
+    CullingStats {
+        objects,
+        culled: objects - render_objects.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Matrix4, Point3, SquareMatrix};
+
+    use super::*;
+    use crate::engine::{
+        camera::{Camera, Projection},
+        materials::MaterialId,
+        mesh::MeshId,
+    };
+
+    /// Looks down -z from the origin, see [`Camera::new`].
+    fn perspective_camera() -> Camera {
+        // 70 degrees vertically, so the frustum is about 7 units wide at a distance of 10.
+        Camera::new(Point3::new(0.0, 0.0, 0.0), 1.0)
+    }
+
+    fn orthographic_camera() -> Camera {
+        Camera {
+            projection: Projection::Orthographic {
+                height: 10.0,
+                near: 0.1,
+                far: 100.0,
+            },
+            ..perspective_camera()
+        }
+    }
+
+    fn frustum(camera: &Camera) -> Frustum {
+        Frustum::from_view_projection(camera.view_projection_matrix())
+    }
+
+    /// A box with half extents 1 around `(x, y, z)`.
+    fn unit_box(x: f32, y: f32, z: f32) -> Aabb {
+        Aabb {
+            min: Point3::new(x - 1.0, y - 1.0, z - 1.0),
+            max: Point3::new(x + 1.0, y + 1.0, z + 1.0),
+        }
+    }
+
+    fn render_object(index: usize, bounds: Aabb) -> RenderObject {
+        RenderObject {
+            mesh: MeshId(index),
+            material: MaterialId(0),
+            transform: Matrix4::identity(),
+            bounds,
+        }
+    }
+
+    fn is_visible(frustum: &Frustum, bounds: Aabb) -> bool {
+        let mut render_objects = vec![render_object(0, bounds)];
+        cull_render_objects(frustum, &mut render_objects);
+        !render_objects.is_empty()
+    }
+
+    #[test]
+    fn infinite_perspective_drops_the_far_plane() {
+        assert_eq!(frustum(&perspective_camera()).planes.len(), 5);
+        assert_eq!(frustum(&orthographic_camera()).planes.len(), 6);
+    }
+
+    #[test]
+    fn perspective_keeps_objects_inside() {
+        let frustum = frustum(&perspective_camera());
+        assert!(is_visible(&frustum, unit_box(0.0, 0.0, -10.0)));
+        assert!(is_visible(&frustum, unit_box(5.0, -5.0, -10.0)));
+        // There is no far plane.
+        assert!(is_visible(&frustum, unit_box(0.0, 0.0, -100_000.0)));
+    }
+
+    #[test]
+    fn perspective_culls_objects_outside() {
+        let frustum = frustum(&perspective_camera());
+        assert!(!is_visible(&frustum, unit_box(0.0, 0.0, 10.0)), "behind");
+        assert!(!is_visible(&frustum, unit_box(20.0, 0.0, -10.0)), "right");
+        assert!(!is_visible(&frustum, unit_box(-20.0, 0.0, -10.0)), "left");
+        assert!(!is_visible(&frustum, unit_box(0.0, 20.0, -10.0)), "above");
+        assert!(!is_visible(&frustum, unit_box(0.0, -20.0, -10.0)), "below");
+    }
+
+    #[test]
+    fn perspective_keeps_objects_straddling_a_plane() {
+        let frustum = frustum(&perspective_camera());
+        assert!(is_visible(&frustum, unit_box(7.5, 0.0, -10.0)), "right");
+        assert!(is_visible(&frustum, unit_box(0.0, -7.5, -10.0)), "below");
+        assert!(is_visible(&frustum, unit_box(0.0, 0.0, 0.0)), "near");
+    }
+
+    #[test]
+    fn orthographic_culls_against_all_six_planes() {
+        let frustum = frustum(&orthographic_camera());
+        assert!(is_visible(&frustum, unit_box(0.0, 0.0, -50.0)));
+        assert!(is_visible(&frustum, unit_box(4.0, 4.0, -50.0)));
+        assert!(!is_visible(&frustum, unit_box(0.0, 0.0, 10.0)), "behind");
+        assert!(
+            !is_visible(&frustum, unit_box(0.0, 0.0, -200.0)),
+            "beyond far"
+        );
+        assert!(!is_visible(&frustum, unit_box(8.0, 0.0, -50.0)), "right");
+        assert!(!is_visible(&frustum, unit_box(0.0, -8.0, -50.0)), "below");
+        assert!(
+            is_visible(&frustum, unit_box(5.5, 0.0, -50.0)),
+            "straddling right"
+        );
+        assert!(
+            is_visible(&frustum, unit_box(0.0, 0.0, -100.5)),
+            "straddling far"
+        );
+    }
+
+    #[test]
+    fn planes_are_normalized() {
+        for camera in [perspective_camera(), orthographic_camera()] {
+            for plane in frustum(&camera).planes {
+                assert!((plane.truncate().magnitude() - 1.0).abs() < 1e-5, "{plane:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn culling_keeps_the_order_of_visible_objects() {
+        let frustum = frustum(&perspective_camera());
+        let mut render_objects = vec![
+            render_object(0, unit_box(0.0, 0.0, -10.0)),
+            render_object(1, unit_box(0.0, 0.0, 10.0)),
+            render_object(2, unit_box(7.5, 0.0, -10.0)),
+            render_object(3, unit_box(20.0, 0.0, -10.0)),
+            render_object(4, unit_box(0.0, 0.0, -50.0)),
+        ];
+        let stats = cull_render_objects(&frustum, &mut render_objects);
+        assert_eq!(
+            stats,
+            CullingStats {
+                objects: 5,
+                culled: 2
+            }
+        );
+        assert_eq!(stats.drawn(), 3);
+        let meshes = render_objects
+            .iter()
+            .map(|object| object.mesh)
+            .collect::<Vec<_>>();
+        assert_eq!(meshes, [MeshId(0), MeshId(2), MeshId(4)]);
+    }
+}
@@ -0,0 +1,454 @@
+use cgmath::{Matrix4, SquareMatrix};
+use thiserror::Error;
+
+use super::{materials::MaterialId, mesh::MeshId};
+
+pub mod bounds;
+pub mod culling;
+pub mod transform;
+
+use bounds::Aabb;
+use transform::Transform;
+
+/// Handle of a node added with [`Scene::add_node`]. Ids of removed nodes are never reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeshAttachment {
+    pub mesh: MeshId,
+    pub material: MaterialId,
+}
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneError {
+    #[error("Node {0:?} doesn't exist or was removed")]
+    UnknownNode(NodeId),
+    #[error("Node {node:?} can't become a child of itself or its descendant {parent:?}")]
+    Cycle { node: NodeId, parent: NodeId },
+}
+
+/// What the renderer needs to draw a mesh, extracted from the scene every frame.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderObject {
+    pub mesh: MeshId,
+    pub material: MaterialId,
+    pub transform: Matrix4<f32>,
+    /// World space bounds of the mesh.
+    pub bounds: Aabb,
+}
+
+struct Node {
+    name: String,
+    transform: Transform,
+    world_transform: Matrix4<f32>,
+    /// The local transform or the parent changed since the world transform was computed.
+    dirty: bool,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    mesh: Option<MeshAttachment>,
+}
+
+/// Hierarchy of nodes with transforms relative to their parents, some of which draw a mesh.
+/// World transforms are only recomputed for nodes whose transform or ancestors changed.
+#[derive(Default)]
+pub struct Scene {
+    /// Removed nodes leave a `None` behind so the other ids stay valid.
+    nodes: Vec<Option<Node>>,
+    roots: Vec<NodeId>,
+}
+
+impl Scene {
+    pub fn new() -> Scene {
+        Scene::default()
+    }
+
+    /// Adds a node as the last child of `parent`, or as a root.
+    pub fn add_node(
+        &mut self,
+        name: impl Into<String>,
+        parent: Option<NodeId>,
+        transform: Transform,
+    ) -> Result<NodeId, SceneError> {
+        let id = NodeId(self.nodes.len());
+        match parent {
+            Some(parent) => self.node_mut(parent)?.children.push(id),
+            None => self.roots.push(id),
+        }
+        self.nodes.push(Some(Node {
+            name: name.into(),
+            transform,
+            world_transform: Matrix4::identity(),
+            dirty: true,
+            parent,
+            children: Vec::new(),
+            mesh: None,
+        }));
+        Ok(id)
+    }
+
+    /// Removes `node` together with all of its descendants.
+    pub fn remove_node(&mut self, node: NodeId) -> Result<(), SceneError> {
+        let parent = self.node(node)?.parent;
+        self.siblings_mut(parent).retain(|&sibling| sibling != node);
+        let mut removed = vec![node];
+        while let Some(id) = removed.pop() {
+            if let Some(node) = self.nodes[id.0].take() {
+                removed.extend(node.children);
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves `node` with its descendants to the end of `parent`'s children, or to the roots.
+    /// The local transform is kept, so the world transform changes with the new parent.
+    pub fn set_parent(&mut self, node: NodeId, parent: Option<NodeId>) -> Result<(), SceneError> {
+        let old_parent = self.node(node)?.parent;
+        if let Some(parent) = parent {
+            let mut ancestor = Some(parent);
+            while let Some(id) = ancestor {
+                if id == node {
+                    return Err(SceneError::Cycle { node, parent });
+                }
+                ancestor = self.node(id)?.parent;
+            }
+        }
+        self.siblings_mut(old_parent)
+            .retain(|&sibling| sibling != node);
+        self.siblings_mut(parent).push(node);
+        let node = self.node_mut(node)?;
+        node.parent = parent;
+        node.dirty = true;
+        Ok(())
+    }
+
+    pub fn set_transform(&mut self, node: NodeId, transform: Transform) -> Result<(), SceneError> {
+        let node = self.node_mut(node)?;
+        node.transform = transform;
+        node.dirty = true;
+        Ok(())
+    }
+
+    /// Draws `mesh` with `material` at the node's world transform, replacing a previous mesh.
+    pub fn attach_mesh(
+        &mut self,
+        node: NodeId,
+        mesh: MeshId,
+        material: MaterialId,
+    ) -> Result<(), SceneError> {
+        self.node_mut(node)?.mesh = Some(MeshAttachment { mesh, material });
+        Ok(())
+    }
+
+    pub fn detach_mesh(&mut self, node: NodeId) -> Result<Option<MeshAttachment>, SceneError> {
+        Ok(self.node_mut(node)?.mesh.take())
+    }
+
+    pub fn contains(&self, node: NodeId) -> bool {
+        self.node(node).is_ok()
+    }
+
+    pub fn name(&self, node: NodeId) -> Option<&str> {
+        self.node(node).ok().map(|node| node.name.as_str())
+    }
+
+    pub fn transform(&self, node: NodeId) -> Option<Transform> {
+        self.node(node).ok().map(|node| node.transform)
+    }
+
+    /// As of the last [`Scene::update_world_transforms`].
+    pub fn world_transform(&self, node: NodeId) -> Option<Matrix4<f32>> {
+        self.node(node).ok().map(|node| node.world_transform)
+    }
+
+    pub fn mesh(&self, node: NodeId) -> Option<MeshAttachment> {
+        self.node(node).ok().and_then(|node| node.mesh)
+    }
+
+    pub fn parent(&self, node: NodeId) -> Option<NodeId> {
+        self.node(node).ok().and_then(|node| node.parent)
+    }
+
+    pub fn children(&self, node: NodeId) -> &[NodeId] {
+        self.node(node)
+            .map(|node| node.children.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    /// Recomputes the world transforms of dirty nodes and their descendants, parents first.
+    pub fn update_world_transforms(&mut self) {
+        let mut stack = self
+            .roots
+            .iter()
+            .rev()
+            .map(|&root| (root, Matrix4::identity(), false))
+            .collect::<Vec<_>>();
+        while let Some((id, parent_world_transform, parent_changed)) = stack.pop() {
+            let Some(node) = self.nodes[id.0].as_mut() else {
+                continue;
+            };
+            let changed = parent_changed || node.dirty;
+            if changed {
+                node.world_transform = parent_world_transform * node.transform.matrix();
+                node.dirty = false;
+            }
+            let world_transform = node.world_transform;
+            stack.extend(
+                node.children
+                    .iter()
+                    .rev()
+                    .map(|&child| (child, world_transform, changed)),
+            );
+        }
+    }
+
+    /// Updates the world transforms and appends a render object for every node with a mesh.
+    /// `mesh_bounds` gives the object space bounds of a mesh, or fails for unknown meshes.
+    pub fn extract<E>(
+        &mut self,
+        mesh_bounds: impl Fn(MeshId) -> Result<Aabb, E>,
+        render_objects: &mut Vec<RenderObject>,
+    ) -> Result<(), E> {
+        self.update_world_transforms();
+        for node in self.nodes.iter().flatten() {
+            let Some(attachment) = node.mesh else {
+                continue;
+            };
+            render_objects.push(RenderObject {
+                mesh: attachment.mesh,
+                material: attachment.material,
+                transform: node.world_transform,
+                bounds: mesh_bounds(attachment.mesh)?.transformed(&node.world_transform),
+            });
+        }
+        Ok(())
+    }
+
+    fn node(&self, node: NodeId) -> Result<&Node, SceneError> {
+        self.nodes
+            .get(node.0)
+            .and_then(Option::as_ref)
+            .ok_or(SceneError::UnknownNode(node))
+    }
+
+    fn node_mut(&mut self, node: NodeId) -> Result<&mut Node, SceneError> {
+        self.nodes
+            .get_mut(node.0)
+            .and_then(Option::as_mut)
+            .ok_or(SceneError::UnknownNode(node))
+    }
+
+    /// The children of `parent`, or the roots. `parent` has to exist.
+    fn siblings_mut(&mut self, parent: Option<NodeId>) -> &mut Vec<NodeId> {
+        match parent {
+            Some(parent) => {
+                &mut self.nodes[parent.0]
+                    .as_mut()
+                    .expect("parent of a live node was removed")
+                    .children
+            }
+            None => &mut self.roots,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use cgmath::{Point3, Quaternion, Rad, Rotation3, Transform as _, Vector3};
+
+    use super::*;
+
+    fn translation(x: f32, y: f32, z: f32) -> Transform {
+        Transform::from_translation(Vector3::new(x, y, z))
+    }
+
+    fn origin_of(scene: &Scene, node: NodeId) -> Point3<f32> {
+        scene
+            .world_transform(node)
+            .unwrap()
+            .transform_point(Point3::new(0.0, 0.0, 0.0))
+    }
+
+    fn assert_near(actual: Point3<f32>, expected: Point3<f32>) {
+        let distance = (actual - expected).map(f32::abs);
+        assert!(
+            distance.x < 1e-5 && distance.y < 1e-5 && distance.z < 1e-5,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    fn unit_bounds(_: MeshId) -> Result<Aabb, Infallible> {
+        Ok(Aabb {
+            min: Point3::new(-1.0, -1.0, -1.0),
+            max: Point3::new(1.0, 1.0, 1.0),
+        })
+    }
+
+    /// root -> middle -> leaf, each one unit further along x than its parent.
+    fn chain() -> (Scene, [NodeId; 3]) {
+        let mut scene = Scene::new();
+        let root = scene
+            .add_node("root", None, translation(1.0, 0.0, 0.0))
+            .unwrap();
+        let middle = scene
+            .add_node("middle", Some(root), translation(1.0, 0.0, 0.0))
+            .unwrap();
+        let leaf = scene
+            .add_node("leaf", Some(middle), translation(1.0, 0.0, 0.0))
+            .unwrap();
+        scene.update_world_transforms();
+        (scene, [root, middle, leaf])
+    }
+
+    #[test]
+    fn world_transforms_propagate_through_the_chain() {
+        let (scene, [root, middle, leaf]) = chain();
+        assert_near(origin_of(&scene, root), Point3::new(1.0, 0.0, 0.0));
+        assert_near(origin_of(&scene, middle), Point3::new(2.0, 0.0, 0.0));
+        assert_near(origin_of(&scene, leaf), Point3::new(3.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn rotation_and_scale_apply_to_descendants() {
+        let mut scene = Scene::new();
+        let root = scene
+            .add_node(
+                "root",
+                None,
+                Transform {
+                    rotation: Quaternion::from_angle_z(Rad(std::f32::consts::FRAC_PI_2)),
+                    scale: Vector3::new(2.0, 2.0, 2.0),
+                    ..Transform::default()
+                },
+            )
+            .unwrap();
+        let child = scene
+            .add_node("child", Some(root), translation(1.0, 0.0, 0.0))
+            .unwrap();
+        scene.update_world_transforms();
+        assert_near(origin_of(&scene, child), Point3::new(0.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn set_transform_updates_the_subtree() {
+        let (mut scene, [root, middle, leaf]) = chain();
+        scene
+            .set_transform(middle, translation(0.0, 5.0, 0.0))
+            .unwrap();
+        // Nothing changes until the next update.
+        assert_near(origin_of(&scene, leaf), Point3::new(3.0, 0.0, 0.0));
+        scene.update_world_transforms();
+        assert_near(origin_of(&scene, root), Point3::new(1.0, 0.0, 0.0));
+        assert_near(origin_of(&scene, middle), Point3::new(1.0, 5.0, 0.0));
+        assert_near(origin_of(&scene, leaf), Point3::new(2.0, 5.0, 0.0));
+    }
+
+    #[test]
+    fn set_parent_keeps_the_local_transform() {
+        let (mut scene, [root, middle, leaf]) = chain();
+        let other = scene
+            .add_node("other", None, translation(0.0, 0.0, 10.0))
+            .unwrap();
+        scene.set_parent(middle, Some(other)).unwrap();
+        scene.update_world_transforms();
+        assert_near(origin_of(&scene, middle), Point3::new(1.0, 0.0, 10.0));
+        assert_near(origin_of(&scene, leaf), Point3::new(2.0, 0.0, 10.0));
+        assert_eq!(scene.parent(middle), Some(other));
+        assert!(scene.children(root).is_empty());
+        assert_eq!(scene.children(other), &[middle]);
+
+        scene.set_parent(middle, None).unwrap();
+        scene.update_world_transforms();
+        assert_near(origin_of(&scene, leaf), Point3::new(2.0, 0.0, 0.0));
+        assert_eq!(scene.roots(), &[root, other, middle]);
+    }
+
+    #[test]
+    fn set_parent_rejects_cycles() {
+        let (mut scene, [root, middle, leaf]) = chain();
+        assert_eq!(
+            scene.set_parent(root, Some(leaf)),
+            Err(SceneError::Cycle {
+                node: root,
+                parent: leaf
+            })
+        );
+        assert_eq!(
+            scene.set_parent(middle, Some(middle)),
+            Err(SceneError::Cycle {
+                node: middle,
+                parent: middle
+            })
+        );
+        // The failed calls left the hierarchy alone.
+        assert_eq!(scene.roots(), &[root]);
+        assert_eq!(scene.parent(leaf), Some(middle));
+    }
+
+    #[test]
+    fn remove_node_removes_the_subtree() {
+        let (mut scene, [root, middle, leaf]) = chain();
+        let sibling = scene
+            .add_node("sibling", Some(root), Transform::default())
+            .unwrap();
+        scene.remove_node(middle).unwrap();
+        assert!(scene.contains(root));
+        assert!(scene.contains(sibling));
+        assert!(!scene.contains(middle));
+        assert!(!scene.contains(leaf));
+        assert_eq!(scene.children(root), &[sibling]);
+        assert_eq!(scene.remove_node(leaf), Err(SceneError::UnknownNode(leaf)));
+        assert_eq!(
+            scene.add_node("orphan", Some(middle), Transform::default()),
+            Err(SceneError::UnknownNode(middle))
+        );
+        // Ids are never reused.
+        let node = scene.add_node("new", None, Transform::default()).unwrap();
+        assert_ne!(node, middle);
+        assert_ne!(node, leaf);
+    }
+
+    #[test]
+    fn extract_emits_nodes_with_meshes() {
+        let (mut scene, [root, middle, leaf]) = chain();
+        scene.attach_mesh(root, MeshId(0), MaterialId(1)).unwrap();
+        scene.attach_mesh(leaf, MeshId(2), MaterialId(3)).unwrap();
+        scene.attach_mesh(middle, MeshId(4), MaterialId(5)).unwrap();
+        assert_eq!(
+            scene.detach_mesh(middle),
+            Ok(Some(MeshAttachment {
+                mesh: MeshId(4),
+                material: MaterialId(5)
+            }))
+        );
+        scene
+            .set_transform(root, translation(0.0, 1.0, 0.0))
+            .unwrap();
+
+        let mut render_objects = Vec::new();
+        scene.extract(unit_bounds, &mut render_objects).unwrap();
+        assert_eq!(render_objects.len(), 2);
+
+        let leaf_object = render_objects
+            .iter()
+            .find(|object| object.mesh == MeshId(2))
+            .unwrap();
+        assert_eq!(leaf_object.material, MaterialId(3));
+        assert_eq!(leaf_object.transform, scene.world_transform(leaf).unwrap());
+        assert_near(leaf_object.bounds.min, Point3::new(1.0, 0.0, -1.0));
+        assert_near(leaf_object.bounds.max, Point3::new(3.0, 2.0, 1.0));
+
+        let root_object = render_objects
+            .iter()
+            .find(|object| object.mesh == MeshId(0))
+            .unwrap();
+        assert_eq!(root_object.material, MaterialId(1));
+        assert_near(root_object.bounds.center(), Point3::new(0.0, 1.0, 0.0));
+    }
+}
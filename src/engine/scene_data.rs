@@ -0,0 +1,103 @@
+use anyhow::Error;
+use ash::{
+    vk::{
+        DescriptorSet, DescriptorSetLayout, DescriptorType, PipelineBindPoint, PipelineLayout,
+        ShaderStageFlags,
+    },
+    Device,
+};
+use bytemuck::{Pod, Zeroable};
+use cgmath::{InnerSpace, Vector3};
+
+use super::{camera::Camera, command_encoder::BindCommands, descriptors::DescriptorLayoutBuilder};
+
+/// Descriptor set index the scene data is bound to in every pipeline layout.
+pub const GLOBAL_DESCRIPTOR_SET: u32 = 0;
+pub const SCENE_DATA_BINDING: u32 = 0;
+
+/// Mirrors the `SceneData` uniform block in the shaders. Only vec4 and mat4 members
+/// are used so the std140 layout matches `#[repr(C)]` without padding fields.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct GpuSceneData {
+    pub view: [[f32; 4]; 4],
+    pub projection: [[f32; 4]; 4],
+    pub view_projection: [[f32; 4]; 4],
+    /// xyz: world space camera position, w: unused.
+    pub camera_position: [f32; 4],
+    /// x: seconds since engine start, y: frame delta in seconds, z: frame number, w: unused.
+    pub time: [f32; 4],
+    /// xyz: normalized direction the light travels in, w: intensity.
+    pub light_direction: [f32; 4],
+    /// rgb: light color, a: unused.
+    pub light_color: [f32; 4],
+    /// rgb: ambient color, a: ambient intensity.
+    pub ambient_color: [f32; 4],
+}
+
+/// CPU-side lighting parameters that end up in [`GpuSceneData`] every frame.
+#[derive(Debug, Clone, Copy)]
+pub struct SceneLighting {
+    pub light_direction: Vector3<f32>,
+    pub light_intensity: f32,
+    pub light_color: [f32; 3],
+    pub ambient_color: [f32; 3],
+    pub ambient_intensity: f32,
+}
+
+impl Default for SceneLighting {
+    fn default() -> Self {
+        Self {
+            light_direction: Vector3::new(-0.3, -1.0, -0.5),
+            light_intensity: 1.0,
+            light_color: [1.0, 1.0, 1.0],
+            ambient_color: [1.0, 1.0, 1.0],
+            ambient_intensity: 0.1,
+        }
+    }
+}
+
+impl GpuSceneData {
+    pub fn new(
+        camera: &Camera,
+        lighting: &SceneLighting,
+        elapsed_seconds: f32,
+        delta_seconds: f32,
+        frame_number: u64,
+    ) -> GpuSceneData {
+        let direction = lighting.light_direction.normalize();
+        let [light_r, light_g, light_b] = lighting.light_color;
+        let [ambient_r, ambient_g, ambient_b] = lighting.ambient_color;
+        GpuSceneData {
+            view: camera.view_matrix().into(),
+            projection: camera.projection_matrix().into(),
+            view_projection: camera.view_projection_matrix().into(),
+            camera_position: [camera.position.x, camera.position.y, camera.position.z, 1.0],
+            time: [elapsed_seconds, delta_seconds, frame_number as f32, 0.0],
+            light_direction: [direction.x, direction.y, direction.z, lighting.light_intensity],
+            light_color: [light_r, light_g, light_b, 1.0],
+            ambient_color: [ambient_r, ambient_g, ambient_b, lighting.ambient_intensity],
+        }
+    }
+}
+
+pub fn create_global_set_layout(device: &Device) -> Result<DescriptorSetLayout, Error> {
+    DescriptorLayoutBuilder::default()
+        .add_binding(
+            SCENE_DATA_BINDING,
+            DescriptorType::UNIFORM_BUFFER,
+            ShaderStageFlags::ALL_GRAPHICS | ShaderStageFlags::COMPUTE,
+        )
+        .build(device)
+}
+
+/// Binds the frame's scene data to set 0. Has to be called by every renderer after
+/// binding its pipeline since all pipeline layouts start with the global set layout.
+pub fn bind_global_descriptor(
+    commands: &mut impl BindCommands,
+    bind_point: PipelineBindPoint,
+    layout: PipelineLayout,
+    global_descriptor: DescriptorSet,
+) {
+    commands.bind_descriptor_sets(bind_point, layout, GLOBAL_DESCRIPTOR_SET, &[global_descriptor]);
+}
@@ -1,9 +1,15 @@
 use ash::vk::{
-    ColorSpaceKHR, CompositeAlphaFlagsKHR, Extent2D, Format, Image, ImageUsageFlags, PhysicalDevice, PresentModeKHR, SharingMode, SurfaceCapabilitiesKHR, SurfaceFormatKHR, SurfaceKHR, SwapchainCreateFlagsKHR, SwapchainCreateInfoKHR, SwapchainKHR
+    ColorSpaceKHR, CompositeAlphaFlagsKHR, Extent2D, Format, Image, ImageAspectFlags, ImageUsageFlags, ImageView, ImageViewCreateInfo, ImageViewType, PhysicalDevice, PresentModeKHR, SharingMode, SurfaceCapabilitiesKHR, SurfaceFormatKHR, SurfaceKHR, SwapchainCreateFlagsKHR, SwapchainCreateInfoKHR, SwapchainKHR
 };
 use thiserror::Error;
 
+use crate::engine::errors::engine_error::{EngineError, VkResultExt};
 use crate::engine::queues::QueueIndices;
+use crate::engine::util::image_sub_resource_range;
+
+/// UNORM so the UI's sRGB encoded colors are written as is. The scene is encoded by the
+/// tonemap pass before it is blitted here.
+pub static SWAPCHAIN_IMAGE_FORMAT: Format = Format::B8G8R8A8_UNORM;
 
 pub struct SwapchainSupportDetails {
    pub surface_capabilities: SurfaceCapabilitiesKHR,
@@ -13,8 +19,8 @@ pub struct SwapchainSupportDetails {
 
 #[derive(Error, Debug)]
 pub enum SwapchainSupportError {
-    #[error("Couldn't retrieve format")]
-    FailedToGetSupportDetails,
+    #[error("Couldn't retrieve the surface support details: {0}")]
+    FailedToGetSupportDetails(ash::vk::Result),
 }
 
 #[derive(Error, Debug)]
@@ -22,7 +28,9 @@ pub enum SwapchainCreationError {
     #[error("Failed to create the swapchain,\n ERR_MSG: {err_message:?}")]
     SwapchainCreationError {
         err_message: ash::vk::Result
-    }
+    },
+    #[error("No queue family can present to the surface")]
+    NoPresentationFamily,
 }
 
 impl SwapchainSupportDetails {
@@ -31,23 +39,19 @@ impl SwapchainSupportDetails {
         physical_device: PhysicalDevice,
         surface: SurfaceKHR,
     ) -> Result<SwapchainSupportDetails, SwapchainSupportError> {
-        Ok(SwapchainSupportDetails {
-            surface_capabilities: unsafe {
-                surface_instance
+        unsafe {
+            Ok(SwapchainSupportDetails {
+                surface_capabilities: surface_instance
                     .get_physical_device_surface_capabilities(physical_device, surface)
-                    .unwrap()
-            },
-            surface_formats: unsafe {
-                surface_instance
+                    .map_err(SwapchainSupportError::FailedToGetSupportDetails)?,
+                surface_formats: surface_instance
                     .get_physical_device_surface_formats(physical_device, surface)
-                    .unwrap()
-            },
-            present_modes: unsafe {
-                surface_instance
+                    .map_err(SwapchainSupportError::FailedToGetSupportDetails)?,
+                present_modes: surface_instance
                     .get_physical_device_surface_present_modes(physical_device, surface)
-                    .unwrap()
-            },
-        })
+                    .map_err(SwapchainSupportError::FailedToGetSupportDetails)?,
+            })
+        }
     }
 }
 
@@ -57,23 +61,34 @@ pub fn create_swapchain(
     queue_indices: QueueIndices,
     surface: SurfaceKHR,
     width: u32,
-    height: u32
+    height: u32,
+    present_mode: PresentModeKHR,
 ) -> Result<SwapchainKHR, SwapchainCreationError> {
     let extent = Extent2D::default().height(height).width(width);
     
     let min_image_count = get_image_count(swapchain_support_details.surface_capabilities.min_image_count + 1, swapchain_support_details.surface_capabilities.max_image_count);
 
-    let queue_family_indices = [queue_indices.graphics_queue_index.unwrap()];
+    let graphics_family = queue_indices.graphics_queue_index;
+    let presentation_family = queue_indices
+        .presentation_queue_index
+        .ok_or(SwapchainCreationError::NoPresentationFamily)?;
+    // Exclusive images would need an ownership transfer between rendering and presenting
+    // when the families differ, sharing them is simpler and the cost is negligible.
+    let queue_family_indices = [graphics_family, presentation_family];
+    let (sharing_mode, queue_family_indices) = match graphics_family == presentation_family {
+        true => (SharingMode::EXCLUSIVE, &queue_family_indices[..0]),
+        false => (SharingMode::CONCURRENT, &queue_family_indices[..]),
+    };
     let create_info= SwapchainCreateInfoKHR::default()
         .flags(SwapchainCreateFlagsKHR::default())
         .image_usage(ImageUsageFlags::TRANSFER_DST | ImageUsageFlags::COLOR_ATTACHMENT)
         .image_extent(extent)
-        .present_mode(PresentModeKHR::FIFO)
-        .image_format(Format::B8G8R8A8_UNORM)
+        .present_mode(present_mode)
+        .image_format(SWAPCHAIN_IMAGE_FORMAT)
         .image_color_space(ColorSpaceKHR::SRGB_NONLINEAR)
-        .image_sharing_mode(SharingMode::CONCURRENT)
+        .image_sharing_mode(sharing_mode)
         .image_array_layers(1)
-        .queue_family_indices(&queue_family_indices)
+        .queue_family_indices(queue_family_indices)
         .pre_transform(swapchain_support_details.surface_capabilities.current_transform)
         .composite_alpha(CompositeAlphaFlagsKHR::OPAQUE)
         .clipped(true)
@@ -87,16 +102,41 @@ pub fn create_swapchain(
     }
 }
 
-fn get_image_count(min_image_count: u32, max_image_count: u32) -> u32 {
-    u32::min(min_image_count, max_image_count)
+/// FIFO is the only mode that is guaranteed to exist and the only one that waits for vblank.
+/// Without vsync MAILBOX is preferred since it does not tear, then IMMEDIATE.
+pub fn choose_present_mode(swapchain_support_details: &SwapchainSupportDetails, vsync: bool) -> PresentModeKHR {
+    if vsync {
+        return PresentModeKHR::FIFO;
+    }
+    [PresentModeKHR::MAILBOX, PresentModeKHR::IMMEDIATE]
+        .into_iter()
+        .find(|mode| swapchain_support_details.present_modes.contains(mode))
+        .unwrap_or(PresentModeKHR::FIFO)
 }
 
-pub fn create_swapchain_images(device: &ash::khr::swapchain::Device, swapchain: SwapchainKHR) -> Result<Vec<Image>, anyhow::Error> {
-    unsafe { match device.get_swapchain_images(swapchain) {
-        Ok(images) => Ok(images),
-        Err(_) => {
-            Err(anyhow::anyhow!("Failed to retrieve images"))
-        },
-    }
+/// A `max_image_count` of 0 means there is no upper limit.
+fn get_image_count(min_image_count: u32, max_image_count: u32) -> u32 {
+    match max_image_count {
+        0 => min_image_count,
+        max_image_count => u32::min(min_image_count, max_image_count),
     }
 }
+
+pub fn create_swapchain_images(device: &ash::khr::swapchain::Device, swapchain: SwapchainKHR) -> Result<Vec<Image>, EngineError> {
+    unsafe { device.get_swapchain_images(swapchain) }.vk_context("vkGetSwapchainImagesKHR")
+}
+
+pub fn create_swapchain_image_views(device: &ash::Device, images: &[Image]) -> Result<Vec<ImageView>, EngineError> {
+    images
+        .iter()
+        .map(|image| {
+            let create_info = ImageViewCreateInfo::default()
+                .image(*image)
+                .view_type(ImageViewType::TYPE_2D)
+                .format(SWAPCHAIN_IMAGE_FORMAT)
+                .subresource_range(image_sub_resource_range(ImageAspectFlags::COLOR));
+            unsafe { device.create_image_view(&create_info, None) }
+                .vk_object_context("vkCreateImageView", "swapchain image")
+        })
+        .collect()
+}
@@ -1,12 +1,21 @@
-use anyhow::Error;
-use ash::{vk::{Fence, FenceCreateFlags, FenceCreateInfo, Semaphore, SemaphoreCreateFlags, SemaphoreCreateInfo}, Device};
+use ash::{vk::{Fence, FenceCreateFlags, FenceCreateInfo, Semaphore, SemaphoreCreateFlags, SemaphoreCreateInfo, SemaphoreType, SemaphoreTypeCreateInfo}, Device};
 
-pub fn create_fence(device: &Device, flags: FenceCreateFlags) -> Result<Fence, Error>{
+use super::errors::engine_error::{EngineError, VkResultExt};
+
+pub fn create_fence(device: &Device, flags: FenceCreateFlags) -> Result<Fence, EngineError>{
     let create_info = FenceCreateInfo::default().flags(flags);
-    unsafe { Ok(device.create_fence(&create_info, None).unwrap()) }
+    unsafe { device.create_fence(&create_info, None) }.vk_context("vkCreateFence")
 } 
 
-pub fn create_semaphore(device: &Device) -> Result<Semaphore, Error>{
+pub fn create_semaphore(device: &Device) -> Result<Semaphore, EngineError>{
     let create_info= SemaphoreCreateInfo::default().flags(SemaphoreCreateFlags::empty());
-    unsafe { Ok(device.create_semaphore(&create_info, None).unwrap()) }
+    unsafe { device.create_semaphore(&create_info, None) }.vk_context("vkCreateSemaphore")
+}
+
+pub fn create_timeline_semaphore(device: &Device, initial_value: u64) -> Result<Semaphore, EngineError> {
+    let mut type_create_info = SemaphoreTypeCreateInfo::default()
+        .semaphore_type(SemaphoreType::TIMELINE)
+        .initial_value(initial_value);
+    let create_info = SemaphoreCreateInfo::default().push_next(&mut type_create_info);
+    unsafe { device.create_semaphore(&create_info, None) }.vk_context("vkCreateSemaphore")
 }
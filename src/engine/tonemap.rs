@@ -0,0 +1,118 @@
+use anyhow::Error;
+use ash::{
+    vk::{
+        DescriptorImageInfo, DescriptorSet, DescriptorSetLayout, DescriptorType,
+        ImageLayout, Pipeline, PipelineBindPoint, PipelineCache, PipelineLayout, ShaderStageFlags,
+        WriteDescriptorSet,
+    },
+    Device,
+};
+
+use super::{
+    command_encoder::{BindCommands, CommandEncoder},
+    debug_names::DebugNames,
+    descriptors::{DescriptorAllocator, DescriptorLayoutBuilder},
+    images::AllocatedImage,
+    pipelines::{create_compute_pipeline, create_pipeline_layout, include_shader, load_shader_module},
+    scene_data::bind_global_descriptor,
+};
+
+const DRAW_IMAGE_SET: u32 = 1;
+const DRAW_IMAGE_BINDING: u32 = 0;
+/// Matches `local_size_x` and `local_size_y` in `tonemap.comp`.
+const WORKGROUP_SIZE: u32 = 16;
+
+/// Tonemaps the HDR draw image and encodes it as sRGB in place, before it is blitted to
+/// the UNORM swapchain image. The swapchain's color space is sRGB, but a blit only
+/// converts formats, it never applies the transfer function.
+pub struct Tonemapper {
+    set_layout: DescriptorSetLayout,
+    pipeline_layout: PipelineLayout,
+    pipeline: Pipeline,
+    descriptors: DescriptorAllocator,
+    set: DescriptorSet,
+}
+
+impl Tonemapper {
+    pub fn new(
+        device: &Device,
+        global_set_layout: DescriptorSetLayout,
+        draw_image: &AllocatedImage,
+        pipeline_cache: PipelineCache,
+    ) -> Result<Tonemapper, Error> {
+        let descriptors = DescriptorAllocator::new(device, 1, &[(DescriptorType::STORAGE_IMAGE, 1.0)])?;
+        let mut tonemapper = Tonemapper {
+            set_layout: DescriptorSetLayout::null(),
+            pipeline_layout: PipelineLayout::null(),
+            pipeline: Pipeline::null(),
+            descriptors,
+            set: DescriptorSet::null(),
+        };
+        // Destroying null handles is a no-op, so whatever was created so far can go.
+        if let Err(err) = tonemapper.create_objects(device, global_set_layout, pipeline_cache) {
+            tonemapper.destroy(device);
+            return Err(err);
+        }
+        tonemapper.set_draw_image(device, draw_image);
+        Ok(tonemapper)
+    }
+
+    fn create_objects(
+        &mut self,
+        device: &Device,
+        global_set_layout: DescriptorSetLayout,
+        pipeline_cache: PipelineCache,
+    ) -> Result<(), Error> {
+        self.set_layout = DescriptorLayoutBuilder::default()
+            .add_binding(DRAW_IMAGE_BINDING, DescriptorType::STORAGE_IMAGE, ShaderStageFlags::COMPUTE)
+            .build(device)?;
+        self.pipeline_layout = create_pipeline_layout(device, global_set_layout, &[self.set_layout], &[])?;
+        let shader = load_shader_module(device, include_shader!("tonemap.comp"))?;
+        let pipeline = create_compute_pipeline(device, self.pipeline_layout, shader, pipeline_cache);
+        unsafe { device.destroy_shader_module(shader, None) };
+        self.pipeline = pipeline?;
+        self.set = self.descriptors.allocate(device, self.set_layout)?;
+        Ok(())
+    }
+
+    /// Points the pass at a recreated draw image. The device has to be idle.
+    pub fn set_draw_image(&self, device: &Device, draw_image: &AllocatedImage) {
+        let image_infos = [DescriptorImageInfo::default()
+            .image_view(draw_image.image_view)
+            .image_layout(ImageLayout::GENERAL)];
+        let write = WriteDescriptorSet::default()
+            .dst_set(self.set)
+            .dst_binding(DRAW_IMAGE_BINDING)
+            .descriptor_type(DescriptorType::STORAGE_IMAGE)
+            .image_info(&image_infos);
+        unsafe { device.update_descriptor_sets(&[write], &[]) };
+    }
+
+    /// Expects the draw image in GENERAL and leaves it there.
+    pub fn record(&self, encoder: &mut CommandEncoder, global_descriptor: DescriptorSet, draw_image: &AllocatedImage) {
+        encoder.bind_pipeline(PipelineBindPoint::COMPUTE, self.pipeline);
+        bind_global_descriptor(encoder, PipelineBindPoint::COMPUTE, self.pipeline_layout, global_descriptor);
+        encoder.bind_descriptor_sets(PipelineBindPoint::COMPUTE, self.pipeline_layout, DRAW_IMAGE_SET, &[self.set]);
+        let extent = draw_image.extent_2d();
+        encoder.dispatch(
+            extent.width.div_ceil(WORKGROUP_SIZE),
+            extent.height.div_ceil(WORKGROUP_SIZE),
+            1,
+        );
+    }
+
+    pub fn name_objects(&self, names: &DebugNames) {
+        names.name(self.pipeline, "tonemap pipeline");
+        names.name(self.pipeline_layout, "tonemap pipeline layout");
+        names.name(self.set_layout, "tonemap set layout");
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_descriptor_set_layout(self.set_layout, None);
+        }
+        self.descriptors.destroy(device);
+    }
+}
@@ -0,0 +1,353 @@
+use egui::{
+    Event, Key, Modifiers, MouseWheelUnit, PointerButton, Pos2, RawInput, Rect, Vec2, ViewportId,
+};
+use winit::{
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::{self, KeyCode, ModifiersState, NamedKey, PhysicalKey},
+    window::Window,
+};
+
+/// Collects winit window events into an [`egui::RawInput`] for the next UI frame.
+pub struct UiInput {
+    raw_input: RawInput,
+    pointer_position: Pos2,
+    modifiers: Modifiers,
+}
+
+impl UiInput {
+    pub fn new(max_texture_side: usize) -> UiInput {
+        UiInput {
+            raw_input: RawInput {
+                max_texture_side: Some(max_texture_side),
+                focused: true,
+                ..Default::default()
+            },
+            pointer_position: Pos2::ZERO,
+            modifiers: Modifiers::default(),
+        }
+    }
+
+    pub fn handle_window_event(&mut self, window: &Window, event: &WindowEvent) {
+        let pixels_per_point = window.scale_factor() as f32;
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.pointer_position = Pos2::new(
+                    position.x as f32 / pixels_per_point,
+                    position.y as f32 / pixels_per_point,
+                );
+                self.push(Event::PointerMoved(self.pointer_position));
+            }
+            WindowEvent::CursorLeft { .. } => self.push(Event::PointerGone),
+            WindowEvent::MouseInput { state, button, .. } => {
+                if let Some(button) = translate_mouse_button(*button) {
+                    self.push(Event::PointerButton {
+                        pos: self.pointer_position,
+                        button,
+                        pressed: *state == ElementState::Pressed,
+                        modifiers: self.modifiers,
+                    });
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (unit, delta) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => (MouseWheelUnit::Line, Vec2::new(*x, *y)),
+                    MouseScrollDelta::PixelDelta(position) => (
+                        MouseWheelUnit::Point,
+                        Vec2::new(position.x as f32, position.y as f32) / pixels_per_point,
+                    ),
+                };
+                self.push(Event::MouseWheel {
+                    unit,
+                    delta,
+                    modifiers: self.modifiers,
+                });
+            }
+            WindowEvent::KeyboardInput { event, .. } => self.handle_key_event(event),
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = translate_modifiers(modifiers.state());
+                self.raw_input.modifiers = self.modifiers;
+            }
+            WindowEvent::Focused(focused) => {
+                self.raw_input.focused = *focused;
+                self.push(Event::WindowFocused(*focused));
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_key_event(&mut self, event: &KeyEvent) {
+        let pressed = event.state == ElementState::Pressed;
+        let physical_key = match event.physical_key {
+            PhysicalKey::Code(code) => translate_key_code(code),
+            PhysicalKey::Unidentified(_) => None,
+        };
+        let key = match &event.logical_key {
+            keyboard::Key::Named(named) => translate_named_key(*named),
+            // Characters like "a" or "+" are what egui's key names are.
+            keyboard::Key::Character(text) => Key::from_name(text.as_str()),
+            _ => None,
+        }
+        .or(physical_key);
+
+        if let Some(key) = key {
+            if pressed && self.modifiers.command {
+                match key {
+                    Key::C => self.push(Event::Copy),
+                    Key::X => self.push(Event::Cut),
+                    _ => {}
+                }
+            }
+            self.push(Event::Key {
+                key,
+                physical_key,
+                pressed,
+                repeat: event.repeat,
+                modifiers: self.modifiers,
+            });
+        }
+
+        // Enter is handled as a key, everything else printable also produces text.
+        let is_enter = event.logical_key == keyboard::Key::Named(NamedKey::Enter);
+        if let Some(text) = event.text.as_ref().filter(|_| pressed && !is_enter) {
+            let text = text.chars().filter(|c| !c.is_control()).collect::<String>();
+            if !(text.is_empty() || self.modifiers.ctrl || self.modifiers.mac_cmd) {
+                self.push(Event::Text(text));
+            }
+        }
+    }
+
+    fn push(&mut self, event: Event) {
+        self.raw_input.events.push(event);
+    }
+
+    /// Returns the input gathered since the last call, sized for `window`.
+    pub fn take_raw_input(&mut self, window: &Window, time: f64) -> RawInput {
+        let pixels_per_point = window.scale_factor() as f32;
+        let size = window.inner_size();
+        let screen_size = Vec2::new(size.width as f32, size.height as f32) / pixels_per_point;
+
+        self.raw_input.screen_rect = Some(Rect::from_min_size(Pos2::ZERO, screen_size));
+        self.raw_input.time = Some(time);
+        self.raw_input
+            .viewports
+            .entry(ViewportId::ROOT)
+            .or_default()
+            .native_pixels_per_point = Some(pixels_per_point);
+
+        let next = RawInput {
+            max_texture_side: self.raw_input.max_texture_side,
+            focused: self.raw_input.focused,
+            modifiers: self.modifiers,
+            viewports: self.raw_input.viewports.clone(),
+            ..Default::default()
+        };
+        std::mem::replace(&mut self.raw_input, next)
+    }
+}
+
+fn translate_mouse_button(button: MouseButton) -> Option<PointerButton> {
+    match button {
+        MouseButton::Left => Some(PointerButton::Primary),
+        MouseButton::Right => Some(PointerButton::Secondary),
+        MouseButton::Middle => Some(PointerButton::Middle),
+        MouseButton::Back => Some(PointerButton::Extra1),
+        MouseButton::Forward => Some(PointerButton::Extra2),
+        MouseButton::Other(_) => None,
+    }
+}
+
+fn translate_modifiers(state: ModifiersState) -> Modifiers {
+    let mac_cmd = cfg!(target_os = "macos") && state.super_key();
+    Modifiers {
+        alt: state.alt_key(),
+        ctrl: state.control_key(),
+        shift: state.shift_key(),
+        mac_cmd,
+        command: match cfg!(target_os = "macos") {
+            true => mac_cmd,
+            false => state.control_key(),
+        },
+    }
+}
+
+fn translate_named_key(key: NamedKey) -> Option<Key> {
+    Some(match key {
+        NamedKey::ArrowDown => Key::ArrowDown,
+        NamedKey::ArrowLeft => Key::ArrowLeft,
+        NamedKey::ArrowRight => Key::ArrowRight,
+        NamedKey::ArrowUp => Key::ArrowUp,
+        NamedKey::Escape => Key::Escape,
+        NamedKey::Tab => Key::Tab,
+        NamedKey::Backspace => Key::Backspace,
+        NamedKey::Enter => Key::Enter,
+        NamedKey::Space => Key::Space,
+        NamedKey::Insert => Key::Insert,
+        NamedKey::Delete => Key::Delete,
+        NamedKey::Home => Key::Home,
+        NamedKey::End => Key::End,
+        NamedKey::PageUp => Key::PageUp,
+        NamedKey::PageDown => Key::PageDown,
+        NamedKey::Copy => Key::Copy,
+        NamedKey::Cut => Key::Cut,
+        NamedKey::Paste => Key::Paste,
+        NamedKey::F1 => Key::F1,
+        NamedKey::F2 => Key::F2,
+        NamedKey::F3 => Key::F3,
+        NamedKey::F4 => Key::F4,
+        NamedKey::F5 => Key::F5,
+        NamedKey::F6 => Key::F6,
+        NamedKey::F7 => Key::F7,
+        NamedKey::F8 => Key::F8,
+        NamedKey::F9 => Key::F9,
+        NamedKey::F10 => Key::F10,
+        NamedKey::F11 => Key::F11,
+        NamedKey::F12 => Key::F12,
+        NamedKey::F13 => Key::F13,
+        NamedKey::F14 => Key::F14,
+        NamedKey::F15 => Key::F15,
+        NamedKey::F16 => Key::F16,
+        NamedKey::F17 => Key::F17,
+        NamedKey::F18 => Key::F18,
+        NamedKey::F19 => Key::F19,
+        NamedKey::F20 => Key::F20,
+        NamedKey::F21 => Key::F21,
+        NamedKey::F22 => Key::F22,
+        NamedKey::F23 => Key::F23,
+        NamedKey::F24 => Key::F24,
+        NamedKey::F25 => Key::F25,
+        NamedKey::F26 => Key::F26,
+        NamedKey::F27 => Key::F27,
+        NamedKey::F28 => Key::F28,
+        NamedKey::F29 => Key::F29,
+        NamedKey::F30 => Key::F30,
+        NamedKey::F31 => Key::F31,
+        NamedKey::F32 => Key::F32,
+        NamedKey::F33 => Key::F33,
+        NamedKey::F34 => Key::F34,
+        NamedKey::F35 => Key::F35,
+        _ => return None,
+    })
+}
+
+/// Keys by their position on a US layout.
+fn translate_key_code(code: KeyCode) -> Option<Key> {
+    Some(match code {
+        KeyCode::ArrowDown => Key::ArrowDown,
+        KeyCode::ArrowLeft => Key::ArrowLeft,
+        KeyCode::ArrowRight => Key::ArrowRight,
+        KeyCode::ArrowUp => Key::ArrowUp,
+        KeyCode::Escape => Key::Escape,
+        KeyCode::Tab => Key::Tab,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Enter => Key::Enter,
+        KeyCode::Space => Key::Space,
+        KeyCode::Insert => Key::Insert,
+        KeyCode::Delete => Key::Delete,
+        KeyCode::Home => Key::Home,
+        KeyCode::End => Key::End,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::Copy => Key::Copy,
+        KeyCode::Cut => Key::Cut,
+        KeyCode::Paste => Key::Paste,
+        KeyCode::NumpadEnter => Key::Enter,
+        KeyCode::Digit0 => Key::Num0,
+        KeyCode::Digit1 => Key::Num1,
+        KeyCode::Digit2 => Key::Num2,
+        KeyCode::Digit3 => Key::Num3,
+        KeyCode::Digit4 => Key::Num4,
+        KeyCode::Digit5 => Key::Num5,
+        KeyCode::Digit6 => Key::Num6,
+        KeyCode::Digit7 => Key::Num7,
+        KeyCode::Digit8 => Key::Num8,
+        KeyCode::Digit9 => Key::Num9,
+        KeyCode::Numpad0 => Key::Num0,
+        KeyCode::Numpad1 => Key::Num1,
+        KeyCode::Numpad2 => Key::Num2,
+        KeyCode::Numpad3 => Key::Num3,
+        KeyCode::Numpad4 => Key::Num4,
+        KeyCode::Numpad5 => Key::Num5,
+        KeyCode::Numpad6 => Key::Num6,
+        KeyCode::Numpad7 => Key::Num7,
+        KeyCode::Numpad8 => Key::Num8,
+        KeyCode::Numpad9 => Key::Num9,
+        KeyCode::KeyA => Key::A,
+        KeyCode::KeyB => Key::B,
+        KeyCode::KeyC => Key::C,
+        KeyCode::KeyD => Key::D,
+        KeyCode::KeyE => Key::E,
+        KeyCode::KeyF => Key::F,
+        KeyCode::KeyG => Key::G,
+        KeyCode::KeyH => Key::H,
+        KeyCode::KeyI => Key::I,
+        KeyCode::KeyJ => Key::J,
+        KeyCode::KeyK => Key::K,
+        KeyCode::KeyL => Key::L,
+        KeyCode::KeyM => Key::M,
+        KeyCode::KeyN => Key::N,
+        KeyCode::KeyO => Key::O,
+        KeyCode::KeyP => Key::P,
+        KeyCode::KeyQ => Key::Q,
+        KeyCode::KeyR => Key::R,
+        KeyCode::KeyS => Key::S,
+        KeyCode::KeyT => Key::T,
+        KeyCode::KeyU => Key::U,
+        KeyCode::KeyV => Key::V,
+        KeyCode::KeyW => Key::W,
+        KeyCode::KeyX => Key::X,
+        KeyCode::KeyY => Key::Y,
+        KeyCode::KeyZ => Key::Z,
+        KeyCode::F1 => Key::F1,
+        KeyCode::F2 => Key::F2,
+        KeyCode::F3 => Key::F3,
+        KeyCode::F4 => Key::F4,
+        KeyCode::F5 => Key::F5,
+        KeyCode::F6 => Key::F6,
+        KeyCode::F7 => Key::F7,
+        KeyCode::F8 => Key::F8,
+        KeyCode::F9 => Key::F9,
+        KeyCode::F10 => Key::F10,
+        KeyCode::F11 => Key::F11,
+        KeyCode::F12 => Key::F12,
+        KeyCode::F13 => Key::F13,
+        KeyCode::F14 => Key::F14,
+        KeyCode::F15 => Key::F15,
+        KeyCode::F16 => Key::F16,
+        KeyCode::F17 => Key::F17,
+        KeyCode::F18 => Key::F18,
+        KeyCode::F19 => Key::F19,
+        KeyCode::F20 => Key::F20,
+        KeyCode::F21 => Key::F21,
+        KeyCode::F22 => Key::F22,
+        KeyCode::F23 => Key::F23,
+        KeyCode::F24 => Key::F24,
+        KeyCode::F25 => Key::F25,
+        KeyCode::F26 => Key::F26,
+        KeyCode::F27 => Key::F27,
+        KeyCode::F28 => Key::F28,
+        KeyCode::F29 => Key::F29,
+        KeyCode::F30 => Key::F30,
+        KeyCode::F31 => Key::F31,
+        KeyCode::F32 => Key::F32,
+        KeyCode::F33 => Key::F33,
+        KeyCode::F34 => Key::F34,
+        KeyCode::F35 => Key::F35,
+        KeyCode::Comma => Key::Comma,
+        KeyCode::Backslash => Key::Backslash,
+        KeyCode::Slash => Key::Slash,
+        KeyCode::NumpadDivide => Key::Slash,
+        KeyCode::BracketLeft => Key::OpenBracket,
+        KeyCode::BracketRight => Key::CloseBracket,
+        KeyCode::Backquote => Key::Backtick,
+        KeyCode::Minus => Key::Minus,
+        KeyCode::NumpadSubtract => Key::Minus,
+        KeyCode::Period => Key::Period,
+        KeyCode::NumpadDecimal => Key::Period,
+        KeyCode::NumpadAdd => Key::Plus,
+        KeyCode::Equal => Key::Equals,
+        KeyCode::NumpadEqual => Key::Equals,
+        KeyCode::Semicolon => Key::Semicolon,
+        KeyCode::Quote => Key::Quote,
+        _ => return None,
+    })
+}
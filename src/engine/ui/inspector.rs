@@ -0,0 +1,448 @@
+use std::{collections::VecDeque, time::Duration};
+
+use ash::vk::{self, ColorSpaceKHR, Extent2D, Format, PresentModeKHR};
+use egui::{CollapsingHeader, Color32, Grid, Pos2, Sense, Shape, Stroke, Ui};
+
+use crate::engine::{
+    config::{MsaaSamples, MAX_FRAMES_IN_FLIGHT, MAX_RENDER_SCALE, MIN_FRAMES_IN_FLIGHT, MIN_RENDER_SCALE},
+    features::EnabledFeatures,
+    memory::HeapBudget,
+    physical_devices::DeviceInfo,
+    profiler::{GpuProfiler, TimingStats},
+    queries::QueryResults,
+    queues::QueueIndices,
+    scene::culling::CullingStats,
+};
+
+const FRAME_HISTORY_LENGTH: usize = 240;
+
+pub struct SwapchainInfo {
+    pub format: Format,
+    pub color_space: ColorSpaceKHR,
+    pub present_mode: PresentModeKHR,
+    pub image_count: usize,
+    pub extent: Extent2D,
+}
+
+/// Engine state the inspector shows, gathered right before the UI runs.
+pub struct InspectorSnapshot<'a> {
+    pub device_info: &'a DeviceInfo,
+    pub enabled_features: &'a EnabledFeatures,
+    pub queue_indices: QueueIndices,
+    pub swapchain: SwapchainInfo,
+    pub render_extent: Extent2D,
+    pub frame_time: Duration,
+    pub memory_budget: Vec<HeapBudget>,
+    pub memory_budget_supported: bool,
+    pub vsync: bool,
+    pub msaa_samples: MsaaSamples,
+    pub supported_msaa_samples: Vec<MsaaSamples>,
+    pub render_scale: f32,
+    pub frames_in_flight: usize,
+    pub profiler: &'a GpuProfiler,
+    pub query_results: &'a QueryResults,
+    pub frustum_culling: bool,
+    pub culling_stats: CullingStats,
+}
+
+/// Settings changed through the inspector, applied by the engine after the UI ran.
+#[derive(Debug, Default)]
+pub struct InspectorActions {
+    pub vsync: Option<bool>,
+    pub msaa_samples: Option<MsaaSamples>,
+    pub render_scale: Option<f32>,
+    pub frames_in_flight: Option<usize>,
+    pub frustum_culling: Option<bool>,
+    /// Write the profiler's Chrome trace to disk.
+    pub export_trace: bool,
+}
+
+pub struct Inspector {
+    pub open: bool,
+    frame_times: VecDeque<f32>,
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Self {
+            open: true,
+            frame_times: VecDeque::with_capacity(FRAME_HISTORY_LENGTH),
+        }
+    }
+}
+
+impl Inspector {
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        snapshot: &InspectorSnapshot,
+        actions: &mut InspectorActions,
+    ) {
+        if self.frame_times.len() == FRAME_HISTORY_LENGTH {
+            self.frame_times.pop_front();
+        }
+        self.frame_times
+            .push_back(snapshot.frame_time.as_secs_f32() * 1000.0);
+
+        let mut open = self.open;
+        egui::Window::new("Inspector")
+            .open(&mut open)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                CollapsingHeader::new("Settings")
+                    .default_open(true)
+                    .show(ui, |ui| settings_panel(ui, snapshot, actions));
+                CollapsingHeader::new("Frame timing")
+                    .default_open(true)
+                    .show(ui, |ui| self.frame_timing_panel(ui));
+                CollapsingHeader::new("Scene")
+                    .default_open(true)
+                    .show(ui, |ui| scene_panel(ui, snapshot, actions));
+                CollapsingHeader::new("GPU passes")
+                    .show(ui, |ui| profiler_panel(ui, snapshot.profiler, actions));
+                CollapsingHeader::new("Pipeline statistics")
+                    .show(ui, |ui| statistics_panel(ui, snapshot.query_results));
+                CollapsingHeader::new("Device").show(ui, |ui| {
+                    device_panel(ui, snapshot.device_info, snapshot.enabled_features)
+                });
+                CollapsingHeader::new("Queues").show(ui, |ui| queue_panel(ui, snapshot));
+                CollapsingHeader::new("Swapchain").show(ui, |ui| swapchain_panel(ui, snapshot));
+                CollapsingHeader::new("Memory").show(ui, |ui| memory_panel(ui, snapshot));
+            });
+        self.open = open;
+    }
+
+    fn frame_timing_panel(&self, ui: &mut Ui) {
+        let count = self.frame_times.len().max(1) as f32;
+        let average = self.frame_times.iter().sum::<f32>() / count;
+        let min = self
+            .frame_times
+            .iter()
+            .copied()
+            .fold(f32::INFINITY, f32::min);
+        let max = self.frame_times.iter().copied().fold(0.0, f32::max);
+        ui.label(format!(
+            "{average:.2} ms ({:.0} fps), min {min:.2} ms, max {max:.2} ms",
+            1000.0 / average.max(f32::EPSILON)
+        ));
+
+        let (response, painter) =
+            ui.allocate_painter(egui::vec2(ui.available_width(), 80.0), Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 2.0, Color32::from_black_alpha(96));
+        // Scale to the slowest frame but never below 33 ms so a steady 60 fps isn't a flat line at the top.
+        let scale = max.max(33.3);
+        let points = self
+            .frame_times
+            .iter()
+            .enumerate()
+            .map(|(index, time)| {
+                Pos2::new(
+                    rect.left() + rect.width() * index as f32 / FRAME_HISTORY_LENGTH as f32,
+                    rect.bottom() - rect.height() * (time / scale).min(1.0),
+                )
+            })
+            .collect::<Vec<_>>();
+        painter.add(Shape::line(points, Stroke::new(1.0, Color32::LIGHT_GREEN)));
+        let target_y = rect.bottom() - rect.height() * (16.67 / scale);
+        painter.hline(
+            rect.x_range(),
+            target_y,
+            Stroke::new(1.0, Color32::from_gray(110)),
+        );
+    }
+}
+
+fn settings_panel(ui: &mut Ui, snapshot: &InspectorSnapshot, actions: &mut InspectorActions) {
+    let mut vsync = snapshot.vsync;
+    if ui.checkbox(&mut vsync, "VSync").changed() {
+        actions.vsync = Some(vsync);
+    }
+
+    let mut msaa_samples = snapshot.msaa_samples;
+    egui::ComboBox::from_label("MSAA")
+        .selected_text(format!("{msaa_samples:?}"))
+        .show_ui(ui, |ui| {
+            for samples in snapshot.supported_msaa_samples.iter() {
+                ui.selectable_value(&mut msaa_samples, *samples, format!("{samples:?}"));
+            }
+        });
+    if msaa_samples != snapshot.msaa_samples {
+        actions.msaa_samples = Some(msaa_samples);
+    }
+
+    let mut render_scale = snapshot.render_scale;
+    let response = ui.add(
+        egui::Slider::new(&mut render_scale, MIN_RENDER_SCALE..=MAX_RENDER_SCALE)
+            .text("Render scale"),
+    );
+    // Recreating the render targets on every drag step would stall each frame.
+    if response.drag_stopped() || (response.changed() && !response.dragged()) {
+        actions.render_scale = Some(render_scale);
+    }
+    ui.label(format!(
+        "Render targets: {}x{}",
+        snapshot.render_extent.width, snapshot.render_extent.height
+    ));
+
+    let mut frames_in_flight = snapshot.frames_in_flight;
+    ui.add(
+        egui::Slider::new(&mut frames_in_flight, MIN_FRAMES_IN_FLIGHT..=MAX_FRAMES_IN_FLIGHT)
+            .text("Frames in flight"),
+    );
+    if frames_in_flight != snapshot.frames_in_flight {
+        actions.frames_in_flight = Some(frames_in_flight);
+    }
+}
+
+fn scene_panel(ui: &mut Ui, snapshot: &InspectorSnapshot, actions: &mut InspectorActions) {
+    let mut frustum_culling = snapshot.frustum_culling;
+    if ui.checkbox(&mut frustum_culling, "Frustum culling").changed() {
+        actions.frustum_culling = Some(frustum_culling);
+    }
+    let stats = snapshot.culling_stats;
+    ui.label(format!(
+        "Render objects: {}, drawn {}, culled {}",
+        stats.objects,
+        stats.drawn(),
+        stats.culled
+    ));
+}
+
+fn device_panel(ui: &mut Ui, device_info: &DeviceInfo, enabled_features: &EnabledFeatures) {
+    let properties = &device_info.properties;
+    let limits = &properties.limits;
+    Grid::new("device_properties").striped(true).show(ui, |ui| {
+        let mut row = |name: &str, value: String| {
+            ui.label(name);
+            ui.label(value);
+            ui.end_row();
+        };
+        row("Name", device_info.name());
+        row("Type", format!("{:?}", properties.device_type));
+        row(
+            "API version",
+            format!(
+                "{}.{}.{}",
+                vk::api_version_major(properties.api_version),
+                vk::api_version_minor(properties.api_version),
+                vk::api_version_patch(properties.api_version)
+            ),
+        );
+        row(
+            "Driver version",
+            format!("{:#x}", properties.driver_version),
+        );
+        row(
+            "Vendor / device",
+            format!(
+                "{:#06x} / {:#06x}",
+                properties.vendor_id, properties.device_id
+            ),
+        );
+        row("Max image 2D", limits.max_image_dimension2_d.to_string());
+        row(
+            "Max push constants",
+            format!("{} B", limits.max_push_constants_size),
+        );
+        row(
+            "Max bound sets",
+            limits.max_bound_descriptor_sets.to_string(),
+        );
+        row("Max anisotropy", limits.max_sampler_anisotropy.to_string());
+        row(
+            "Max workgroup invocations",
+            limits.max_compute_work_group_invocations.to_string(),
+        );
+        row(
+            "Timestamp period",
+            format!("{} ns", limits.timestamp_period),
+        );
+        row(
+            "Min UBO alignment",
+            limits.min_uniform_buffer_offset_alignment.to_string(),
+        );
+        row(
+            "Color sample counts",
+            format!("{:?}", limits.framebuffer_color_sample_counts),
+        );
+        row(
+            "Depth sample counts",
+            format!("{:?}", limits.framebuffer_depth_sample_counts),
+        );
+    });
+
+    ui.separator();
+    Grid::new("device_features").striped(true).show(ui, |ui| {
+        ui.strong("Feature");
+        ui.strong("Supported");
+        ui.strong("Enabled");
+        ui.end_row();
+        for (feature, supported) in device_info.features.iter() {
+            ui.label(feature.name());
+            yes_no_label(ui, *supported);
+            yes_no_label(ui, enabled_features.is_enabled(*feature));
+            ui.end_row();
+        }
+    });
+    ui.separator();
+    ui.label("Enabled extensions:");
+    for extension in enabled_features.extensions.iter() {
+        ui.label(extension.to_string_lossy());
+    }
+}
+
+fn yes_no_label(ui: &mut Ui, value: bool) {
+    match value {
+        true => ui.colored_label(Color32::LIGHT_GREEN, "yes"),
+        false => ui.colored_label(Color32::LIGHT_RED, "no"),
+    };
+}
+
+fn profiler_panel(ui: &mut Ui, profiler: &GpuProfiler, actions: &mut InspectorActions) {
+    if !profiler.is_gpu_supported() {
+        ui.label("Timestamps are not supported on the graphics queue or profiling is disabled");
+    }
+    Grid::new("pass_timings").striped(true).show(ui, |ui| {
+        ui.strong("Pass");
+        ui.strong("Avg ms");
+        ui.strong("Min ms");
+        ui.strong("Max ms");
+        ui.end_row();
+        let mut timing_row = |name: &str, stats: &TimingStats| {
+            ui.label(name);
+            ui.label(format!("{:.3}", stats.average_ms));
+            ui.label(format!("{:.3}", stats.min_ms));
+            ui.label(format!("{:.3}", stats.max_ms));
+            ui.end_row();
+        };
+        timing_row("CPU frame", profiler.cpu_frame());
+        timing_row("CPU record", profiler.cpu_record());
+        if profiler.is_gpu_supported() {
+            timing_row("GPU frame", profiler.gpu_frame());
+            for pass in profiler.passes() {
+                timing_row(pass.name, &pass.gpu);
+            }
+        }
+    });
+    if ui.button("Export Chrome trace").clicked() {
+        actions.export_trace = true;
+    }
+}
+
+fn statistics_panel(ui: &mut Ui, results: &QueryResults) {
+    if results.statistics.is_empty() && results.occlusion.is_empty() {
+        ui.label("No query results, pipelineStatisticsQuery may be unsupported");
+        return;
+    }
+    ui.label(format!("Frame {}", results.frame_number));
+    for (name, statistics) in results.statistics.iter() {
+        ui.strong(*name);
+        Grid::new(("pipeline_statistics", *name)).striped(true).show(ui, |ui| {
+            for (label, value) in [
+                ("Input assembly vertices", statistics.input_assembly_vertices),
+                ("Input assembly primitives", statistics.input_assembly_primitives),
+                ("Vertex shader invocations", statistics.vertex_shader_invocations),
+                ("Clipping invocations", statistics.clipping_invocations),
+                ("Clipping primitives", statistics.clipping_primitives),
+                ("Fragment shader invocations", statistics.fragment_shader_invocations),
+                ("Compute shader invocations", statistics.compute_shader_invocations),
+            ] {
+                ui.label(label);
+                ui.label(value.to_string());
+                ui.end_row();
+            }
+        });
+    }
+    for (name, samples) in results.occlusion.iter() {
+        ui.label(format!("{name}: {samples} samples passed"));
+    }
+}
+
+fn queue_panel(ui: &mut Ui, snapshot: &InspectorSnapshot) {
+    let queue_indices = snapshot.queue_indices;
+    ui.label(format!(
+        "Graphics family: {}",
+        queue_indices.graphics_queue_index
+    ));
+    ui.label(format!(
+        "Presentation family: {:?}",
+        queue_indices.presentation_queue_index
+    ));
+    ui.label(format!(
+        "Transfer family: {:?}",
+        queue_indices.transfer_queue_index
+    ));
+    ui.label(format!(
+        "Compute family: {:?}",
+        queue_indices.compute_queue_index
+    ));
+    ui.separator();
+    Grid::new("queue_families").striped(true).show(ui, |ui| {
+        ui.strong("Family");
+        ui.strong("Flags");
+        ui.strong("Count");
+        ui.strong("Timestamp bits");
+        ui.end_row();
+        for (index, family) in snapshot.device_info.queue_families.iter().enumerate() {
+            ui.label(index.to_string());
+            ui.label(format!("{:?}", family.queue_flags));
+            ui.label(family.queue_count.to_string());
+            ui.label(family.timestamp_valid_bits.to_string());
+            ui.end_row();
+        }
+    });
+}
+
+fn swapchain_panel(ui: &mut Ui, snapshot: &InspectorSnapshot) {
+    let swapchain = &snapshot.swapchain;
+    Grid::new("swapchain").striped(true).show(ui, |ui| {
+        ui.label("Format");
+        ui.label(format!("{:?}", swapchain.format));
+        ui.end_row();
+        ui.label("Color space");
+        ui.label(format!("{:?}", swapchain.color_space));
+        ui.end_row();
+        ui.label("Present mode");
+        ui.label(format!("{:?}", swapchain.present_mode));
+        ui.end_row();
+        ui.label("Images");
+        ui.label(swapchain.image_count.to_string());
+        ui.end_row();
+        ui.label("Extent");
+        ui.label(format!(
+            "{}x{}",
+            swapchain.extent.width, swapchain.extent.height
+        ));
+        ui.end_row();
+    });
+}
+
+fn memory_panel(ui: &mut Ui, snapshot: &InspectorSnapshot) {
+    if !snapshot.memory_budget_supported {
+        ui.label("VK_EXT_memory_budget is not available, showing heap sizes only.");
+    }
+    for (index, heap) in snapshot.memory_budget.iter().enumerate() {
+        let kind = match heap.device_local {
+            true => "device local",
+            false => "host",
+        };
+        ui.label(format!(
+            "Heap {index} ({kind}): {} budget of {}",
+            format_bytes(heap.budget),
+            format_bytes(heap.size)
+        ));
+        if let Some(usage) = heap.usage {
+            let fraction = usage as f32 / heap.budget.max(1) as f32;
+            ui.add(egui::ProgressBar::new(fraction).text(format!("{} used", format_bytes(usage))));
+        }
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const MIB: f64 = 1024.0 * 1024.0;
+    match bytes as f64 / MIB {
+        mib if mib >= 1024.0 => format!("{:.2} GiB", mib / 1024.0),
+        mib => format!("{mib:.1} MiB"),
+    }
+}
@@ -0,0 +1,192 @@
+use std::time::Instant;
+
+use anyhow::Error;
+use ash::{
+    vk::{
+        DescriptorSet, DescriptorSetLayout, Extent2D, Format, ImageView,
+        PhysicalDeviceMemoryProperties, PipelineCache,
+    },
+    Device,
+};
+use egui::{ClippedPrimitive, TexturesDelta};
+use input::UiInput;
+use renderer::UiRenderer;
+use winit::{event::WindowEvent, window::Window};
+
+use super::{command_encoder::CommandEncoder, debug_names::DebugNames};
+
+mod input;
+pub mod inspector;
+mod renderer;
+
+/// Output of the last [`Ui::run`] that has not been drawn yet.
+struct UiFrameOutput {
+    primitives: Vec<ClippedPrimitive>,
+    textures_delta: TexturesDelta,
+    pixels_per_point: f32,
+}
+
+/// Immediate-mode debug UI drawn by the engine on top of the scene.
+pub struct Ui {
+    context: egui::Context,
+    input: UiInput,
+    renderer: UiRenderer,
+    output: Option<UiFrameOutput>,
+    start_time: Instant,
+}
+
+impl Ui {
+    pub fn new(
+        device: &Device,
+        memory_properties: &PhysicalDeviceMemoryProperties,
+        global_set_layout: DescriptorSetLayout,
+        color_format: Format,
+        frames_in_flight: usize,
+        max_texture_side: usize,
+        pipeline_cache: PipelineCache,
+    ) -> Result<Ui, Error> {
+        Ok(Ui {
+            context: egui::Context::default(),
+            input: UiInput::new(max_texture_side),
+            renderer: UiRenderer::new(
+                device,
+                memory_properties,
+                global_set_layout,
+                color_format,
+                frames_in_flight,
+                pipeline_cache,
+            )?,
+            output: None,
+            start_time: Instant::now(),
+        })
+    }
+
+    /// Feeds `event` to egui. Returns `true` if egui wants to keep the event for itself,
+    /// e.g. because the pointer is over a window or a text field has focus.
+    pub fn handle_window_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        self.input.handle_window_event(window, event);
+        match event {
+            WindowEvent::CursorMoved { .. }
+            | WindowEvent::MouseInput { .. }
+            | WindowEvent::MouseWheel { .. } => self.context.wants_pointer_input(),
+            WindowEvent::KeyboardInput { .. } => self.context.wants_keyboard_input(),
+            _ => false,
+        }
+    }
+
+    /// Runs one egui frame and keeps the tessellated result for the next draw.
+    pub fn run(&mut self, window: &Window, run_ui: impl FnMut(&egui::Context)) {
+        let raw_input = self
+            .input
+            .take_raw_input(window, self.start_time.elapsed().as_secs_f64());
+        let full_output = self.context.run(raw_input, run_ui);
+        window.set_cursor(translate_cursor_icon(full_output.platform_output.cursor_icon));
+
+        let primitives = self
+            .context
+            .tessellate(full_output.shapes, full_output.pixels_per_point);
+        // Texture changes of a frame that was never drawn still have to be applied.
+        let mut textures_delta = full_output.textures_delta;
+        if let Some(previous) = self.output.take() {
+            let mut previous_delta = previous.textures_delta;
+            previous_delta.append(textures_delta);
+            textures_delta = previous_delta;
+        }
+        self.output = Some(UiFrameOutput {
+            primitives,
+            textures_delta,
+            pixels_per_point: full_output.pixels_per_point,
+        });
+    }
+
+    /// Adds or removes frame slots. The device has to be idle.
+    pub fn set_frames_in_flight(&mut self, device: &Device, frames_in_flight: usize) -> Result<(), Error> {
+        self.renderer.set_frames_in_flight(device, frames_in_flight)
+    }
+
+    /// Must be called once the fence of `frame_index` has been waited on.
+    pub fn begin_frame(&mut self, device: &Device, frame_index: usize) {
+        self.renderer.begin_frame(device, frame_index);
+    }
+
+    /// Uploads texture changes and draws the last UI frame onto `target_view`,
+    /// which has to be in COLOR_ATTACHMENT_OPTIMAL.
+    #[allow(clippy::too_many_arguments)]
+    pub fn record(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        frame_index: usize,
+        target_view: ImageView,
+        target_extent: Extent2D,
+        global_descriptor: DescriptorSet,
+    ) -> Result<(), Error> {
+        let Some(output) = self.output.take() else {
+            return Ok(());
+        };
+        if let Err(err) = self
+            .renderer
+            .update_textures(device, encoder, frame_index, &output.textures_delta)
+        {
+            // Kept for the next frame, egui doesn't send texture changes twice. Setting
+            // whatever was already applied again is harmless.
+            self.output = Some(output);
+            return Err(err);
+        }
+        let result = self.renderer.render(
+            device,
+            encoder,
+            frame_index,
+            target_view,
+            target_extent,
+            &output.primitives,
+            output.pixels_per_point,
+            global_descriptor,
+        );
+        // The textures are up to date even if drawing failed.
+        self.renderer
+            .free_textures(frame_index, &output.textures_delta.free);
+        result
+    }
+
+    pub fn name_objects(&self, names: &DebugNames) {
+        self.renderer.name_objects(names);
+    }
+
+    pub fn destroy(&mut self, device: &Device) {
+        self.renderer.destroy(device);
+    }
+}
+
+fn translate_cursor_icon(cursor_icon: egui::CursorIcon) -> winit::window::CursorIcon {
+    use egui::CursorIcon as Egui;
+    use winit::window::CursorIcon as Winit;
+    match cursor_icon {
+        Egui::PointingHand => Winit::Pointer,
+        Egui::Text => Winit::Text,
+        Egui::VerticalText => Winit::VerticalText,
+        Egui::Crosshair => Winit::Crosshair,
+        Egui::Move => Winit::Move,
+        Egui::Grab => Winit::Grab,
+        Egui::Grabbing => Winit::Grabbing,
+        Egui::NotAllowed | Egui::NoDrop => Winit::NotAllowed,
+        Egui::Wait => Winit::Wait,
+        Egui::Progress => Winit::Progress,
+        Egui::Help => Winit::Help,
+        Egui::ResizeHorizontal | Egui::ResizeColumn => Winit::EwResize,
+        Egui::ResizeVertical | Egui::ResizeRow => Winit::NsResize,
+        Egui::ResizeNeSw => Winit::NeswResize,
+        Egui::ResizeNwSe => Winit::NwseResize,
+        Egui::ResizeEast => Winit::EResize,
+        Egui::ResizeWest => Winit::WResize,
+        Egui::ResizeNorth => Winit::NResize,
+        Egui::ResizeSouth => Winit::SResize,
+        Egui::ResizeNorthEast => Winit::NeResize,
+        Egui::ResizeNorthWest => Winit::NwResize,
+        Egui::ResizeSouthEast => Winit::SeResize,
+        Egui::ResizeSouthWest => Winit::SwResize,
+        Egui::ZoomIn => Winit::ZoomIn,
+        Egui::ZoomOut => Winit::ZoomOut,
+        _ => Winit::Default,
+    }
+}
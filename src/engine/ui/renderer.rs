@@ -0,0 +1,593 @@
+use std::{
+    collections::HashMap,
+    mem::{offset_of, size_of},
+};
+
+use anyhow::Error;
+use ash::{
+    vk::{
+        AttachmentLoadOp, AttachmentStoreOp, BufferImageCopy, BufferUsageFlags,
+        DescriptorImageInfo, DescriptorSet, DescriptorSetLayout, DescriptorType, DeviceSize,
+        Extent2D, Extent3D, Filter, Format, ImageAspectFlags, ImageLayout,
+        ImageSubresourceLayers, ImageUsageFlags, ImageView, IndexType, MemoryPropertyFlags,
+        Offset2D, Offset3D, PhysicalDeviceMemoryProperties, Pipeline, PipelineBindPoint,
+        PipelineCache, PipelineLayout, PushConstantRange, Rect2D, RenderingAttachmentInfo,
+        RenderingInfo, SampleCountFlags, Sampler, SamplerAddressMode, SamplerCreateInfo,
+        SamplerMipmapMode, ShaderStageFlags, VertexInputAttributeDescription,
+        VertexInputBindingDescription, VertexInputRate, Viewport, WriteDescriptorSet,
+    },
+    Device,
+};
+use egui::{
+    epaint::{ImageDelta, Primitive, Vertex},
+    ClippedPrimitive, ImageData, TextureFilter, TextureId, TextureOptions, TextureWrapMode,
+    TexturesDelta,
+};
+
+use crate::engine::{
+    buffers::{create_buffer, AllocatedBuffer},
+    command_encoder::{BindCommands, CommandEncoder},
+    debug_names::DebugNames,
+    descriptors::{DescriptorAllocator, DescriptorLayoutBuilder},
+    images::{create_allocated_image, AllocatedImage},
+    pipelines::{create_pipeline_layout, include_shader, load_shader_module, BlendMode, GraphicsPipelineBuilder},
+    scene_data::bind_global_descriptor,
+};
+
+const INITIAL_VERTEX_BUFFER_SIZE: DeviceSize = 1 << 20;
+const INITIAL_INDEX_BUFFER_SIZE: DeviceSize = 1 << 18;
+const MAX_TEXTURES: u32 = 1024;
+const TEXTURE_SET: u32 = 1;
+
+struct UiTexture {
+    image: AllocatedImage,
+    sampler: Sampler,
+    descriptor_set: DescriptorSet,
+}
+
+impl UiTexture {
+    fn destroy(&self, device: &Device, descriptor_allocator: &DescriptorAllocator) {
+        let _ = descriptor_allocator.free(device, self.descriptor_set);
+        unsafe { device.destroy_sampler(self.sampler, None) };
+        self.image.destroy(device);
+    }
+}
+
+/// Per frame in flight resources. Everything in here may still be read by the GPU until
+/// that frame's fence has been waited on.
+struct UiFrameResources {
+    vertex_buffer: AllocatedBuffer,
+    index_buffer: AllocatedBuffer,
+    staging_buffers: Vec<AllocatedBuffer>,
+    retired_textures: Vec<UiTexture>,
+}
+
+impl UiFrameResources {
+    fn new(device: &Device, memory_properties: &PhysicalDeviceMemoryProperties) -> Result<UiFrameResources, Error> {
+        Ok(UiFrameResources {
+            vertex_buffer: create_host_buffer(
+                device,
+                memory_properties,
+                INITIAL_VERTEX_BUFFER_SIZE,
+                BufferUsageFlags::VERTEX_BUFFER,
+            )?,
+            index_buffer: create_host_buffer(
+                device,
+                memory_properties,
+                INITIAL_INDEX_BUFFER_SIZE,
+                BufferUsageFlags::INDEX_BUFFER,
+            )?,
+            staging_buffers: Vec::new(),
+            retired_textures: Vec::new(),
+        })
+    }
+}
+
+/// Renders tessellated egui output with a dedicated pipeline on top of an already
+/// filled color image.
+pub struct UiRenderer {
+    pipeline_layout: PipelineLayout,
+    pipeline: Pipeline,
+    texture_set_layout: DescriptorSetLayout,
+    descriptor_allocator: DescriptorAllocator,
+    memory_properties: PhysicalDeviceMemoryProperties,
+    textures: HashMap<TextureId, UiTexture>,
+    frames: Vec<UiFrameResources>,
+}
+
+impl UiRenderer {
+    pub fn new(
+        device: &Device,
+        memory_properties: &PhysicalDeviceMemoryProperties,
+        global_set_layout: DescriptorSetLayout,
+        color_format: Format,
+        frames_in_flight: usize,
+        pipeline_cache: PipelineCache,
+    ) -> Result<UiRenderer, Error> {
+        let texture_set_layout = DescriptorLayoutBuilder::default()
+            .add_binding(
+                0,
+                DescriptorType::COMBINED_IMAGE_SAMPLER,
+                ShaderStageFlags::FRAGMENT,
+            )
+            .build(device)?;
+        let descriptor_allocator = DescriptorAllocator::new(
+            device,
+            MAX_TEXTURES,
+            &[(DescriptorType::COMBINED_IMAGE_SAMPLER, 1.0)],
+        )?;
+
+        let push_constant_ranges = [PushConstantRange::default()
+            .stage_flags(ShaderStageFlags::VERTEX)
+            .offset(0)
+            .size(size_of::<[f32; 2]>() as u32)];
+        let pipeline_layout = create_pipeline_layout(
+            device,
+            global_set_layout,
+            &[texture_set_layout],
+            &push_constant_ranges,
+        )?;
+
+        let vertex_shader = load_shader_module(device, include_shader!("ui.vert"))?;
+        let fragment_shader = load_shader_module(device, include_shader!("ui.frag"))?;
+        let bindings = [VertexInputBindingDescription::default()
+            .binding(0)
+            .stride(size_of::<Vertex>() as u32)
+            .input_rate(VertexInputRate::VERTEX)];
+        let attributes = [
+            VertexInputAttributeDescription::default()
+                .location(0)
+                .binding(0)
+                .format(Format::R32G32_SFLOAT)
+                .offset(offset_of!(Vertex, pos) as u32),
+            VertexInputAttributeDescription::default()
+                .location(1)
+                .binding(0)
+                .format(Format::R32G32_SFLOAT)
+                .offset(offset_of!(Vertex, uv) as u32),
+            VertexInputAttributeDescription::default()
+                .location(2)
+                .binding(0)
+                .format(Format::R8G8B8A8_UNORM)
+                .offset(offset_of!(Vertex, color) as u32),
+        ];
+        let pipeline = GraphicsPipelineBuilder::default()
+            .shader(ShaderStageFlags::VERTEX, vertex_shader)
+            .shader(ShaderStageFlags::FRAGMENT, fragment_shader)
+            .vertex_input(&bindings, &attributes)
+            .blend_mode(BlendMode::PremultipliedAlpha)
+            .color_format(color_format)
+            .build(device, pipeline_layout, pipeline_cache);
+        unsafe {
+            device.destroy_shader_module(vertex_shader, None);
+            device.destroy_shader_module(fragment_shader, None);
+        }
+        let pipeline = pipeline?;
+
+        let frames = (0..frames_in_flight)
+            .map(|_| UiFrameResources::new(device, memory_properties))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(UiRenderer {
+            pipeline_layout,
+            pipeline,
+            texture_set_layout,
+            descriptor_allocator,
+            memory_properties: *memory_properties,
+            textures: HashMap::new(),
+            frames,
+        })
+    }
+
+    /// Adds or removes frame slots. The device has to be idle.
+    pub fn set_frames_in_flight(&mut self, device: &Device, frames_in_flight: usize) -> Result<(), Error> {
+        for frame_index in frames_in_flight..self.frames.len() {
+            self.begin_frame(device, frame_index);
+        }
+        for frame in self.frames.drain(frames_in_flight.min(self.frames.len())..) {
+            frame.vertex_buffer.destroy(device);
+            frame.index_buffer.destroy(device);
+        }
+        while self.frames.len() < frames_in_flight {
+            self.frames
+                .push(UiFrameResources::new(device, &self.memory_properties)?);
+        }
+        Ok(())
+    }
+
+    /// Releases what the previous use of this frame slot left behind. Must be called
+    /// after the frame's fence has been waited on.
+    pub fn begin_frame(&mut self, device: &Device, frame_index: usize) {
+        let frame = &mut self.frames[frame_index];
+        for buffer in frame.staging_buffers.drain(..) {
+            buffer.destroy(device);
+        }
+        for texture in frame.retired_textures.drain(..) {
+            texture.destroy(device, &self.descriptor_allocator);
+        }
+    }
+
+    /// Records uploads for new and partially updated textures. Has to be recorded outside
+    /// of a rendering scope and before [`UiRenderer::render`].
+    pub fn update_textures(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        frame_index: usize,
+        textures_delta: &TexturesDelta,
+    ) -> Result<(), Error> {
+        for (id, delta) in textures_delta.set.iter() {
+            self.update_texture(device, encoder, frame_index, *id, delta)?;
+        }
+        Ok(())
+    }
+
+    /// Textures egui no longer needs. They are destroyed once this frame slot comes around again.
+    pub fn free_textures(&mut self, frame_index: usize, ids: &[TextureId]) {
+        for id in ids {
+            if let Some(texture) = self.textures.remove(id) {
+                self.frames[frame_index].retired_textures.push(texture);
+            }
+        }
+    }
+
+    fn update_texture(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        frame_index: usize,
+        id: TextureId,
+        delta: &ImageDelta,
+    ) -> Result<(), Error> {
+        // A partial update of a texture we don't know about has nothing to write to.
+        if delta.pos.is_some() && !self.textures.contains_key(&id) {
+            return Ok(());
+        }
+        let pixels: Vec<u8> = match &delta.image {
+            ImageData::Color(image) => image.pixels.iter().flat_map(|c| c.to_array()).collect(),
+            ImageData::Font(image) => image.srgba_pixels(None).flat_map(|c| c.to_array()).collect(),
+        };
+        let [width, height] = delta.image.size();
+
+        let staging_buffer = create_buffer(
+            device,
+            &self.memory_properties,
+            pixels.len() as DeviceSize,
+            BufferUsageFlags::TRANSFER_SRC,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+        if let Err(err) = staging_buffer.write(&pixels) {
+            staging_buffer.destroy(device);
+            return Err(err);
+        }
+
+        let (image, old_layout, offset) = match delta.pos {
+            Some([x, y]) => {
+                let offset = Offset3D::default().x(x as i32).y(y as i32);
+                (self.textures[&id].image.image, ImageLayout::SHADER_READ_ONLY_OPTIMAL, offset)
+            }
+            None => {
+                let texture = match self.create_texture(device, width as u32, height as u32, delta.options) {
+                    Ok(texture) => texture,
+                    Err(err) => {
+                        staging_buffer.destroy(device);
+                        return Err(err);
+                    }
+                };
+                let image = texture.image.image;
+                if let Some(old_texture) = self.textures.insert(id, texture) {
+                    self.frames[frame_index].retired_textures.push(old_texture);
+                }
+                (image, ImageLayout::UNDEFINED, Offset3D::default())
+            }
+        };
+
+        encoder.transition_image(image, old_layout, ImageLayout::TRANSFER_DST_OPTIMAL);
+        let region = BufferImageCopy::default()
+            .image_subresource(
+                ImageSubresourceLayers::default()
+                    .aspect_mask(ImageAspectFlags::COLOR)
+                    .layer_count(1),
+            )
+            .image_offset(offset)
+            .image_extent(Extent3D {
+                width: width as u32,
+                height: height as u32,
+                depth: 1,
+            });
+        encoder.copy_buffer_to_image(
+            staging_buffer.buffer,
+            image,
+            ImageLayout::TRANSFER_DST_OPTIMAL,
+            &[region],
+        );
+        encoder.transition_image(
+            image,
+            ImageLayout::TRANSFER_DST_OPTIMAL,
+            ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+
+        self.frames[frame_index].staging_buffers.push(staging_buffer);
+        Ok(())
+    }
+
+    fn create_texture(
+        &self,
+        device: &Device,
+        width: u32,
+        height: u32,
+        options: TextureOptions,
+    ) -> Result<UiTexture, Error> {
+        let image = create_allocated_image(
+            device,
+            &self.memory_properties,
+            Extent2D { width, height },
+            Format::R8G8B8A8_UNORM,
+            ImageUsageFlags::SAMPLED | ImageUsageFlags::TRANSFER_DST,
+            SampleCountFlags::TYPE_1,
+            ImageAspectFlags::COLOR,
+        )?;
+
+        let address_mode = match options.wrap_mode {
+            TextureWrapMode::ClampToEdge => SamplerAddressMode::CLAMP_TO_EDGE,
+            TextureWrapMode::Repeat => SamplerAddressMode::REPEAT,
+            TextureWrapMode::MirroredRepeat => SamplerAddressMode::MIRRORED_REPEAT,
+        };
+        let sampler_create_info = SamplerCreateInfo::default()
+            .mag_filter(translate_filter(options.magnification))
+            .min_filter(translate_filter(options.minification))
+            .mipmap_mode(SamplerMipmapMode::LINEAR)
+            .address_mode_u(address_mode)
+            .address_mode_v(address_mode)
+            .address_mode_w(address_mode);
+        let sampler = match unsafe { device.create_sampler(&sampler_create_info, None) } {
+            Ok(sampler) => sampler,
+            Err(err) => {
+                image.destroy(device);
+                return Err(err.into());
+            }
+        };
+
+        let descriptor_set = match self.descriptor_allocator.allocate(device, self.texture_set_layout) {
+            Ok(descriptor_set) => descriptor_set,
+            Err(err) => {
+                unsafe { device.destroy_sampler(sampler, None) };
+                image.destroy(device);
+                return Err(err);
+            }
+        };
+        let image_infos = [DescriptorImageInfo::default()
+            .sampler(sampler)
+            .image_view(image.image_view)
+            .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)];
+        let write = WriteDescriptorSet::default()
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&image_infos);
+        unsafe { device.update_descriptor_sets(&[write], &[]) };
+
+        Ok(UiTexture {
+            image,
+            sampler,
+            descriptor_set,
+        })
+    }
+
+    /// Draws `primitives` on top of `target_view`, which has to be in COLOR_ATTACHMENT_OPTIMAL.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        frame_index: usize,
+        target_view: ImageView,
+        target_extent: Extent2D,
+        primitives: &[ClippedPrimitive],
+        pixels_per_point: f32,
+        global_descriptor: DescriptorSet,
+    ) -> Result<(), Error> {
+        let meshes = primitives
+            .iter()
+            .filter_map(|primitive| match &primitive.primitive {
+                Primitive::Mesh(mesh) => Some((primitive.clip_rect, mesh)),
+                Primitive::Callback(_) => None,
+            })
+            .collect::<Vec<_>>();
+        if meshes.is_empty() {
+            return Ok(());
+        }
+
+        let vertex_bytes = meshes
+            .iter()
+            .map(|(_, mesh)| (mesh.vertices.len() * size_of::<Vertex>()) as DeviceSize)
+            .sum();
+        let index_bytes = meshes
+            .iter()
+            .map(|(_, mesh)| (mesh.indices.len() * size_of::<u32>()) as DeviceSize)
+            .sum();
+        self.reserve_buffers(device, frame_index, vertex_bytes, index_bytes)?;
+
+        let frame = &self.frames[frame_index];
+        let (mut vertex_offset, mut index_offset) = (0, 0);
+        for (_, mesh) in meshes.iter() {
+            frame.vertex_buffer.write_at(vertex_offset, &mesh.vertices)?;
+            frame.index_buffer.write_at(index_offset, &mesh.indices)?;
+            vertex_offset += (mesh.vertices.len() * size_of::<Vertex>()) as DeviceSize;
+            index_offset += (mesh.indices.len() * size_of::<u32>()) as DeviceSize;
+        }
+
+        let color_attachments = [RenderingAttachmentInfo::default()
+            .image_view(target_view)
+            .image_layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .load_op(AttachmentLoadOp::LOAD)
+            .store_op(AttachmentStoreOp::STORE)];
+        let rendering_info = RenderingInfo::default()
+            .render_area(Rect2D {
+                offset: Offset2D::default(),
+                extent: target_extent,
+            })
+            .layer_count(1)
+            .color_attachments(&color_attachments);
+
+        let screen_size_in_points = [
+            target_extent.width as f32 / pixels_per_point,
+            target_extent.height as f32 / pixels_per_point,
+        ];
+        let viewport = Viewport::default()
+            .width(target_extent.width as f32)
+            .height(target_extent.height as f32)
+            .min_depth(0.0)
+            .max_depth(1.0);
+
+        let mut rendering = encoder.begin_rendering(&rendering_info);
+        rendering.bind_pipeline(PipelineBindPoint::GRAPHICS, self.pipeline);
+        bind_global_descriptor(
+            &mut rendering,
+            PipelineBindPoint::GRAPHICS,
+            self.pipeline_layout,
+            global_descriptor,
+        );
+        rendering.set_viewport(viewport);
+        rendering.push_constants(
+            self.pipeline_layout,
+            ShaderStageFlags::VERTEX,
+            0,
+            bytemuck::cast_slice(&screen_size_in_points),
+        );
+        rendering.bind_vertex_buffer(0, frame.vertex_buffer.buffer, 0);
+        rendering.bind_index_buffer(frame.index_buffer.buffer, 0, IndexType::UINT32);
+
+        let (mut first_vertex, mut first_index) = (0, 0);
+        for (clip_rect, mesh) in meshes {
+            let Some(scissor) = clip_rect_to_scissor(clip_rect, pixels_per_point, target_extent)
+            else {
+                first_vertex += mesh.vertices.len() as i32;
+                first_index += mesh.indices.len() as u32;
+                continue;
+            };
+            if let Some(texture) = self.textures.get(&mesh.texture_id) {
+                rendering.set_scissor(scissor);
+                rendering.bind_descriptor_sets(
+                    PipelineBindPoint::GRAPHICS,
+                    self.pipeline_layout,
+                    TEXTURE_SET,
+                    &[texture.descriptor_set],
+                );
+                rendering.draw_indexed(mesh.indices.len() as u32, 1, first_index, first_vertex, 0);
+            }
+            first_vertex += mesh.vertices.len() as i32;
+            first_index += mesh.indices.len() as u32;
+        }
+
+        rendering.end();
+        Ok(())
+    }
+
+    /// Grows this frame's vertex and index buffers. Only safe after the frame's fence wait.
+    fn reserve_buffers(
+        &mut self,
+        device: &Device,
+        frame_index: usize,
+        vertex_bytes: DeviceSize,
+        index_bytes: DeviceSize,
+    ) -> Result<(), Error> {
+        let frame = &mut self.frames[frame_index];
+        if frame.vertex_buffer.size < vertex_bytes {
+            frame.vertex_buffer.destroy(device);
+            frame.vertex_buffer = create_host_buffer(
+                device,
+                &self.memory_properties,
+                vertex_bytes.next_power_of_two(),
+                BufferUsageFlags::VERTEX_BUFFER,
+            )?;
+        }
+        if frame.index_buffer.size < index_bytes {
+            frame.index_buffer.destroy(device);
+            frame.index_buffer = create_host_buffer(
+                device,
+                &self.memory_properties,
+                index_bytes.next_power_of_two(),
+                BufferUsageFlags::INDEX_BUFFER,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Names the long lived objects, buffers that are grown later keep their names unset.
+    pub fn name_objects(&self, names: &DebugNames) {
+        names.name(self.pipeline, "ui pipeline");
+        names.name(self.pipeline_layout, "ui pipeline layout");
+        names.name(self.texture_set_layout, "ui texture set layout");
+        for (frame_index, frame) in self.frames.iter().enumerate() {
+            names.name_buffer(&frame.vertex_buffer, &format!("frame {frame_index} ui vertex buffer"));
+            names.name_buffer(&frame.index_buffer, &format!("frame {frame_index} ui index buffer"));
+        }
+    }
+
+    pub fn destroy(&mut self, device: &Device) {
+        for frame_index in 0..self.frames.len() {
+            self.begin_frame(device, frame_index);
+        }
+        for frame in self.frames.iter() {
+            frame.vertex_buffer.destroy(device);
+            frame.index_buffer.destroy(device);
+        }
+        for (_, texture) in self.textures.drain() {
+            texture.destroy(device, &self.descriptor_allocator);
+        }
+        self.descriptor_allocator.destroy(device);
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_descriptor_set_layout(self.texture_set_layout, None);
+        }
+    }
+}
+
+fn create_host_buffer(
+    device: &Device,
+    memory_properties: &PhysicalDeviceMemoryProperties,
+    size: DeviceSize,
+    usage: BufferUsageFlags,
+) -> Result<AllocatedBuffer, Error> {
+    create_buffer(
+        device,
+        memory_properties,
+        size,
+        usage,
+        MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+    )
+}
+
+fn translate_filter(filter: TextureFilter) -> Filter {
+    match filter {
+        TextureFilter::Nearest => Filter::NEAREST,
+        TextureFilter::Linear => Filter::LINEAR,
+    }
+}
+
+/// Converts a clip rectangle in points into a scissor in pixels, `None` if nothing is visible.
+fn clip_rect_to_scissor(
+    clip_rect: egui::Rect,
+    pixels_per_point: f32,
+    target_extent: Extent2D,
+) -> Option<Rect2D> {
+    let min_x = (clip_rect.min.x * pixels_per_point).round().clamp(0.0, target_extent.width as f32) as u32;
+    let min_y = (clip_rect.min.y * pixels_per_point).round().clamp(0.0, target_extent.height as f32) as u32;
+    let max_x = (clip_rect.max.x * pixels_per_point).round().clamp(0.0, target_extent.width as f32) as u32;
+    let max_y = (clip_rect.max.y * pixels_per_point).round().clamp(0.0, target_extent.height as f32) as u32;
+    if max_x <= min_x || max_y <= min_y {
+        return None;
+    }
+    Some(Rect2D {
+        offset: Offset2D {
+            x: min_x as i32,
+            y: min_y as i32,
+        },
+        extent: Extent2D {
+            width: max_x - min_x,
+            height: max_y - min_y,
+        },
+    })
+}
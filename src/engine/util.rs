@@ -1,40 +1,65 @@
-use anyhow::Error;
-use ash::vk::{AccessFlags2, CommandBuffer, Image, ImageAspectFlags, ImageLayout, ImageMemoryBarrier2KHR, ImageSubresourceRange, PipelineStageFlags2, REMAINING_ARRAY_LAYERS, REMAINING_MIP_LEVELS};
-use log::LevelFilter;
+use ash::vk::{
+    AccessFlags2, Extent2D, Image, ImageAspectFlags, ImageBlit2, ImageLayout,
+    ImageMemoryBarrier2KHR, ImageSubresourceLayers, ImageSubresourceRange, Offset3D,
+    PipelineStageFlags2, REMAINING_ARRAY_LAYERS, REMAINING_MIP_LEVELS,
+};
 
-pub fn transition_image(
-    command_buffer: CommandBuffer,
+/// A barrier moving all of `image` to `new_layout` that waits for every earlier write.
+/// Simple but coarse, only depth images get the depth aspect.
+pub fn image_transition_barrier(
     image: Image,
     current_layout: ImageLayout,
     new_layout: ImageLayout,
-) -> Result<(), Error> {
+) -> ImageMemoryBarrier2KHR<'static> {
     let image_aspect_flag = match new_layout == ImageLayout::DEPTH_ATTACHMENT_OPTIMAL {
         true => ImageAspectFlags::DEPTH,
-        false => ImageAspectFlags::COLOR
+        false => ImageAspectFlags::COLOR,
     };
 
-    
     let sub_resource_range = image_sub_resource_range(image_aspect_flag);
-    
-    let image_barrier = ImageMemoryBarrier2KHR::default()
+
+    ImageMemoryBarrier2KHR::default()
         .src_stage_mask(PipelineStageFlags2::ALL_COMMANDS)
         .src_access_mask(AccessFlags2::MEMORY_WRITE)
         .dst_stage_mask(PipelineStageFlags2::ALL_COMMANDS)
         .dst_access_mask(AccessFlags2::MEMORY_WRITE | AccessFlags2::MEMORY_READ)
         .old_layout(current_layout)
         .new_layout(new_layout)
+        .image(image)
         .subresource_range(sub_resource_range)
-        ;
-
-    Ok(())
 }
 
-    fn image_sub_resource_range(aspect_flag: ImageAspectFlags) -> ImageSubresourceRange {
-        ImageSubresourceRange::default()
-            .aspect_mask(aspect_flag)
-            .base_mip_level(0)
-            .level_count(REMAINING_MIP_LEVELS)
-            .base_array_layer(0)
-            .layer_count(REMAINING_ARRAY_LAYERS)
-    }
+/// A blit region scaling the whole first mip level of a color image onto another.
+pub fn full_blit_region(source_extent: Extent2D, destination_extent: Extent2D) -> ImageBlit2<'static> {
+    let subresource = ImageSubresourceLayers::default()
+        .aspect_mask(ImageAspectFlags::COLOR)
+        .mip_level(0)
+        .base_array_layer(0)
+        .layer_count(1);
+    ImageBlit2::default()
+        .src_offsets([
+            Offset3D::default(),
+            Offset3D::default()
+                .x(source_extent.width as i32)
+                .y(source_extent.height as i32)
+                .z(1),
+        ])
+        .dst_offsets([
+            Offset3D::default(),
+            Offset3D::default()
+                .x(destination_extent.width as i32)
+                .y(destination_extent.height as i32)
+                .z(1),
+        ])
+        .src_subresource(subresource)
+        .dst_subresource(subresource)
+}
 
+pub fn image_sub_resource_range(aspect_flag: ImageAspectFlags) -> ImageSubresourceRange {
+    ImageSubresourceRange::default()
+        .aspect_mask(aspect_flag)
+        .base_mip_level(0)
+        .level_count(REMAINING_MIP_LEVELS)
+        .base_array_layer(0)
+        .layer_count(REMAINING_ARRAY_LAYERS)
+}
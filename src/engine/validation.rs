@@ -0,0 +1,230 @@
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    slice,
+    sync::{Mutex, MutexGuard},
+};
+
+use ash::vk::{
+    DebugUtilsLabelEXT, DebugUtilsMessageSeverityFlagsEXT, DebugUtilsMessageTypeFlagsEXT,
+    DebugUtilsMessengerCallbackDataEXT, ObjectType,
+};
+use log::{debug, error, info, warn};
+
+use super::config::ValidationConfig;
+
+/// Unique messages kept for [`ValidationSink::messages`], later ones are only counted.
+const MAX_RECORDED_MESSAGES: usize = 256;
+
+/// An object a validation message refers to.
+#[derive(Debug, Clone)]
+pub struct ValidationObject {
+    pub object_type: ObjectType,
+    pub handle: u64,
+    pub name: Option<String>,
+}
+
+/// Everything the driver or a layer passed to the debug utils messenger.
+#[derive(Debug, Clone)]
+pub struct ValidationMessage {
+    pub severity: DebugUtilsMessageSeverityFlagsEXT,
+    pub message_type: DebugUtilsMessageTypeFlagsEXT,
+    pub id_name: String,
+    pub id_number: i32,
+    pub message: String,
+    pub objects: Vec<ValidationObject>,
+    pub queue_labels: Vec<String>,
+    pub command_buffer_labels: Vec<String>,
+    /// How often this exact message was reported.
+    pub count: u32,
+}
+
+impl ValidationMessage {
+    /// Copies the callback data, all of it is only valid during the callback.
+    ///
+    /// # Safety
+    /// `data` has to be the callback data of a debug utils messenger callback.
+    pub unsafe fn from_callback_data(
+        severity: DebugUtilsMessageSeverityFlagsEXT,
+        message_type: DebugUtilsMessageTypeFlagsEXT,
+        data: &DebugUtilsMessengerCallbackDataEXT,
+    ) -> ValidationMessage {
+        let objects = unsafe { callback_slice(data.p_objects, data.object_count) }
+            .iter()
+            .map(|object| ValidationObject {
+                object_type: object.object_type,
+                handle: object.object_handle,
+                name: unsafe { object.object_name_as_c_str() }.map(lossy),
+            })
+            .collect();
+        ValidationMessage {
+            severity,
+            message_type,
+            id_name: unsafe { data.message_id_name_as_c_str() }
+                .map(lossy)
+                .unwrap_or_default(),
+            id_number: data.message_id_number,
+            message: unsafe { data.message_as_c_str() }.map(lossy).unwrap_or_default(),
+            objects,
+            queue_labels: unsafe { labels(data.p_queue_labels, data.queue_label_count) },
+            command_buffer_labels: unsafe { labels(data.p_cmd_buf_labels, data.cmd_buf_label_count) },
+            count: 1,
+        }
+    }
+
+    pub fn is_validation_error(&self) -> bool {
+        self.severity.contains(DebugUtilsMessageSeverityFlagsEXT::ERROR)
+            && self.message_type.contains(DebugUtilsMessageTypeFlagsEXT::VALIDATION)
+    }
+
+    pub fn log(&self) {
+        let mut text = format!(
+            "{:?} [{} ({})] : {}",
+            self.message_type, self.id_name, self.id_number, self.message
+        );
+        for object in self.objects.iter() {
+            text += &format!(
+                "\n  object {:?} {:#x} {}",
+                object.object_type,
+                object.handle,
+                object.name.as_deref().unwrap_or("(unnamed)")
+            );
+        }
+        if !self.queue_labels.is_empty() {
+            text += &format!("\n  queue labels: {}", self.queue_labels.join(" > "));
+        }
+        if !self.command_buffer_labels.is_empty() {
+            text += &format!("\n  command buffer labels: {}", self.command_buffer_labels.join(" > "));
+        }
+        match self.severity {
+            DebugUtilsMessageSeverityFlagsEXT::ERROR => error!("{text}"),
+            DebugUtilsMessageSeverityFlagsEXT::WARNING => warn!("{text}"),
+            DebugUtilsMessageSeverityFlagsEXT::INFO => info!("{text}"),
+            _ => debug!("{text}"),
+        }
+    }
+}
+
+/// Messages received so far, repeats of a message included.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValidationCounts {
+    pub errors: u32,
+    pub warnings: u32,
+    pub infos: u32,
+    pub verbose: u32,
+    /// Repeats of an earlier message, which are not logged again.
+    pub duplicates: u32,
+    /// Messages dropped because their ID is in [`ValidationConfig::ignored_message_ids`].
+    pub ignored: u32,
+}
+
+impl ValidationCounts {
+    pub fn total(&self) -> u32 {
+        self.errors + self.warnings + self.infos + self.verbose
+    }
+}
+
+#[derive(Default)]
+struct SinkState {
+    counts: ValidationCounts,
+    messages: Vec<ValidationMessage>,
+    /// `(id_number, message)` to the position in `messages`, if it was recorded.
+    seen: HashMap<(i32, String), Option<usize>>,
+    /// The first validation error with [`ValidationConfig::fail_on_error`] set, until taken.
+    first_error: Option<ValidationMessage>,
+}
+
+/// Receives the messages of the debug utils messenger through its user data pointer.
+/// The messenger may call from any thread, so the state sits behind a mutex.
+pub struct ValidationSink {
+    ignored_message_ids: Vec<String>,
+    fail_on_error: bool,
+    state: Mutex<SinkState>,
+}
+
+impl ValidationSink {
+    pub fn new(config: &ValidationConfig) -> ValidationSink {
+        ValidationSink {
+            ignored_message_ids: config.ignored_message_ids.clone(),
+            fail_on_error: config.fail_on_error,
+            state: Mutex::default(),
+        }
+    }
+
+    pub fn handle(&self, message: ValidationMessage) {
+        let mut state = self.state();
+        if self.is_ignored(&message) {
+            state.counts.ignored += 1;
+            return;
+        }
+        match message.severity {
+            DebugUtilsMessageSeverityFlagsEXT::ERROR => state.counts.errors += 1,
+            DebugUtilsMessageSeverityFlagsEXT::WARNING => state.counts.warnings += 1,
+            DebugUtilsMessageSeverityFlagsEXT::INFO => state.counts.infos += 1,
+            _ => state.counts.verbose += 1,
+        }
+
+        let key = (message.id_number, message.message.clone());
+        if let Some(recorded) = state.seen.get(&key).copied() {
+            state.counts.duplicates += 1;
+            if let Some(index) = recorded {
+                state.messages[index].count += 1;
+            }
+            return;
+        }
+        message.log();
+        let recorded = (state.messages.len() < MAX_RECORDED_MESSAGES).then_some(state.messages.len());
+        state.seen.insert(key, recorded);
+        // The callback can't unwind, so the error is handed to the engine instead of panicking.
+        if self.fail_on_error && message.is_validation_error() && state.first_error.is_none() {
+            state.first_error = Some(message.clone());
+        }
+        if recorded.is_some() {
+            state.messages.push(message);
+        }
+    }
+
+    pub fn counts(&self) -> ValidationCounts {
+        self.state().counts
+    }
+
+    /// The first occurrence of every distinct message, up to a limit.
+    pub fn messages(&self) -> Vec<ValidationMessage> {
+        self.state().messages.clone()
+    }
+
+    /// The first validation error since the last call, only recorded with
+    /// [`ValidationConfig::fail_on_error`] set.
+    pub fn take_first_error(&self) -> Option<ValidationMessage> {
+        self.state().first_error.take()
+    }
+
+    fn is_ignored(&self, message: &ValidationMessage) -> bool {
+        self.ignored_message_ids.iter().any(|id| {
+            *id == message.id_name || id.parse::<i32>() == Ok(message.id_number)
+        })
+    }
+
+    /// Nothing leaves the state half updated, so it is still usable after a poisoning panic.
+    fn state(&self) -> MutexGuard<'_, SinkState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn lossy(string: &CStr) -> String {
+    string.to_string_lossy().into_owned()
+}
+
+unsafe fn callback_slice<'a, T>(pointer: *const T, count: u32) -> &'a [T] {
+    match pointer.is_null() {
+        true => &[],
+        false => unsafe { slice::from_raw_parts(pointer, count as usize) },
+    }
+}
+
+unsafe fn labels(pointer: *const DebugUtilsLabelEXT, count: u32) -> Vec<String> {
+    unsafe { callback_slice(pointer, count) }
+        .iter()
+        .filter_map(|label| unsafe { label.label_name_as_c_str() }.map(lossy))
+        .collect()
+}
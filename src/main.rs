@@ -1,15 +1,17 @@
+use anyhow::{Context, Error};
 use app::App;
 use log::LevelFilter;
-use winit::event_loop::{ActiveEventLoop, EventLoop};
+use winit::event_loop::{ControlFlow, EventLoop};
 
 mod app;
+mod demo;
 mod engine;
 
-fn main() {
-    let mut app = App::default();
-    let event_loop = EventLoop::new().unwrap();
+fn main() -> Result<(), Error> {
     let _ = env_logger::builder().filter_level(LevelFilter::Debug).try_init();
-    event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
-    event_loop.run_app(&mut app).unwrap();
-    println!("Hello, world!");
+    let mut app = App::default();
+    let event_loop = EventLoop::new().context("Failed to create the event loop")?;
+    event_loop.set_control_flow(ControlFlow::Poll);
+    event_loop.run_app(&mut app).context("Event loop failed")?;
+    Ok(())
 }